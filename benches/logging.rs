@@ -0,0 +1,161 @@
+//! Benchmarks of the logging path, run with `cargo bench --features test-util`.
+//!
+//! Loggers write to a `NullAppender`, so appender I/O doesn't dominate the measurements.
+//! The benchmarks are skipped when no JavaVM can be created, e.g. when the log4j jars cannot be provisioned
+
+use criterion::{black_box, criterion_group, Criterion};
+use jni::objects::JObject;
+use jni::JNIEnv;
+use log4j::test_support::TestJvm;
+use log4j::{JavaLogger, LogLevel};
+use std::sync::Barrier;
+use std::time::{Duration, Instant};
+
+const NULL_APPENDER_CLASS: &str = "org/apache/log4j/varia/NullAppender";
+/// Number of threads in the contention benchmark
+const THREADS: u64 = 8;
+/// Log calls per local reference frame, every call creates a local reference for its message
+const CALLS_PER_FRAME: u64 = 1000;
+/// Size of the message in the large message benchmarks
+const LARGE_MESSAGE_BYTES: usize = 1024 * 1024;
+
+/// Create a logger whose only appender is a NullAppender
+fn null_logger<'a>(env: &'a JNIEnv<'a>, name: &str) -> JavaLogger<'a> {
+    let logger = JavaLogger::new(env, name).expect("Failed to create JavaLogger");
+    logger
+        .reconfigure(|ctx| {
+            for appender in ctx.appenders()? {
+                ctx.remove_appender(appender)?;
+            }
+
+            let null_appender = ctx.env().new_object(NULL_APPENDER_CLASS, "()V", &[])?;
+            ctx.add_appender(null_appender)?;
+            ctx.env().call_method(ctx.logger(), "setAdditivity", "(Z)V", &[false.into()])?;
+            Ok(())
+        })
+        .expect("Failed to attach NullAppender");
+    logger
+}
+
+/// Run `f` `iterations` times, freeing the created local references every `CALLS_PER_FRAME` calls
+fn timed<F: Fn()>(env: &JNIEnv, iterations: u64, f: F) -> Duration {
+    let start = Instant::now();
+    let mut remaining = iterations;
+    while remaining > 0 {
+        let calls = remaining.min(CALLS_PER_FRAME);
+        env.with_local_frame(CALLS_PER_FRAME as i32, || {
+            (0..calls).for_each(|_| f());
+            Ok(JObject::null())
+        })
+        .expect("Failed to use local frame");
+        remaining -= calls;
+    }
+    start.elapsed()
+}
+
+/// Log `iterations` times
+fn log_n(env: &JNIEnv, logger: &JavaLogger, level: LogLevel, iterations: u64) -> Duration {
+    timed(env, iterations, || logger.log(level, black_box("Benchmark message")).expect("Failed to log"))
+}
+
+fn single_threaded(c: &mut Criterion) {
+    let vm = TestJvm::get();
+    let env = vm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+    let logger = null_logger(&env, "bench.SingleThreaded");
+
+    c.bench_function("log", |b| b.iter_custom(|iterations| log_n(&env, &logger, LogLevel::Info, iterations)));
+}
+
+fn disabled_level(c: &mut Criterion) {
+    let vm = TestJvm::get();
+    let env = vm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+    let logger = null_logger(&env, "bench.Disabled");
+    let info = LogLevel::Info.to_java_level(&env).expect("Failed to get INFO level");
+    logger
+        .reconfigure(|ctx| {
+            ctx.env().call_method(ctx.logger(), "setLevel", "(Lorg/apache/log4j/Level;)V", &[info.into()])?;
+            Ok(())
+        })
+        .expect("Failed to set level");
+
+    c.bench_function("log disabled level", |b| b.iter_custom(|iterations| log_n(&env, &logger, LogLevel::Debug, iterations)));
+}
+
+fn contention(c: &mut Criterion) {
+    let vm = TestJvm::get();
+
+    c.bench_function("log 8 threads", |b| {
+        b.iter_custom(|iterations| {
+            let per_thread = (iterations / THREADS).max(1);
+            let barrier = Barrier::new(THREADS as usize);
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..THREADS)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            let env = vm.attach_current_thread().expect("Failed to attach thread to the JVM");
+                            let logger = null_logger(&env, "bench.Contention");
+                            barrier.wait();
+                            log_n(&env, &logger, LogLevel::Info, per_thread)
+                        })
+                    })
+                    .collect();
+
+                handles.into_iter().map(|handle| handle.join().expect("Logging thread panicked")).max().unwrap_or_default()
+            })
+        })
+    });
+}
+
+fn large_message(c: &mut Criterion) {
+    let vm = TestJvm::get();
+    let env = vm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+    let logger = null_logger(&env, "bench.LargeMessage");
+
+    // Mostly supplementary-plane characters, which are surrogate pairs in UTF-16
+    let mut message = String::with_capacity(LARGE_MESSAGE_BYTES);
+    while message.len() < LARGE_MESSAGE_BYTES {
+        message.push_str("\u{1F600}\u{1F680} ");
+    }
+    let utf16: Vec<u16> = message.encode_utf16().collect();
+
+    let mut group = c.benchmark_group("1 MB message");
+    group.sample_size(20);
+    group.bench_function("log from UTF-16 via String", |b| {
+        b.iter_custom(|iterations| timed(&env, iterations, || logger.log(LogLevel::Info, String::from_utf16(black_box(&utf16)).unwrap()).expect("Failed to log")))
+    });
+    group.bench_function("log_utf16", |b| {
+        b.iter_custom(|iterations| timed(&env, iterations, || logger.log_utf16(LogLevel::Info, black_box(&utf16)).expect("Failed to log")))
+    });
+    group.finish();
+}
+
+fn constructor(c: &mut Criterion) {
+    let vm = TestJvm::get();
+    let env = vm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+    c.bench_function("JavaLogger::new", |b| {
+        b.iter_custom(|iterations| {
+            timed(&env, iterations, || {
+                black_box(JavaLogger::new(&env, "bench.Constructor").expect("Failed to create JavaLogger"));
+            })
+        })
+    });
+}
+
+criterion_group!(benches, single_threaded, disabled_level, contention, large_message, constructor);
+
+fn main() {
+    // Silence the panic message of TestJvm::get, a missing JVM is reported below
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let available = std::panic::catch_unwind(TestJvm::get).is_ok();
+    std::panic::set_hook(hook);
+
+    if !available {
+        eprintln!("Skipping benchmarks, no JavaVM is available");
+        return;
+    }
+
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+}
@@ -0,0 +1,1104 @@
+use crate::error::{Error, Result};
+use crate::level::LogLevel;
+use crate::logger::{InnerLogger, JavaLogger};
+use crate::path::{path_to_java_string, PathConversion};
+use crate::pattern::{check_syntax, Pattern};
+use crate::sys;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use std::path::{Path, PathBuf};
+
+/// Fully qualified name of the class log4j considers the caller boundary for location information
+const CATEGORY_FQN: &str = "org.apache.log4j.Category";
+const PATTERN_LAYOUT_CLASS: &str = "org/apache/log4j/PatternLayout";
+const SIMPLE_LAYOUT_CLASS: &str = "org/apache/log4j/SimpleLayout";
+const WRITER_APPENDER_CLASS: &str = "org/apache/log4j/WriterAppender";
+const CONSOLE_APPENDER_CLASS: &str = "org/apache/log4j/ConsoleAppender";
+const FILE_APPENDER_CLASS: &str = "org/apache/log4j/FileAppender";
+const STRING_WRITER_CLASS: &str = "java/io/StringWriter";
+const LOGGING_EVENT_CLASS: &str = "org/apache/log4j/spi/LoggingEvent";
+const LEVEL_MATCH_FILTER_CLASS: &str = "org/apache/log4j/varia/LevelMatchFilter";
+const DENY_ALL_FILTER_CLASS: &str = "org/apache/log4j/varia/DenyAllFilter";
+const APPENDER_SKELETON_CLASS: &str = "org/apache/log4j/AppenderSkeleton";
+const ROLLING_FILE_APPENDER_CLASS: &str = "org/apache/log4j/RollingFileAppender";
+const OPTION_HANDLER_CLASS: &str = "org/apache/log4j/spi/OptionHandler";
+
+impl<'a> JavaLogger<'a> {
+    /// Attach a console appender writing to `System.out`
+    ///
+    /// # Params
+    /// - `pattern` The conversion pattern for the `PatternLayout`
+    ///
+    /// # Error
+    /// - If the pattern is invalid, unless `set_simple_layout_fallback` is enabled
+    /// - If one of the underlying JNI calls fail
+    pub fn add_console_appender(&self, pattern: &str) -> Result<()> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        sys::local_frame(inner.env, || {
+            let layout = build_layout(inner.env, pattern, inner.simple_layout_fallback)?;
+            let console_appender = console_appender(inner.env, layout)?;
+            add_appender(inner.env, inner.logger, console_appender)
+        })
+    }
+
+    /// Attach a console appender using the logcat-like pattern from `Pattern::logcat()`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn add_console_appender_logcat(&self) -> Result<()> {
+        self.add_console_appender(&Pattern::logcat())
+    }
+
+    /// Attach a file appender receiving all events
+    ///
+    /// # Params
+    /// - `path` The file to append to, converted with `path_to_java_string`
+    /// - `pattern` The conversion pattern for the `PatternLayout`
+    /// - `create_dirs` Create the parent directories of `path` if they don't exist, as `FileAppender` fails otherwise
+    ///
+    /// # Error
+    /// - If the parent directories cannot be created
+    /// - If the pattern is invalid, unless `set_simple_layout_fallback` is enabled
+    /// - If the file cannot be opened
+    /// - If one of the underlying JNI calls fail
+    pub fn add_file_appender<P: AsRef<Path>>(&self, path: P, pattern: &str, create_dirs: bool) -> Result<()> {
+        let path = path.as_ref();
+        if create_dirs {
+            if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent).map_err(|source| Error::CreateDirectory {
+                    path: parent.to_path_buf(),
+                    source,
+                })?;
+            }
+        }
+
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        sys::local_frame(inner.env, || {
+            let layout = build_layout(inner.env, pattern, inner.simple_layout_fallback)?;
+            let file_appender = file_appender(inner.env, path, layout)?;
+            add_appender(inner.env, inner.logger, file_appender)
+        })
+    }
+
+    /// Attach a file appender which only receives ERROR events, next to the appenders already attached.
+    /// This is achieved with a `LevelMatchFilter` accepting ERROR, followed by a `DenyAllFilter`
+    ///
+    /// # Params
+    /// - `path` The file to append to, converted with `path_to_java_string`
+    /// - `pattern` The conversion pattern for the `PatternLayout`
+    ///
+    /// # Error
+    /// - If the pattern is invalid, unless `set_simple_layout_fallback` is enabled
+    /// - If the file cannot be opened
+    /// - If one of the underlying JNI calls fail
+    pub fn add_error_file_appender<P: AsRef<Path>>(&self, path: P, pattern: &str) -> Result<()> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        let env = inner.env;
+        sys::local_frame(env, || {
+            let layout = build_layout(env, pattern, inner.simple_layout_fallback)?;
+            let file_appender = file_appender(env, path.as_ref(), layout)?;
+            add_filter(env, file_appender, level_match_filter(env, LogLevel::Error, true)?)?;
+            add_filter(env, file_appender, env.new_object(DENY_ALL_FILTER_CLASS, "()V", &[])?)?;
+            add_appender(env, inner.logger, file_appender)
+        })
+    }
+
+    /// Send this logger's output only to a dedicated file: additivity is turned off and a file appender is attached,
+    /// so events no longer reach the appenders of ancestors such as the root logger's console appender.
+    /// Other loggers in the hierarchy are unaffected
+    ///
+    /// # Params
+    /// - `path` The file to append to, converted with `path_to_java_string`
+    /// - `pattern` The conversion pattern for the `PatternLayout`
+    ///
+    /// # Error
+    /// - If the pattern is invalid, unless `set_simple_layout_fallback` is enabled
+    /// - If the file cannot be opened
+    /// - If one of the underlying JNI calls fail
+    pub fn route_to_file<P: AsRef<Path>>(&self, path: P, pattern: &str) -> Result<()> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        sys::local_frame(inner.env, || {
+            let layout = build_layout(inner.env, pattern, inner.simple_layout_fallback)?;
+            let file_appender = file_appender(inner.env, path.as_ref(), layout)?;
+            let _monitor = inner.env.lock_obj(inner.logger)?;
+            add_appender(inner.env, inner.logger, file_appender)?;
+            set_additivity(inner.env, inner.logger, false)
+        })
+    }
+
+    /// Attach every appender in `configs`, in order, or none at all.
+    /// If building or attaching one of the appenders fails, the appenders attached so far are removed and closed again,
+    /// and the error is returned. A pending Java exception is cleared so the rollback can proceed.
+    /// The rollback is best effort: an appender which can't be removed or closed doesn't stop the others from being rolled back,
+    /// and the original error is returned regardless. Files created by file appenders are left on disk, as they may have existed before
+    ///
+    /// # Error
+    /// - If a pattern is invalid, unless `set_simple_layout_fallback` is enabled
+    /// - If a file cannot be opened
+    /// - If one of the underlying JNI calls fail
+    pub fn add_appenders_atomic(&self, configs: &[AppenderConfig]) -> Result<()> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        let env = inner.env;
+        let _monitor = env.lock_obj(inner.logger)?;
+        sys::local_frame(env, || {
+            let mut added = Vec::with_capacity(configs.len());
+            for config in configs {
+                let result = build_appender(env, config, inner.simple_layout_fallback).and_then(|appender| {
+                    // Rolled back even if attaching it fails, removing an appender which isn't attached does nothing
+                    added.push(appender);
+                    add_appender(env, inner.logger, appender)
+                });
+
+                if let Err(e) = result {
+                    // Failures during the rollback are ignored, the exception they leave must not stop the next step
+                    let _ = env.exception_clear();
+                    for appender in added {
+                        if remove_appender(env, inner.logger, appender).is_err() {
+                            let _ = env.exception_clear();
+                        }
+                        if env.call_method(appender, "close", "()V", &[]).is_err() {
+                            let _ = env.exception_clear();
+                        }
+                    }
+
+                    return Err(e);
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Whether a message at `level` would be output: the logger must be enabled for `level`, taking the repository threshold into account,
+    /// and at least one appender reachable from this logger must have a threshold which accepts `level`.
+    /// Appenders reachable through additivity are included. Filters are not evaluated, as they depend on the event itself
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn would_log(&self, level: LogLevel) -> Result<bool> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        let env = inner.env;
+        sys::local_frame(env, || {
+            let priority = level.to_java_level(env)?;
+            if !env.call_method(inner.logger, "isEnabledFor", "(Lorg/apache/log4j/Priority;)Z", &[JValue::Object(priority)])?.z()? {
+                return Ok(false);
+            }
+
+            let mut category = inner.logger;
+            while !category.is_null() {
+                for appender in all_appenders(env, category)? {
+                    let accepts = !env.is_instance_of(appender, APPENDER_SKELETON_CLASS)?
+                        || env.call_method(appender, "isAsSevereAsThreshold", "(Lorg/apache/log4j/Priority;)Z", &[JValue::Object(priority)])?.z()?;
+                    if accepts {
+                        return Ok(true);
+                    }
+                }
+
+                if !get_additivity(env, category)? {
+                    break;
+                }
+
+                category = env.call_method(category, "getParent", "()Lorg/apache/log4j/Category;", &[])?.l()?;
+            }
+
+            Ok(false)
+        })
+    }
+
+    /// Make every appender attached to this logger output only events at exactly `level`, e.g. only DEBUG for targeted debugging.
+    /// A `LevelMatchFilter` accepting `level` followed by a `DenyAllFilter` is added to the end of each appender's filter chain,
+    /// filters already in the chain are evaluated first. The logger's own level still applies, so set it to `level` or lower.
+    /// Appenders of ancestor loggers and appenders attached later are unaffected
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn only_level(&self, level: LogLevel) -> Result<()> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        let env = inner.env;
+        let _monitor = env.lock_obj(inner.logger)?;
+        sys::local_frame(env, || {
+            for appender in all_appenders(env, inner.logger)? {
+                add_filter(env, appender, level_match_filter(env, level, true)?)?;
+                add_filter(env, appender, env.new_object(DENY_ALL_FILTER_CLASS, "()V", &[])?)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Whether events are also passed on to the appenders of ancestor loggers, using `Category#getAdditivity()`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn get_additivity(&self) -> Result<bool> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        get_additivity(inner.env, inner.logger)
+    }
+
+    /// Close every appender attached to this logger using `Appender#close()`, releasing file handles and other OS resources.
+    /// The appenders stay attached, but will no longer output anything
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn close_appenders(&self) -> Result<()> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        sys::local_frame(inner.env, || {
+            for appender in all_appenders(inner.env, inner.logger)? {
+                inner.env.call_method(appender, "close", "()V", &[])?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Get an appender attached to this logger by name
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn get_appender(&self, name: &str) -> Result<Option<AppenderHandle<'a>>> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        let appender = sys::local_frame_object(inner.env, || {
+            let name = inner.env.new_string(name)?;
+            Ok(inner.env.call_method(inner.logger, "getAppender", "(Ljava/lang/String;)Lorg/apache/log4j/Appender;", &[JValue::Object(name.into())])?.l()?)
+        })?;
+        if appender.is_null() {
+            return Ok(None);
+        }
+
+        Ok(Some(AppenderHandle::from_jobject(appender)))
+    }
+
+    /// Force a rollover of a `RollingFileAppender` attached to this logger, using `RollingFileAppender#rollOver()`.
+    /// The current file is closed and renamed to the first backup, and a fresh file is started,
+    /// regardless of whether the size threshold was reached
+    ///
+    /// # Params
+    /// - `appender_name` The name of the appender, which must be a `RollingFileAppender`
+    ///
+    /// # Error
+    /// - If no appender named `appender_name` is attached to this logger
+    /// - If the appender is not a `RollingFileAppender`
+    /// - If one of the underlying JNI calls fail
+    pub fn roll_over(&self, appender_name: &str) -> Result<()> {
+        let env = self.inner.lock().expect("Failed to lock inner logger").env;
+        sys::local_frame(env, || {
+            let handle = self.get_appender(appender_name)?.ok_or_else(|| Error::AppenderNotFound(appender_name.to_string()))?;
+            let appender = handle.as_jobject();
+            if !env.is_instance_of(appender, ROLLING_FILE_APPENDER_CLASS)? {
+                return Err(Error::NotRollable(appender_name.to_string()));
+            }
+
+            // Synchronized in log4j as well, so events being appended concurrently go either to the old or the new file
+            let _monitor = env.lock_obj(appender)?;
+            env.call_method(appender, "rollOver", "()V", &[])?;
+            Ok(())
+        })
+    }
+
+    /// Log a message to a single appender only, using `Appender#doAppend(LoggingEvent)`.
+    /// The logger's appenders and additivity are bypassed entirely, as is the logger's level:
+    /// the event is delivered regardless of whether this logger is enabled for `level`.
+    /// The appender's own threshold and filters still apply. The trace id of `with_trace_id` and the timestamp of `stamp_rust_time`
+    /// are put in the MDC, as with `log`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_to<S: AsRef<str>>(&self, appender: &AppenderHandle, level: LogLevel, message: S) -> Result<()> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        let env = inner.env;
+        let message = inner.pipeline.transform(level, message.as_ref());
+        inner.pipeline.guarded(level.resolved(), || message.to_string(), || {
+            sys::local_frame(env, || {
+                inner.pipeline.traced(env, || {
+                    let fqn = env.new_string(CATEGORY_FQN)?;
+                    let priority = level.to_java_level(env)?;
+                    let message = Self::jstring(env, &message)?;
+                    let event = env.new_object(
+                        LOGGING_EVENT_CLASS,
+                        "(Ljava/lang/String;Lorg/apache/log4j/Category;Lorg/apache/log4j/Priority;Ljava/lang/Object;Ljava/lang/Throwable;)V",
+                        &[JValue::Object(fqn.into()), JValue::Object(inner.logger), JValue::Object(priority), message, JValue::Object(JObject::null())],
+                    )?;
+
+                    env.call_method(appender.appender, "doAppend", "(Lorg/apache/log4j/spi/LoggingEvent;)V", &[JValue::Object(event)])?;
+                    Ok(())
+                })
+            })
+        })
+    }
+
+    /// Reconfigure the logger's appenders atomically.
+    /// The logger is locked for the entire duration of `f`, so no log call made through this JavaLogger
+    /// (or its clones) can interleave with a half-applied configuration.
+    /// The Java monitor of the logger is held as well, see `with_java_lock`.
+    ///
+    /// `f` must not log through this JavaLogger, as that would deadlock.
+    ///
+    /// # Error
+    /// - If `f` fails
+    pub fn reconfigure<F: FnOnce(&ReconfigContext<'a, '_>) -> Result<()>>(&self, f: F) -> Result<()> {
+        self.with_java_lock(f)
+    }
+
+    /// Run `f` while holding the Java monitor of the `org.apache.log4j.Logger`, i.e. inside `synchronized (logger)`.
+    /// log4j synchronizes on the logger when it changes or uses its appenders, and so does Java code reconfiguring it at runtime,
+    /// so several operations in `f` are atomic with respect to both Rust and Java.
+    /// The Rust-side lock of this JavaLogger is held as well, as with `reconfigure`.
+    ///
+    /// Multi-step changes made by this crate take the monitor too: `reconfigure`, `add_appenders_atomic`, `route_to_file`,
+    /// `only_level`, `capture` while attaching and detaching its appender, and every enumeration of the attached appenders.
+    ///
+    /// `f` must not use this JavaLogger, as that would deadlock.
+    ///
+    /// # Error
+    /// - If `f` fails
+    /// - If one of the underlying JNI calls fail
+    pub fn with_java_lock<R, F: FnOnce(&ReconfigContext<'a, '_>) -> Result<R>>(&self, f: F) -> Result<R> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        let _monitor = inner.env.lock_obj(inner.logger)?;
+        f(&ReconfigContext { inner: &inner })
+    }
+}
+
+/// Description of an appender to attach with `JavaLogger::add_appenders_atomic`.
+/// With the `toml` feature it is also the schema of the appenders in `configure_from_toml`, with the variant as `kind`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "toml", derive(serde::Deserialize), serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields))]
+pub enum AppenderConfig {
+    /// A console appender writing to `System.out`, as attached by `add_console_appender`
+    Console {
+        pattern: String,
+    },
+    /// A file appender appending to `path`
+    File {
+        path: PathBuf,
+        pattern: String,
+    },
+    /// A file appender which only receives ERROR events, as attached by `add_error_file_appender`
+    ErrorFile {
+        path: PathBuf,
+        pattern: String,
+    },
+}
+
+/// A handle to an `org.apache.log4j.Appender`
+#[derive(Clone, Copy)]
+pub struct AppenderHandle<'a> {
+    appender: JObject<'a>,
+}
+
+impl<'a> AppenderHandle<'a> {
+    /// Wrap an `org.apache.log4j.Appender` obtained elsewhere
+    pub fn from_jobject(appender: JObject<'a>) -> Self {
+        Self {
+            appender,
+        }
+    }
+
+    /// The underlying `org.apache.log4j.Appender`
+    pub fn as_jobject(&self) -> JObject<'a> {
+        self.appender
+    }
+
+    /// Replace the layout of the appender while it is in use, using `Appender#setLayout(Layout)`.
+    /// The appender's monitor is held, so no event is formatted while the layout is swapped.
+    /// The layout's options are activated, the appender's are not: for file appenders that would reopen the file,
+    /// and the appenders of log4j itself pick up the new layout without it
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn set_layout(&self, env: &JNIEnv<'a>, layout: &LayoutHandle<'a>) -> Result<()> {
+        if env.is_instance_of(layout.layout, OPTION_HANDLER_CLASS)? {
+            env.call_method(layout.layout, "activateOptions", "()V", &[])?;
+        }
+
+        let _monitor = env.lock_obj(self.appender)?;
+        env.call_method(self.appender, "setLayout", "(Lorg/apache/log4j/Layout;)V", &[JValue::Object(layout.layout)])?;
+        Ok(())
+    }
+
+    /// The conversion pattern of the appender's layout, `None` if it has no layout or the layout is not a `PatternLayout`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn layout_pattern(&self, env: &JNIEnv<'a>) -> Result<Option<String>> {
+        sys::local_frame(env, || {
+            let layout = env.call_method(self.appender, "getLayout", "()Lorg/apache/log4j/Layout;", &[])?.l()?;
+            if layout.is_null() || !env.is_instance_of(layout, PATTERN_LAYOUT_CLASS)? {
+                return Ok(None);
+            }
+
+            let pattern = env.call_method(layout, "getConversionPattern", "()Ljava/lang/String;", &[])?.l()?;
+            if pattern.is_null() {
+                return Ok(None);
+            }
+
+            Ok(Some(env.get_string(pattern.into())?.into()))
+        })
+    }
+}
+
+/// A handle to an `org.apache.log4j.Layout`, see `AppenderHandle::set_layout`
+#[derive(Clone, Copy)]
+pub struct LayoutHandle<'a> {
+    layout: JObject<'a>,
+}
+
+impl<'a> LayoutHandle<'a> {
+    /// Create a `PatternLayout` with the provided conversion pattern, see `Pattern` for building one
+    ///
+    /// # Error
+    /// - If the pattern is invalid
+    /// - If one of the underlying JNI calls fail
+    pub fn pattern(env: &JNIEnv<'a>, pattern: &str) -> Result<Self> {
+        Ok(Self {
+            layout: build_layout(env, pattern, false)?,
+        })
+    }
+
+    /// Wrap an `org.apache.log4j.Layout` obtained elsewhere
+    pub fn from_jobject(layout: JObject<'a>) -> Self {
+        Self {
+            layout,
+        }
+    }
+
+    /// The underlying `org.apache.log4j.Layout`
+    pub fn as_jobject(&self) -> JObject<'a> {
+        self.layout
+    }
+}
+
+/// Appender primitives available while reconfiguring a logger with `JavaLogger::reconfigure`
+pub struct ReconfigContext<'a, 'b> {
+    inner: &'b InnerLogger<'a>,
+}
+
+impl<'a, 'b> ReconfigContext<'a, 'b> {
+    /// The JNIEnv the logger was created with
+    pub fn env(&self) -> &'a JNIEnv<'a> {
+        self.inner.env
+    }
+
+    /// The `org.apache.log4j.Logger` being reconfigured
+    pub fn logger(&self) -> JObject<'a> {
+        self.inner.logger
+    }
+
+    /// Attach an `org.apache.log4j.Appender`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn add_appender(&self, appender: JObject) -> Result<()> {
+        add_appender(self.inner.env, self.inner.logger, appender)
+    }
+
+    /// Detach an `org.apache.log4j.Appender`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn remove_appender(&self, appender: JObject) -> Result<()> {
+        remove_appender(self.inner.env, self.inner.logger, appender)
+    }
+
+    /// Detach the appender with the provided name, if attached
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn remove_appender_by_name(&self, name: &str) -> Result<()> {
+        sys::local_frame(self.inner.env, || {
+            let name = self.inner.env.new_string(name)?;
+            self.inner.env.call_method(self.inner.logger, "removeAppender", "(Ljava/lang/String;)V", &[JValue::Object(name.into())])?;
+            Ok(())
+        })
+    }
+
+    /// Detach all appenders
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn remove_all_appenders(&self) -> Result<()> {
+        self.inner.env.call_method(self.inner.logger, "removeAllAppenders", "()V", &[])?;
+        Ok(())
+    }
+
+    /// Get all appenders currently attached
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn appenders(&self) -> Result<Vec<JObject<'a>>> {
+        all_appenders(self.inner.env, self.inner.logger)
+    }
+}
+
+/// Create the appender described by `config`, without attaching it
+///
+/// # Error
+/// - If the pattern is invalid and `fallback` is disabled
+/// - If a file cannot be opened
+/// - If one of the underlying JNI calls fail
+fn build_appender<'a>(env: &JNIEnv<'a>, config: &AppenderConfig, fallback: bool) -> Result<JObject<'a>> {
+    sys::local_frame_object(env, || match config {
+        AppenderConfig::Console { pattern } => console_appender(env, build_layout(env, pattern, fallback)?),
+        AppenderConfig::File { path, pattern } => file_appender(env, path, build_layout(env, pattern, fallback)?),
+        AppenderConfig::ErrorFile { path, pattern } => {
+            let appender = file_appender(env, path, build_layout(env, pattern, fallback)?)?;
+            add_filter(env, appender, level_match_filter(env, LogLevel::Error, true)?)?;
+            add_filter(env, appender, env.new_object(DENY_ALL_FILTER_CLASS, "()V", &[])?)?;
+            Ok(appender)
+        }
+    })
+}
+
+/// Create a `PatternLayout` with the provided conversion pattern.
+/// The pattern is checked first, as log4j silently produces odd output for malformed patterns
+///
+/// # Params
+/// - `fallback` Create a `SimpleLayout` rather than failing when the pattern is invalid
+///
+/// # Error
+/// - If the pattern is invalid and `fallback` is disabled
+/// - If one of the underlying JNI calls fail
+pub(crate) fn build_layout<'a>(env: &JNIEnv<'a>, pattern: &str, fallback: bool) -> Result<JObject<'a>> {
+    if let Err(reason) = check_syntax(pattern) {
+        if fallback {
+            return Ok(env.new_object(SIMPLE_LAYOUT_CLASS, "()V", &[])?);
+        }
+
+        return Err(Error::InvalidPattern {
+            pattern: pattern.to_string(),
+            reason,
+        });
+    }
+
+    sys::local_frame_object(env, || {
+        let pattern = env.new_string(pattern)?;
+        Ok(env.new_object(PATTERN_LAYOUT_CLASS, "(Ljava/lang/String;)V", &[JValue::Object(pattern.into())])?)
+    })
+}
+
+/// Create a `ConsoleAppender` writing to `System.out`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn console_appender<'a>(env: &JNIEnv<'a>, layout: JObject<'a>) -> Result<JObject<'a>> {
+    Ok(env.new_object(CONSOLE_APPENDER_CLASS, "(Lorg/apache/log4j/Layout;)V", &[JValue::Object(layout)])?)
+}
+
+/// Create a `FileAppender` appending to `path`, converted with `path_to_java_string`
+///
+/// # Error
+/// - If the file cannot be opened
+/// - If one of the underlying JNI calls fail
+pub(crate) fn file_appender<'a>(env: &JNIEnv<'a>, path: &Path, layout: JObject<'a>) -> Result<JObject<'a>> {
+    sys::local_frame_object(env, || {
+        let path = env.new_string(path_to_java_string(path, PathConversion::Lossy)?)?;
+        Ok(env.new_object(FILE_APPENDER_CLASS, "(Lorg/apache/log4j/Layout;Ljava/lang/String;)V", &[JValue::Object(layout), JValue::Object(path.into())])?)
+    })
+}
+
+/// Create a `LevelMatchFilter` for `level`
+///
+/// # Params
+/// - `accept_on_match` Whether matching events are accepted (`true`) or denied (`false`)
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn level_match_filter<'a>(env: &JNIEnv<'a>, level: LogLevel, accept_on_match: bool) -> Result<JObject<'a>> {
+    sys::local_frame_object(env, || {
+        let level_name = env.call_method(level.to_java_level(env)?, "toString", "()Ljava/lang/String;", &[])?.l()?;
+        let filter = env.new_object(LEVEL_MATCH_FILTER_CLASS, "()V", &[])?;
+        env.call_method(filter, "setLevelToMatch", "(Ljava/lang/String;)V", &[JValue::Object(level_name)])?;
+        env.call_method(filter, "setAcceptOnMatch", "(Z)V", &[JValue::Bool(accept_on_match.into())])?;
+        Ok(filter)
+    })
+}
+
+/// Add a filter to the end of an appender's filter chain using `Appender#addFilter(Filter)`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn add_filter(env: &JNIEnv, appender: JObject, filter: JObject) -> Result<()> {
+    env.call_method(appender, "addFilter", "(Lorg/apache/log4j/spi/Filter;)V", &[JValue::Object(filter)])?;
+    Ok(())
+}
+
+/// Create a `WriterAppender` writing to a new `java.io.StringWriter`.
+/// Returns the appender and the StringWriter, in that order
+///
+/// # Error
+/// - If the pattern is invalid
+/// - If one of the underlying JNI calls fail
+pub(crate) fn string_appender<'a>(env: &JNIEnv<'a>, pattern: &str) -> Result<(JObject<'a>, JObject<'a>)> {
+    let string_writer = env.new_object(STRING_WRITER_CLASS, "()V", &[])?;
+    let appender = sys::local_frame_object(env, || {
+        let layout = build_layout(env, pattern, false)?;
+        Ok(env.new_object(WRITER_APPENDER_CLASS, "(Lorg/apache/log4j/Layout;Ljava/io/Writer;)V", &[JValue::Object(layout), JValue::Object(string_writer)])?)
+    })?;
+    Ok((appender, string_writer))
+}
+
+/// Read everything written to a `java.io.StringWriter` so far
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn read_string_writer(env: &JNIEnv, string_writer: JObject) -> Result<String> {
+    sys::local_frame(env, || {
+        let content = env.call_method(string_writer, "toString", "()Ljava/lang/String;", &[])?.l()?;
+        Ok(env.get_string(content.into())?.into())
+    })
+}
+
+/// Attach an appender to a logger using `Category#addAppender(Appender)`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn add_appender(env: &JNIEnv, logger: JObject, appender: JObject) -> Result<()> {
+    env.call_method(logger, "addAppender", "(Lorg/apache/log4j/Appender;)V", &[JValue::Object(appender)])?;
+    Ok(())
+}
+
+/// Detach an appender from a logger using `Category#removeAppender(Appender)`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn remove_appender(env: &JNIEnv, logger: JObject, appender: JObject) -> Result<()> {
+    env.call_method(logger, "removeAppender", "(Lorg/apache/log4j/Appender;)V", &[JValue::Object(appender)])?;
+    Ok(())
+}
+
+/// Get all appenders attached to a logger using `Category#getAllAppenders()`.
+/// The logger's monitor is held while enumerating, so appenders added or removed concurrently can't tear the enumeration
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn all_appenders<'a>(env: &JNIEnv<'a>, logger: JObject<'a>) -> Result<Vec<JObject<'a>>> {
+    let _monitor = env.lock_obj(logger)?;
+    let enumeration = env.call_method(logger, "getAllAppenders", "()Ljava/util/Enumeration;", &[])?.l()?;
+
+    let mut appenders = Vec::new();
+    while env.call_method(enumeration, "hasMoreElements", "()Z", &[])?.z()? {
+        appenders.push(env.call_method(enumeration, "nextElement", "()Ljava/lang/Object;", &[])?.l()?);
+    }
+
+    env.delete_local_ref(enumeration)?;
+    Ok(appenders)
+}
+
+/// Get the additivity flag of a logger using `Category#getAdditivity()`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn get_additivity(env: &JNIEnv, logger: JObject) -> Result<bool> {
+    Ok(env.call_method(logger, "getAdditivity", "()Z", &[])?.z()?)
+}
+
+/// Set the additivity flag of a logger using `Category#setAdditivity(boolean)`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn set_additivity(env: &JNIEnv, logger: JObject, additive: bool) -> Result<()> {
+    env.call_method(logger, "setAdditivity", "(Z)V", &[JValue::Bool(additive.into())])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support::{self, ConfigSnapshot};
+
+    #[test]
+    fn error_file_appender() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("ErrorFile").expect("Failed to create JavaLogger");
+
+        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = tmpdir.path().join("errors.log");
+        logger.add_error_file_appender(&path, "%p %m%n").expect("Failed to add error file appender");
+
+        logger.log(LogLevel::Info, "Just informing").expect("Failed to log to INFO level");
+        logger.log(LogLevel::Error, "Something broke").expect("Failed to log to ERROR level");
+        // The file appender must not outlive the temporary directory
+        logger.close_appenders().expect("Failed to close appenders");
+        logger.reconfigure(|ctx| ctx.remove_all_appenders()).expect("Failed to remove appenders");
+
+        let content = std::fs::read_to_string(&path).expect("Failed to read error file");
+        assert_eq!(content.lines().collect::<Vec<_>>(), vec!["ERROR Something broke"]);
+    }
+
+    #[test]
+    fn file_appender_create_dirs() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("CreateDirs").expect("Failed to create JavaLogger");
+
+        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = tmpdir.path().join("nested").join("logs").join("app.log");
+        logger.add_file_appender(&path, "%p %m%n", true).expect("Failed to add file appender");
+        assert!(path.parent().unwrap().is_dir());
+
+        logger.log(LogLevel::Info, "First run").expect("Failed to log to INFO level");
+        logger.close_appenders().expect("Failed to close appenders");
+        logger.reconfigure(|ctx| ctx.remove_all_appenders()).expect("Failed to remove appenders");
+        assert_eq!(std::fs::read_to_string(&path).expect("Failed to read log file"), "INFO First run\n");
+    }
+
+    #[test]
+    fn route_to_file() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let snapshot = ConfigSnapshot::capture(&env).expect("Failed to capture configuration");
+
+        let root = env.call_static_method("org/apache/log4j/LogManager", "getRootLogger", "()Lorg/apache/log4j/Logger;", &[]).unwrap().l().unwrap();
+        let (root_appender, root_writer) = string_appender(&env, "%c %m%n").expect("Failed to create string appender");
+        add_appender(&env, root, root_appender).expect("Failed to add root appender");
+
+        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = tmpdir.path().join("component.log");
+        let component = JavaLogger::new(&env, "com.example.Routed.Component").expect("Failed to create JavaLogger");
+        component.route_to_file(&path, "%m%n").expect("Failed to route to file");
+        let other = JavaLogger::new(&env, "com.example.Routed.Other").expect("Failed to create JavaLogger");
+
+        component.log(LogLevel::Info, "To the file").expect("Failed to log to INFO level");
+        other.log(LogLevel::Info, "To the console").expect("Failed to log to INFO level");
+        snapshot.restore().expect("Failed to restore configuration");
+
+        assert_eq!(std::fs::read_to_string(&path).expect("Failed to read component file"), "To the file\n");
+        assert_eq!(read_string_writer(&env, root_writer).unwrap(), "com.example.Routed.Other To the console\n");
+    }
+
+    #[test]
+    fn add_appenders_atomic() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("Atomic").expect("Failed to create JavaLogger");
+
+        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = tmpdir.path().join("atomic.log");
+        let configs = vec![
+            AppenderConfig::Console { pattern: "%p %m%n".to_string() },
+            AppenderConfig::File { path: path.clone(), pattern: "%m%n".to_string() },
+            AppenderConfig::ErrorFile { path, pattern: "%d{HH:mm %m%n".to_string() },
+        ];
+
+        match logger.add_appenders_atomic(&configs) {
+            Err(Error::InvalidPattern { pattern, .. }) => assert_eq!(pattern, "%d{HH:mm %m%n"),
+            _ => panic!("Expected InvalidPattern"),
+        }
+        assert!(!env.exception_check().unwrap());
+        logger.reconfigure(|ctx| {
+            assert!(ctx.appenders()?.is_empty());
+            Ok(())
+        })
+        .expect("Failed to list appenders");
+
+        logger.add_appenders_atomic(&configs[..2]).expect("Failed to add appenders");
+        logger.reconfigure(|ctx| {
+            assert_eq!(ctx.appenders()?.len(), 2);
+            ctx.remove_all_appenders()
+        })
+        .expect("Failed to remove appenders");
+    }
+
+    #[test]
+    fn only_level() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("OnlyLevel").expect("Failed to create capturing logger");
+        scope.set_level(&logger, LogLevel::Debug).expect("Failed to set level");
+
+        logger.only_level(LogLevel::Warn).expect("Failed to restrict to WARN");
+        for level in [LogLevel::Error, LogLevel::Warn, LogLevel::Info, LogLevel::Debug] {
+            logger.log(level, "Message").expect("Failed to log");
+        }
+
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec!["WARN Message"]);
+    }
+
+    #[test]
+    fn concurrent_java_reconfiguration() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("Concurrent").expect("Failed to create JavaLogger");
+        let logger_name = scope.logger_name("Concurrent");
+
+        let vm: &jni::JavaVM = &jvm;
+        let stop = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|threads| {
+            // Reconfigures the logger the way Java code would: adding or removing a pair of appenders inside synchronized (logger)
+            let reconfigurer = threads.spawn(|| {
+                let env = vm.attach_current_thread().expect("Failed to attach thread to the JVM");
+                let name = env.new_string(&logger_name).unwrap();
+                let java_logger = env.call_static_method("org/apache/log4j/Logger", "getLogger", "(Ljava/lang/String;)Lorg/apache/log4j/Logger;", &[JValue::Object(name.into())]).unwrap().l().unwrap();
+                let first = env.new_object("org/apache/log4j/varia/NullAppender", "()V", &[]).unwrap();
+                let second = env.new_object("org/apache/log4j/varia/NullAppender", "()V", &[]).unwrap();
+
+                while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+                    for add in [true, false] {
+                        let _monitor = env.lock_obj(java_logger).unwrap();
+                        for appender in [first, second] {
+                            match add {
+                                true => add_appender(&env, java_logger, appender).unwrap(),
+                                false => remove_appender(&env, java_logger, appender).unwrap(),
+                            }
+                        }
+                    }
+                }
+            });
+
+            // Collected before asserting, a panic here would leave the reconfiguring thread running
+            let counts: Result<Vec<usize>> = (0..2000).map(|_| logger.with_java_lock(|ctx| Ok(ctx.appenders()?.len()))).collect();
+            stop.store(true, std::sync::atomic::Ordering::Relaxed);
+            reconfigurer.join().expect("Reconfiguring thread panicked");
+
+            for count in counts.expect("Failed to enumerate appenders") {
+                assert!(count == 0 || count == 2, "Observed {} appenders", count);
+            }
+        });
+
+        logger.reconfigure(|ctx| ctx.remove_all_appenders()).expect("Failed to remove appenders");
+    }
+
+    #[test]
+    fn additivity() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("Additivity").expect("Failed to create JavaLogger");
+
+        assert!(logger.get_additivity().expect("Failed to get additivity"));
+        logger.reconfigure(|ctx| set_additivity(ctx.env(), ctx.logger(), false)).expect("Failed to turn off additivity");
+        assert!(!logger.get_additivity().expect("Failed to get additivity"));
+        logger.reconfigure(|ctx| set_additivity(ctx.env(), ctx.logger(), true)).expect("Failed to turn on additivity");
+    }
+
+    #[test]
+    fn would_log() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("WouldLog").expect("Failed to create JavaLogger");
+        scope.set_level(&logger, LogLevel::Debug).expect("Failed to set level");
+        logger.reconfigure(|ctx| set_additivity(ctx.env(), ctx.logger(), false)).expect("Failed to turn off additivity");
+        assert!(!logger.would_log(LogLevel::Error).unwrap());
+
+        let warn = LogLevel::Warn.to_java_level(&env).unwrap();
+        for _ in 0..2 {
+            let (appender, _) = string_appender(&env, "%m%n").expect("Failed to create string appender");
+            env.call_method(appender, "setThreshold", "(Lorg/apache/log4j/Priority;)V", &[JValue::Object(warn)]).expect("Failed to set threshold");
+            scope.add_appender(&logger, appender).expect("Failed to add appender");
+        }
+
+        assert!(!logger.would_log(LogLevel::Debug).unwrap());
+        assert!(!logger.would_log(LogLevel::Info).unwrap());
+        assert!(logger.would_log(LogLevel::Warn).unwrap());
+        assert!(logger.would_log(LogLevel::Error).unwrap());
+
+        scope.set_level(&logger, LogLevel::Error).expect("Failed to set level");
+        assert!(!logger.would_log(LogLevel::Warn).unwrap());
+    }
+
+    #[test]
+    fn reconfigure_while_logging() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("Reconfigure").expect("Failed to create JavaLogger");
+
+        let done = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|threads| {
+            // A clone shares the lock which reconfigure holds
+            let clone = logger.clone();
+            let done = &done;
+            threads.spawn(move || {
+                while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                    clone.log(LogLevel::Info, "Logging while reconfiguring").expect("Failed to log to INFO level");
+                }
+            });
+
+            for _ in 0..50 {
+                logger
+                    .reconfigure(|ctx| {
+                        ctx.remove_all_appenders()?;
+                        ctx.add_appender(string_appender(ctx.env(), "%m%n")?.0)?;
+                        ctx.add_appender(string_appender(ctx.env(), "%p %m%n")?.0)
+                    })
+                    .expect("Failed to reconfigure");
+            }
+            done.store(true, std::sync::atomic::Ordering::Relaxed);
+        });
+
+        logger.reconfigure(|ctx| {
+            assert_eq!(ctx.appenders()?.len(), 2);
+            ctx.remove_all_appenders()
+        }).expect("Failed to inspect appenders");
+    }
+
+    #[test]
+    fn logcat() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("Logcat").expect("Failed to create JavaLogger");
+
+        let (string_appender, string_writer) = string_appender(&env, &Pattern::logcat()).expect("Failed to create string appender");
+        scope.add_appender(&logger, string_appender).expect("Failed to add string appender");
+        logger.log(LogLevel::Warn, "Battery low").expect("Failed to log to WARN level");
+
+        // e.g. "10-16 13:37:00.123 WARN/com.example.scope0.Logcat: Battery low"
+        let output = read_string_writer(&env, string_writer).expect("Failed to read StringWriter");
+        let (date, rest) = output.split_at(18);
+        assert!(date.chars().enumerate().all(|(i, c)| match i {
+            2 => c == '-',
+            5 => c == ' ',
+            8 | 11 => c == ':',
+            14 => c == '.',
+            _ => c.is_ascii_digit(),
+        }));
+        assert_eq!(rest.trim_end(), format!(" WARN/{}: Battery low", scope.logger_name("Logcat")));
+
+        logger.add_console_appender_logcat().expect("Failed to add logcat console appender");
+        logger.reconfigure(|ctx| {
+            let appenders = ctx.appenders()?;
+            let layout = env.call_method(appenders[1], "getLayout", "()Lorg/apache/log4j/Layout;", &[])?.l()?;
+            let pattern = env.call_method(layout, "getConversionPattern", "()Ljava/lang/String;", &[])?.l()?;
+            assert_eq!(String::from(env.get_string(pattern.into())?), Pattern::logcat());
+            ctx.remove_appender(appenders[1])
+        }).expect("Failed to inspect appenders");
+    }
+
+    #[test]
+    fn close_appenders() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("CloseAppenders").expect("Failed to create JavaLogger");
+
+        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = tmpdir.path().join("close.log");
+        let layout = build_layout(&env, "%m%n", false).expect("Failed to create layout");
+        let file_appender = file_appender(&env, &path, layout).expect("Failed to create file appender");
+        scope.add_appender(&logger, file_appender).expect("Failed to add file appender");
+
+        logger.log(LogLevel::Info, "Before close").expect("Failed to log to INFO level");
+        logger.close_appenders().expect("Failed to close appenders");
+
+        let content = std::fs::read_to_string(&path).expect("Failed to read log file");
+        assert_eq!(content.lines().collect::<Vec<_>>(), vec!["Before close"]);
+        std::fs::remove_file(&path).expect("Log file should no longer be in use");
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("InvalidPattern").expect("Failed to create JavaLogger");
+
+        match logger.add_console_appender("%d{HH:mm %m%n") {
+            Err(Error::InvalidPattern { pattern, .. }) => assert_eq!(pattern, "%d{HH:mm %m%n"),
+            _ => panic!("Expected InvalidPattern"),
+        }
+
+        logger.set_simple_layout_fallback(true);
+        logger.add_console_appender("%d{HH:mm %m%n").expect("Failed to add console appender with fallback");
+        logger.reconfigure(|ctx| {
+            let appenders = ctx.appenders()?;
+            assert_eq!(appenders.len(), 1);
+            let layout = env.call_method(appenders[0], "getLayout", "()Lorg/apache/log4j/Layout;", &[])?.l()?;
+            assert!(env.is_instance_of(layout, SIMPLE_LAYOUT_CLASS)?);
+            ctx.remove_all_appenders()
+        }).expect("Failed to inspect appenders");
+    }
+
+    #[test]
+    fn log_to_single_appender() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("LogTo").expect("Failed to create JavaLogger");
+
+        logger.with_trace_id(|| "trace-1".to_string());
+
+        let (audit_appender, audit_writer) = string_appender(&env, "%p %X{traceId} %m%n").expect("Failed to create string appender");
+        let (other_appender, other_writer) = string_appender(&env, "%p %m%n").expect("Failed to create string appender");
+        scope.add_appender(&logger, audit_appender).expect("Failed to add string appender");
+        scope.add_appender(&logger, other_appender).expect("Failed to add string appender");
+
+        logger.log_to(&AppenderHandle::from_jobject(audit_appender), LogLevel::Info, "Audited").expect("Failed to log to appender");
+
+        assert_eq!(read_string_writer(&env, audit_writer).unwrap().lines().collect::<Vec<_>>(), vec!["INFO trace-1 Audited"]);
+        assert_eq!(read_string_writer(&env, other_writer).unwrap(), "");
+    }
+
+    #[test]
+    fn set_layout() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("SetLayout").expect("Failed to create JavaLogger");
+
+        let (appender, writer) = string_appender(&env, "%p %m%n").expect("Failed to create string appender");
+        env.call_method(appender, "setName", "(Ljava/lang/String;)V", &[JValue::Object(env.new_string("console").unwrap().into())]).unwrap();
+        scope.add_appender(&logger, appender).expect("Failed to add string appender");
+        logger.log(LogLevel::Info, "Before").expect("Failed to log to INFO level");
+
+        let handle = logger.get_appender("console").expect("Failed to get appender").expect("Appender is missing");
+        assert_eq!(handle.layout_pattern(&env).expect("Failed to get pattern").as_deref(), Some("%p %m%n"));
+
+        let json = LayoutHandle::pattern(&env, "{\"level\":\"%p\",\"message\":\"%m\"}%n").expect("Failed to create layout");
+        handle.set_layout(&env, &json).expect("Failed to set layout");
+        logger.log(LogLevel::Warn, "After").expect("Failed to log to WARN level");
+        assert_eq!(read_string_writer(&env, writer).unwrap().lines().collect::<Vec<_>>(), vec!["INFO Before", "{\"level\":\"WARN\",\"message\":\"After\"}"]);
+
+        let simple = LayoutHandle::from_jobject(env.new_object(SIMPLE_LAYOUT_CLASS, "()V", &[]).unwrap());
+        handle.set_layout(&env, &simple).expect("Failed to set layout");
+        assert_eq!(handle.layout_pattern(&env).expect("Failed to get pattern"), None);
+        assert!(matches!(LayoutHandle::pattern(&env, "%m%"), Err(Error::InvalidPattern { .. })));
+    }
+
+    #[test]
+    fn roll_over() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("RollOver").expect("Failed to create JavaLogger");
+
+        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = tmpdir.path().join("rolling.log");
+        let layout = env.new_object(PATTERN_LAYOUT_CLASS, "(Ljava/lang/String;)V", &[JValue::Object(env.new_string("%m%n").unwrap().into())]).unwrap();
+        let appender = env
+            .new_object(ROLLING_FILE_APPENDER_CLASS, "(Lorg/apache/log4j/Layout;Ljava/lang/String;)V", &[JValue::Object(layout), JValue::Object(env.new_string(path.to_str().unwrap()).unwrap().into())])
+            .expect("Failed to create RollingFileAppender");
+        env.call_method(appender, "setName", "(Ljava/lang/String;)V", &[JValue::Object(env.new_string("rolling").unwrap().into())]).unwrap();
+        scope.add_appender(&logger, appender).expect("Failed to add RollingFileAppender");
+
+        logger.log(LogLevel::Info, "Before").expect("Failed to log to INFO level");
+        logger.roll_over("rolling").expect("Failed to roll over");
+        logger.log(LogLevel::Info, "After").expect("Failed to log to INFO level");
+
+        // The default threshold is 10 MB, far from reached
+        let backup = tmpdir.path().join("rolling.log.1");
+        assert_eq!(std::fs::read_to_string(&backup).expect("Backup file is missing"), "Before\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "After\n");
+
+        match logger.roll_over("missing") {
+            Err(Error::AppenderNotFound(name)) => assert_eq!(name, "missing"),
+            _ => panic!("Expected AppenderNotFound"),
+        }
+
+        let null_appender = env.new_object("org/apache/log4j/varia/NullAppender", "()V", &[]).unwrap();
+        env.call_method(null_appender, "setName", "(Ljava/lang/String;)V", &[JValue::Object(env.new_string("null").unwrap().into())]).unwrap();
+        scope.add_appender(&logger, null_appender).expect("Failed to add NullAppender");
+        match logger.roll_over("null") {
+            Err(Error::NotRollable(name)) => assert_eq!(name, "null"),
+            _ => panic!("Expected NotRollable"),
+        }
+    }
+}
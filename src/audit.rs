@@ -0,0 +1,115 @@
+use crate::appender::{add_appender, build_layout, set_additivity};
+use crate::error::{Error, Result};
+use crate::level::LogLevel;
+use crate::logger::JavaLogger;
+use crate::mdc;
+use crate::sys;
+use jni::objects::JValue;
+use jni::JNIEnv;
+
+/// The name of the dedicated audit logger
+const AUDIT_LOGGER_NAME: &str = "audit";
+/// The name given to the audit file appender, used to verify it is still attached
+const AUDIT_APPENDER_NAME: &str = "audit-file";
+/// The fixed conversion pattern for audit records
+const AUDIT_PATTERN: &str = "%d{ISO8601} user=%X{user} action=%X{action} %m%n";
+const FILE_APPENDER_CLASS: &str = "org/apache/log4j/FileAppender";
+
+/// Append-only audit trail.
+/// Records are written by the dedicated `audit` logger, with additivity turned off, to a file appender in append mode
+/// which flushes after every record. Recording refuses to run when the file appender is no longer attached,
+/// so a misconfiguration cannot silently drop audit records
+pub struct AuditLogger<'a> {
+    logger: JavaLogger<'a>,
+}
+
+impl<'a> AuditLogger<'a> {
+    /// Set up the audit logger, appending to `path`
+    ///
+    /// # Error
+    /// - If the file cannot be opened
+    /// - If one of the underlying JNI calls fail
+    pub fn new(env: &'a JNIEnv<'a>, path: &str) -> Result<Self> {
+        let logger = JavaLogger::new(env, AUDIT_LOGGER_NAME)?;
+        logger.reconfigure(|ctx| {
+            sys::local_frame(env, || {
+                let layout = build_layout(env, AUDIT_PATTERN, false)?;
+                let path = env.new_string(path)?;
+                let appender = env.new_object(FILE_APPENDER_CLASS, "(Lorg/apache/log4j/Layout;Ljava/lang/String;Z)V", &[JValue::Object(layout), JValue::Object(path.into()), JValue::Bool(true.into())])?;
+                env.call_method(appender, "setImmediateFlush", "(Z)V", &[JValue::Bool(true.into())])?;
+                env.call_method(appender, "setName", "(Ljava/lang/String;)V", &[JValue::Object(env.new_string(AUDIT_APPENDER_NAME)?.into())])?;
+
+                set_additivity(env, ctx.logger(), false)?;
+                add_appender(env, ctx.logger(), appender)
+            })
+        })?;
+
+        let audit_logger = Self {
+            logger,
+        };
+        audit_logger.verify()?;
+        Ok(audit_logger)
+    }
+
+    /// The underlying logger
+    pub fn logger(&self) -> &JavaLogger<'a> {
+        &self.logger
+    }
+
+    /// Check that the audit file appender is still attached
+    ///
+    /// # Error
+    /// - If the audit file appender is not attached
+    /// - If one of the underlying JNI calls fail
+    pub fn verify(&self) -> Result<()> {
+        let env = self.logger.inner.lock().expect("Failed to lock inner logger").env;
+        sys::local_frame(env, || match self.logger.get_appender(AUDIT_APPENDER_NAME)? {
+            Some(_) => Ok(()),
+            None => Err(Error::Unconfigured(format!("the audit appender '{}' is not attached to the '{}' logger", AUDIT_APPENDER_NAME, AUDIT_LOGGER_NAME))),
+        })
+    }
+
+    /// Record an audit event. `action` is available as `%X{action}`, a field named `user` is also available as `%X{user}`.
+    /// All fields are written as `key=value` pairs in the message
+    ///
+    /// # Error
+    /// - If the audit file appender is not attached
+    /// - If one of the underlying JNI calls fail
+    pub fn record(&self, action: &str, fields: &[(&str, &str)]) -> Result<()> {
+        self.verify()?;
+
+        let env = self.logger.inner.lock().expect("Failed to lock inner logger").env;
+        let user = fields.iter().find(|(key, _)| *key == "user").map(|(_, value)| *value).unwrap_or_default();
+        let message = fields.iter().map(|(key, value)| format!("{}={}", key, value)).collect::<Vec<_>>().join(" ");
+
+        mdc::with_value(env, "action", action, || {
+            mdc::with_value(env, "user", user, || self.logger.log(LogLevel::Info, &message))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+
+    #[test]
+    fn record() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = tmpdir.path().join("audit.log");
+        let audit = AuditLogger::new(&env, path.to_str().unwrap()).expect("Failed to create AuditLogger");
+
+        audit.record("login", &[("user", "alice"), ("ip", "10.0.0.1")]).expect("Failed to record audit event");
+
+        let content = std::fs::read_to_string(&path).expect("Failed to read audit file");
+        assert!(content.trim_end().ends_with(" user=alice action=login user=alice ip=10.0.0.1"));
+
+        audit.logger().reconfigure(|ctx| ctx.remove_all_appenders()).expect("Failed to remove appenders");
+        assert!(matches!(audit.verify(), Err(Error::Unconfigured(_))));
+        assert!(matches!(audit.record("logout", &[("user", "alice")]), Err(Error::Unconfigured(_))));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::error::Result;
+use crate::level::LogLevel;
+use crate::logger::JavaLogger;
+use crate::runtime::system_property;
+use crate::sys;
+use jni::objects::JObject;
+use jni::JNIEnv;
+
+const INET_ADDRESS_CLASS: &str = "java/net/InetAddress";
+const LOG_MANAGER_CLASS: &str = "org/apache/log4j/LogManager";
+/// Used for environment-derived fields which cannot be determined
+const UNKNOWN: &str = "unknown";
+
+/// Create an `AppInfo` with the name and version of the calling crate, captured at compile time.
+/// The build profile is set to `debug` or `release` depending on `debug_assertions`
+#[macro_export]
+macro_rules! app_info_from_cargo {
+    () => {
+        $crate::AppInfo::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")).profile(if cfg!(debug_assertions) { "debug" } else { "release" })
+    };
+}
+
+/// How `log_startup_banner` formats the banner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BannerStyle {
+    /// A single multi-line message, with a header line followed by one field per line
+    MultiLine,
+    /// A single line of `key=value` pairs, easy to parse for log shippers
+    KeyValue,
+}
+
+/// Metadata about the application, logged by `log_startup_banner`.
+/// The process id, hostname, Java version and log4j version are filled in when the banner is logged
+#[derive(Debug, Clone)]
+pub struct AppInfo {
+    name: String,
+    version: String,
+    git_sha: Option<String>,
+    profile: Option<String>,
+    style: BannerStyle,
+}
+
+impl AppInfo {
+    /// Create the metadata of application `name` at `version`, see also `app_info_from_cargo!`
+    pub fn new<N: Into<String>, V: Into<String>>(name: N, version: V) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            git_sha: None,
+            profile: None,
+            style: BannerStyle::MultiLine,
+        }
+    }
+
+    /// Set the git commit the application was built from
+    pub fn git_sha<S: Into<String>>(mut self, git_sha: S) -> Self {
+        self.git_sha = Some(git_sha.into());
+        self
+    }
+
+    /// Set the build profile, e.g. `release`
+    pub fn profile<S: Into<String>>(mut self, profile: S) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Set how the banner is formatted, defaults to `BannerStyle::MultiLine`
+    pub fn style(mut self, style: BannerStyle) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+/// Log a startup banner at INFO level, with the application's name, version, git sha and build profile,
+/// followed by the process id, hostname, Java version and log4j version.
+/// Environment-derived fields which cannot be determined are logged as `unknown`
+///
+/// # Params
+/// - `logger` The logger to log the banner to
+/// - `app` The application metadata
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub fn log_startup_banner(logger: &JavaLogger, app: &AppInfo) -> Result<()> {
+    let env = logger.inner.lock().expect("Failed to lock inner logger").env;
+    let mut fields = vec![("version", app.version.clone())];
+    fields.extend(app.git_sha.clone().map(|git_sha| ("git_sha", git_sha)));
+    fields.extend(app.profile.clone().map(|profile| ("profile", profile)));
+    fields.push(("pid", std::process::id().to_string()));
+    fields.push(("host", hostname(env)?.unwrap_or_else(|| UNKNOWN.to_string())));
+    fields.push(("java", system_property(env, "java.version")?.unwrap_or_else(|| UNKNOWN.to_string())));
+    fields.push(("log4j", log4j_version(env)?.unwrap_or_else(|| UNKNOWN.to_string())));
+
+    logger.log(LogLevel::Info, format_banner(&app.name, app.style, &fields))
+}
+
+/// Format the banner of application `name` with `fields`
+fn format_banner(name: &str, style: BannerStyle, fields: &[(&str, String)]) -> String {
+    match style {
+        BannerStyle::MultiLine => {
+            let width = fields.iter().map(|(key, _)| key.len()).max().unwrap_or_default();
+            let mut banner = format!("==== Starting {} ====", name);
+            for (key, value) in fields {
+                banner.push_str(&format!("\n{:width$} : {}", key, value, width = width));
+            }
+            banner
+        }
+        BannerStyle::KeyValue => std::iter::once(("app", name))
+            .chain(fields.iter().map(|(key, value)| (*key, value.as_str())))
+            .map(|(key, value)| {
+                // Quote values which would otherwise break up the pair
+                if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '=' || c == '"') {
+                    format!("{}={:?}", key, value)
+                } else {
+                    format!("{}={}", key, value)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+/// Get the hostname using `InetAddress.getLocalHost().getHostName()`.
+/// Returns `None` if the host cannot be resolved, the `UnknownHostException` is cleared
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+fn hostname(env: &JNIEnv) -> Result<Option<String>> {
+    sys::local_frame(env, || {
+        let local_host = match env.call_static_method(INET_ADDRESS_CLASS, "getLocalHost", "()Ljava/net/InetAddress;", &[]) {
+            Ok(local_host) => local_host.l()?,
+            Err(jni::errors::Error::JavaException) => {
+                env.exception_clear()?;
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let hostname = env.call_method(local_host, "getHostName", "()Ljava/lang/String;", &[])?.l()?;
+        java_string(env, hostname)
+    })
+}
+
+/// Get the log4j version from the manifest of the jar containing `LogManager`.
+/// Returns `None` if log4j is not loaded from a jar with an `Implementation-Version`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+fn log4j_version(env: &JNIEnv) -> Result<Option<String>> {
+    sys::local_frame(env, || {
+        let class = env.find_class(LOG_MANAGER_CLASS)?;
+        let package = env.call_method(class, "getPackage", "()Ljava/lang/Package;", &[])?.l()?;
+        if package.is_null() {
+            return Ok(None);
+        }
+
+        let version = env.call_method(package, "getImplementationVersion", "()Ljava/lang/String;", &[])?.l()?;
+        java_string(env, version)
+    })
+}
+
+/// Convert a possibly null `java.lang.String`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+fn java_string(env: &JNIEnv, string: JObject) -> Result<Option<String>> {
+    if string.is_null() {
+        return Ok(None);
+    }
+
+    Ok(Some(env.get_string(string.into())?.into()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support;
+
+    #[test]
+    fn startup_banner() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("StartupBanner").expect("Failed to create capturing logger");
+
+        let app = app_info_from_cargo!().git_sha("0123abc").style(BannerStyle::KeyValue);
+        log_startup_banner(&logger, &app).expect("Failed to log startup banner");
+        log_startup_banner(&logger, &app.style(BannerStyle::MultiLine)).expect("Failed to log startup banner");
+
+        let java_version = system_property(&env, "java.version").unwrap().unwrap();
+        let profile = if cfg!(debug_assertions) { "debug" } else { "release" };
+        let lines = output.lines().expect("Failed to read captured output");
+        let key_value = &lines[0];
+        assert!(key_value.starts_with(&format!("INFO app=log4j version=0.1.0 git_sha=0123abc profile={} ", profile)));
+        assert!(key_value.contains(&format!(" pid={} ", std::process::id())));
+        assert!(key_value.contains(&format!(" java={} ", java_version)));
+        assert!(key_value.contains(" host="));
+        assert!(key_value.contains(" log4j="));
+
+        assert_eq!(lines[1], "INFO ==== Starting log4j ====");
+        assert_eq!(lines[2], "version : 0.1.0");
+        assert_eq!(lines[3], "git_sha : 0123abc");
+        assert_eq!(lines[4], format!("profile : {}", profile));
+        assert_eq!(lines[5], format!("pid     : {}", std::process::id()));
+        assert!(lines[6].starts_with("host    : "));
+        assert_eq!(lines[7], format!("java    : {}", java_version));
+        assert!(lines[8].starts_with("log4j   : "));
+    }
+
+    #[test]
+    fn key_value_quoting() {
+        let fields = [("host", "my host".to_string()), ("empty", String::new())];
+        assert_eq!(format_banner("app", BannerStyle::KeyValue, &fields), r#"app=app host="my host" empty="""#);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::error::Result;
+use crate::level::LogLevel;
+use crate::logger::JavaLogger;
+use std::time::{Duration, Instant};
+
+/// The message logged on every iteration of `benchmark_logging`
+const BENCHMARK_MESSAGE: &str = "log4j-rs benchmark message";
+
+/// Measure the latency of the logging path, from Rust through JNI into log4j and its appenders.
+/// Logs `iterations` messages to the INFO level, followed by a summary with the total and the average duration per call.
+/// The summary is not included in the returned duration
+///
+/// # Params
+/// - `logger` The logger to benchmark, its appenders are part of the measurement
+/// - `iterations` The number of messages to log
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub fn benchmark_logging(logger: &JavaLogger, iterations: u32) -> Result<Duration> {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        logger.log(LogLevel::Info, BENCHMARK_MESSAGE)?;
+    }
+    let elapsed = start.elapsed();
+
+    let average = elapsed.checked_div(iterations).unwrap_or_default();
+    logger.log(LogLevel::Info, format!("Logged {} messages in {:?} ({:?} per message)", iterations, elapsed, average))?;
+    Ok(elapsed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+
+    #[test]
+    fn benchmark() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let logger = JavaLogger::new(&env, "com.example.Benchmark").expect("Failed to create JavaLogger");
+
+        let (elapsed, output) = logger.capture(|| benchmark_logging(&logger, 100)).expect("Failed to capture output");
+        let elapsed = elapsed.expect("Failed to run benchmark");
+        assert!(elapsed > Duration::ZERO);
+
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 101);
+        assert!(lines[..100].iter().all(|line| *line == BENCHMARK_MESSAGE));
+        assert!(lines[100].starts_with("Logged 100 messages in "));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::error::Result;
+use crate::sys::{self, MethodId};
+use jni::objects::JClass;
+use jni::JNIEnv;
+use std::ops::BitOr;
+
+/// Optional log4j methods available to a logger, which differ between log4j 1.2.x releases.
+/// Methods which are not available make the corresponding API return `Error::Unsupported`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    /// `Logger#trace(Object)`, added in log4j 1.2.12. Used by `JavaLogger::log_trace`
+    pub const TRACE: Self = Self(1);
+    /// `Category#getEffectiveLevel()`. Used by `JavaLogger::effective_level`
+    pub const EFFECTIVE_LEVEL: Self = Self(1 << 1);
+    /// `Category#getChainedPriority()`, deprecated in favour of `getEffectiveLevel`. Used by `JavaLogger::chained_priority`
+    pub const CHAINED_PRIORITY: Self = Self(1 << 2);
+
+    /// No optional methods
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Whether all methods in `other` are available
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The resolved optional methods, `None` when not available
+pub(crate) struct OptionalMethods<'a> {
+    pub(crate) trace: Option<MethodId<'a>>,
+    pub(crate) effective_level: Option<MethodId<'a>>,
+    pub(crate) chained_priority: Option<MethodId<'a>>,
+}
+
+impl<'a> OptionalMethods<'a> {
+    /// Resolve every optional method individually on `class`, so a missing method only disables its own API
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub(crate) fn resolve(env: &JNIEnv<'a>, class: JClass<'a>) -> Result<Self> {
+        Ok(Self {
+            trace: sys::optional_method_id(env, class, "trace", "(Ljava/lang/Object;)V")?,
+            effective_level: sys::optional_method_id(env, class, "getEffectiveLevel", "()Lorg/apache/log4j/Level;")?,
+            chained_priority: sys::optional_method_id(env, class, "getChainedPriority", "()Lorg/apache/log4j/Priority;")?,
+        })
+    }
+
+    /// The capabilities corresponding to the resolved methods
+    pub(crate) fn capabilities(&self) -> Capabilities {
+        [
+            (self.trace.is_some(), Capabilities::TRACE),
+            (self.effective_level.is_some(), Capabilities::EFFECTIVE_LEVEL),
+            (self.chained_priority.is_some(), Capabilities::CHAINED_PRIORITY),
+        ]
+        .iter()
+        .filter(|(available, _)| *available)
+        .fold(Capabilities::empty(), |capabilities, (_, capability)| capabilities | *capability)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+
+    #[test]
+    fn bitset() {
+        let capabilities = Capabilities::TRACE | Capabilities::CHAINED_PRIORITY;
+        assert!(capabilities.contains(Capabilities::TRACE));
+        assert!(!capabilities.contains(Capabilities::EFFECTIVE_LEVEL));
+        assert!(!capabilities.contains(Capabilities::TRACE | Capabilities::EFFECTIVE_LEVEL));
+        assert!(Capabilities::empty().contains(Capabilities::empty()));
+    }
+
+    #[test]
+    fn missing_trace() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        // Category never had trace, which was only added to its Logger subclass
+        let category_class = env.find_class("org/apache/log4j/Category").unwrap();
+        let methods = OptionalMethods::resolve(&env, category_class).expect("Failed to resolve optional methods");
+        assert!(!env.exception_check().unwrap());
+        assert_eq!(methods.capabilities(), Capabilities::EFFECTIVE_LEVEL | Capabilities::CHAINED_PRIORITY);
+    }
+}
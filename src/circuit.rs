@@ -0,0 +1,255 @@
+use crate::emergency::EmergencyLog;
+use crate::level::LogLevel;
+use crate::plain::{plain_format, PlainRecord};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Called with the new state whenever a circuit breaker changes state
+type TransitionCallback = Box<dyn Fn(CircuitState) + Send + Sync>;
+
+/// The state of a logger's circuit breaker, see `JavaLogger::set_circuit_breaker`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Messages are delivered to log4j
+    Closed,
+    /// Delivery failed repeatedly, messages go to the fallback until the cool-down has passed
+    Open,
+    /// The cool-down has passed and a single probe delivery is in progress, other messages go to the fallback
+    HalfOpen,
+}
+
+/// Where messages go while a circuit breaker is open
+#[derive(Debug, Clone)]
+pub enum CircuitFallback {
+    /// Write the message to stderr, formatted with `plain_format`
+    Stderr,
+    /// Write the message to a preopened file, formatted with `plain_format`
+    File(Arc<EmergencyLog>),
+    /// Drop the message
+    Discard,
+}
+
+/// Configuration of a circuit breaker, which stops calling into the JVM after repeated delivery failures.
+/// A broken appender or a dying JVM makes every failed call expensive, which can make an outage worse
+pub struct CircuitBreakerConfig {
+    failure_threshold: u32,
+    cool_down: Duration,
+    fallback: CircuitFallback,
+    on_transition: Option<TransitionCallback>,
+}
+
+impl CircuitBreakerConfig {
+    /// Open the circuit after `failure_threshold` consecutive failures, and probe again after `cool_down`.
+    /// A threshold of 0 is treated as 1. Messages go to `CircuitFallback::Stderr` while the circuit is open, unless configured otherwise
+    pub fn new(failure_threshold: u32, cool_down: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cool_down,
+            fallback: CircuitFallback::Stderr,
+            on_transition: None,
+        }
+    }
+
+    /// Set where messages go while the circuit is open
+    pub fn fallback(mut self, fallback: CircuitFallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Call `callback` with the new state on every state change.
+    /// It is called while the logger is locked, so it must not log through the same JavaLogger
+    pub fn on_transition<F: Fn(CircuitState) + Send + Sync + 'static>(mut self, callback: F) -> Self {
+        self.on_transition = Some(Box::new(callback));
+        self
+    }
+}
+
+/// The state machine behind `JavaLogger::set_circuit_breaker`
+pub(crate) struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    /// Used in the fallback output, the logger's name can't be fetched from Java once the circuit is open
+    logger_name: String,
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Instant,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(config: CircuitBreakerConfig, logger_name: String, now: Instant) -> Self {
+        Self {
+            config,
+            logger_name,
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            opened_at: now,
+        }
+    }
+
+    pub(crate) fn state(&self) -> CircuitState {
+        self.state
+    }
+
+    /// Whether a delivery may be attempted at `now`.
+    /// Once the cool-down has passed an open circuit becomes half-open, and the next delivery is the probe
+    pub(crate) fn allow(&mut self, now: Instant) -> bool {
+        match self.state {
+            CircuitState::Closed => true,
+            CircuitState::Open if now.saturating_duration_since(self.opened_at) >= self.config.cool_down => {
+                self.transition(CircuitState::HalfOpen);
+                true
+            }
+            CircuitState::Open | CircuitState::HalfOpen => false,
+        }
+    }
+
+    /// Record the outcome of a delivery allowed by `allow`
+    pub(crate) fn record(&mut self, success: bool, now: Instant) {
+        if success {
+            self.consecutive_failures = 0;
+            if self.state == CircuitState::HalfOpen {
+                self.transition(CircuitState::Closed);
+            }
+            return;
+        }
+
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let open = match self.state {
+            CircuitState::Closed => self.consecutive_failures >= self.config.failure_threshold,
+            // A failed probe restarts the cool-down
+            CircuitState::HalfOpen => true,
+            // The outcome of a delivery which started before the circuit opened
+            CircuitState::Open => false,
+        };
+
+        if open {
+            self.opened_at = now;
+            self.transition(CircuitState::Open);
+        }
+    }
+
+    /// Send a message which is not delivered to log4j to the fallback
+    pub(crate) fn divert(&self, level: LogLevel, message: &str, timestamp: SystemTime) {
+        let line = || {
+            plain_format(&PlainRecord {
+                level,
+                logger: &self.logger_name,
+                message,
+                timestamp: Some(timestamp),
+            })
+        };
+
+        // There is nowhere left to report a failing fallback to
+        match &self.config.fallback {
+            CircuitFallback::Stderr => eprintln!("{}", line()),
+            CircuitFallback::File(file) => {
+                let _ = file.write(&line());
+            }
+            CircuitFallback::Discard => {}
+        }
+    }
+
+    fn transition(&mut self, state: CircuitState) {
+        self.state = state;
+        if let Some(callback) = &self.config.on_transition {
+            callback(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn failing_then_recovering() {
+        let transitions = Arc::new(Mutex::new(Vec::new()));
+        let recorded = transitions.clone();
+        let config = CircuitBreakerConfig::new(3, Duration::from_secs(10)).fallback(CircuitFallback::Discard).on_transition(move |state| recorded.lock().unwrap().push(state));
+
+        let start = Instant::now();
+        let mut breaker = CircuitBreaker::new(config, "com.example".to_string(), start);
+
+        // A success resets the count of consecutive failures
+        for success in [false, false, true, false, false] {
+            assert!(breaker.allow(start));
+            breaker.record(success, start);
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        assert!(breaker.allow(start));
+        breaker.record(false, start);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow(start + Duration::from_secs(9)));
+
+        // The probe fails, the cool-down restarts
+        let probe = start + Duration::from_secs(10);
+        assert!(breaker.allow(probe));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(!breaker.allow(probe), "Only a single probe may be in progress");
+        breaker.record(false, probe);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow(probe + Duration::from_secs(9)));
+
+        // The backend recovered
+        assert!(breaker.allow(probe + Duration::from_secs(10)));
+        breaker.record(true, probe + Duration::from_secs(10));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        use CircuitState::*;
+        assert_eq!(*transitions.lock().unwrap(), vec![Open, HalfOpen, Open, HalfOpen, Closed]);
+    }
+
+    #[test]
+    fn file_fallback() {
+        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = tmpdir.path().join("fallback.log");
+        let file = Arc::new(EmergencyLog::preopen(&path).expect("Failed to open fallback file"));
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig::new(1, Duration::from_secs(1)).fallback(CircuitFallback::File(file)), "com.example".to_string(), Instant::now());
+
+        breaker.divert(LogLevel::Warn, "Diverted", SystemTime::UNIX_EPOCH);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "1970-01-01 00:00:00.000 WARN  com.example - Diverted\n");
+    }
+
+    #[test]
+    fn logger_circuit() {
+        use crate::test::JVM;
+        use crate::test_support::{self, ManualClock};
+        use crate::{AppenderHandle, LogOutcome};
+
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Circuit").expect("Failed to create capturing logger");
+        let clock = Arc::new(ManualClock::new());
+        logger.set_clock(clock.clone());
+
+        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = tmpdir.path().join("fallback.log");
+        let file = Arc::new(EmergencyLog::preopen(&path).expect("Failed to open fallback file"));
+        logger.set_circuit_breaker(CircuitBreakerConfig::new(2, Duration::from_secs(5)).fallback(CircuitFallback::File(file))).expect("Failed to set circuit breaker");
+
+        // Not an appender, so delivery fails with a NoSuchMethodError
+        let broken = AppenderHandle::from_jobject(env.new_string("Not an appender").unwrap().into());
+        for _ in 0..2 {
+            assert!(logger.log_to(&broken, LogLevel::Info, "Lost").is_err());
+            env.exception_clear().unwrap();
+        }
+        assert_eq!(logger.circuit_state(), CircuitState::Open);
+
+        logger.log(LogLevel::Warn, "Diverted").expect("Diverted messages are not errors");
+        assert!(matches!(logger.log_with_deadline(LogLevel::Warn, "Diverted too", Duration::from_secs(1)), Ok(LogOutcome::Diverted)));
+        assert!(output.lines().expect("Failed to read captured output").is_empty());
+
+        // The probe after the cool-down succeeds
+        clock.advance(Duration::from_secs(5));
+        logger.log(LogLevel::Info, "Recovered").expect("Failed to log to INFO level");
+        assert_eq!(logger.circuit_state(), CircuitState::Closed);
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec!["INFO Recovered"]);
+
+        let name = scope.logger_name("Circuit");
+        let fallback = std::fs::read_to_string(&path).unwrap();
+        let fallback: Vec<&str> = fallback.lines().map(|line| line.split_once(' ').unwrap().1.split_once(' ').unwrap().1).collect();
+        assert_eq!(fallback, vec![format!("WARN  {} - Diverted", name), format!("WARN  {} - Diverted too", name)]);
+    }
+}
@@ -0,0 +1,25 @@
+use std::time::{Instant, SystemTime};
+
+/// Source of the current time for time-dependent features such as `log_since_epoch` and `log_since_checkpoint`.
+/// Defaults to `SystemClock`, tests can substitute a clock they control, e.g. `test_support::ManualClock`
+pub trait Clock: Send + Sync {
+    /// The current monotonic time
+    fn now(&self) -> Instant;
+
+    /// The current wall-clock time
+    fn system_now(&self) -> SystemTime;
+}
+
+/// The real clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
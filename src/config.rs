@@ -0,0 +1,234 @@
+use crate::appender::AppenderConfig;
+use crate::error::{Error, Result};
+use crate::logger::JavaLogger;
+use crate::sys;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+const LOG_MANAGER_CLASS: &str = "org/apache/log4j/LogManager";
+const LEVEL_CLASS: &str = "org/apache/log4j/Level";
+
+/// The schema of a configuration file for `configure_from_toml`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    /// Appenders by name, referenced from the logger sections
+    #[serde(default)]
+    appenders: HashMap<String, AppenderConfig>,
+    root: Option<LoggerSection>,
+    /// Loggers by their full name
+    #[serde(default)]
+    loggers: BTreeMap<String, LoggerSection>,
+}
+
+/// The configuration of a single logger
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LoggerSection {
+    /// A level name understood by `Level.toLevel(String)`, e.g. `debug` or `off`
+    level: Option<String>,
+    /// Names of entries in `appenders`, replacing the appenders already attached when present
+    appenders: Option<Vec<String>>,
+    additivity: Option<bool>,
+}
+
+/// Configure log4j from a TOML file, a Rust-native alternative to `log4j.properties`.
+/// Appenders are defined once by name and attached to the root logger and other loggers by referencing that name:
+///
+/// ```toml
+/// [appenders.console]
+/// kind = "console"
+/// pattern = "%d %p %c - %m%n"
+///
+/// [appenders.errors]
+/// kind = "error_file"
+/// path = "logs/errors.log"
+/// pattern = "%d %c - %m%n"
+///
+/// [root]
+/// level = "info"
+/// appenders = ["console"]
+///
+/// [loggers."com.example.db"]
+/// level = "debug"
+/// appenders = ["console", "errors"]
+/// additivity = false
+/// ```
+///
+/// The appender kinds are those of `AppenderConfig`: `console`, `file` and `error_file`.
+/// Every logger referencing an appender gets an appender of its own, so a file should only be referenced once.
+/// The whole file is validated before any logger is changed. Loggers not mentioned in the file are left as they are
+///
+/// # Params
+/// - `path` The TOML file to read
+///
+/// # Error
+/// - If the file cannot be read
+/// - If the file is not valid TOML, does not match the schema, references undefined appenders or contains unknown levels
+/// - If a pattern is invalid or a file cannot be opened
+/// - If one of the underlying JNI calls fail
+pub fn configure_from_toml<'a>(env: &'a JNIEnv<'a>, path: &str) -> Result<()> {
+    let contents = std::fs::read_to_string(path).map_err(|source| Error::ReadConfig {
+        path: Path::new(path).to_path_buf(),
+        source,
+    })?;
+    let config: ConfigFile = toml::from_str(&contents).map_err(|e| Error::InvalidConfig(e.to_string()))?;
+
+    sys::local_frame(env, || {
+        let root = config.root.as_ref().map(|section| (None, section));
+        let sections: Vec<(Option<&str>, &LoggerSection)> = root.into_iter().chain(config.loggers.iter().map(|(name, section)| (Some(name.as_str()), section))).collect();
+
+        // Validate everything first, so an invalid file leaves the configuration untouched
+        let mut resolved = Vec::with_capacity(sections.len());
+        for (name, section) in sections {
+            let level = section.level.as_deref().map(|level| to_java_level(env, level)).transpose()?;
+            let appenders = match &section.appenders {
+                Some(names) => Some(
+                    names
+                        .iter()
+                        .map(|appender| config.appenders.get(appender).cloned().ok_or_else(|| Error::InvalidConfig(format!("undefined appender \"{}\"", appender))))
+                        .collect::<Result<Vec<_>>>()?,
+                ),
+                None => None,
+            };
+            resolved.push((name, section, level, appenders));
+        }
+
+        for (name, section, level, appenders) in resolved {
+            let logger = match name {
+                Some(name) => JavaLogger::new(env, name)?,
+                None => JavaLogger::from_jobject(env, env.call_static_method(LOG_MANAGER_CLASS, "getRootLogger", "()Lorg/apache/log4j/Logger;", &[])?.l()?)?,
+            };
+
+            logger.reconfigure(|ctx| {
+                if let Some(level) = level {
+                    env.call_method(ctx.logger(), "setLevel", "(Lorg/apache/log4j/Level;)V", &[JValue::Object(level)])?;
+                }
+                if let Some(additivity) = section.additivity {
+                    env.call_method(ctx.logger(), "setAdditivity", "(Z)V", &[JValue::Bool(additivity.into())])?;
+                }
+                if appenders.is_some() {
+                    ctx.remove_all_appenders()?;
+                }
+
+                Ok(())
+            })?;
+
+            if let Some(appenders) = appenders {
+                logger.add_appenders_atomic(&appenders)?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Get the `org.apache.log4j.Level` named `name`, case-insensitively, using `Level.toLevel(String, Level)`
+///
+/// # Error
+/// - If there is no level named `name`
+/// - If one of the underlying JNI calls fail
+fn to_java_level<'a>(env: &JNIEnv<'a>, name: &str) -> Result<JObject<'a>> {
+    let java_name = env.new_string(name)?;
+    let level = env
+        .call_static_method(LEVEL_CLASS, "toLevel", "(Ljava/lang/String;Lorg/apache/log4j/Level;)Lorg/apache/log4j/Level;", &[JValue::Object(java_name.into()), JValue::Object(JObject::null())])?
+        .l()?;
+    if level.is_null() {
+        return Err(Error::InvalidConfig(format!("unknown level \"{}\"", name)));
+    }
+
+    Ok(level)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support;
+    use crate::LogLevel;
+
+    #[test]
+    fn schema() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [appenders.errors]
+            kind = "error_file"
+            path = "logs/errors.log"
+            pattern = "%m%n"
+
+            [root]
+            level = "info"
+            "#,
+        )
+        .expect("Failed to parse configuration");
+
+        assert_eq!(
+            config.appenders["errors"],
+            AppenderConfig::ErrorFile {
+                path: "logs/errors.log".into(),
+                pattern: "%m%n".to_string(),
+            }
+        );
+        let root = config.root.expect("Missing root section");
+        assert_eq!(root.level.as_deref(), Some("info"));
+        assert!(root.appenders.is_none());
+        assert!(config.loggers.is_empty());
+
+        assert!(toml::from_str::<ConfigFile>("[appenders.console]\nkind = \"syslog\"\n").is_err());
+    }
+
+    #[test]
+    fn toml_config() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let name = scope.logger_name("Toml");
+        let logger = scope.logger("Toml").expect("Failed to create JavaLogger");
+        scope.set_level(&logger, LogLevel::Info).expect("Failed to set level");
+
+        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = tmpdir.path().join("log4j.toml");
+        let config = format!(
+            r#"
+            [appenders.console]
+            kind = "console"
+            pattern = "%p %m%n"
+
+            [loggers."{}"]
+            level = "warn"
+            appenders = ["console"]
+            additivity = false
+            "#,
+            name
+        );
+        std::fs::write(&path, config).unwrap();
+        configure_from_toml(&env, path.to_str().unwrap()).expect("Failed to apply configuration");
+
+        assert_eq!(logger.effective_level().expect("Failed to get effective level"), LogLevel::Warn);
+        assert!(!logger.get_additivity().expect("Failed to get additivity"));
+        logger
+            .reconfigure(|ctx| {
+                let appenders = ctx.appenders()?;
+                assert_eq!(appenders.len(), 1);
+                assert!(env.is_instance_of(appenders[0], "org/apache/log4j/ConsoleAppender")?);
+                Ok(())
+            })
+            .expect("Failed to inspect appenders");
+
+        // Nothing is applied when the file is invalid
+        std::fs::write(&path, format!("[loggers.\"{}\"]\nlevel = \"error\"\nappenders = [\"missing\"]\n", name)).unwrap();
+        match configure_from_toml(&env, path.to_str().unwrap()) {
+            Err(Error::InvalidConfig(reason)) => assert_eq!(reason, "undefined appender \"missing\""),
+            _ => panic!("Expected InvalidConfig"),
+        }
+        assert_eq!(logger.effective_level().expect("Failed to get effective level"), LogLevel::Warn);
+
+        std::fs::write(&path, format!("[loggers.\"{}\"]\nlevel = \"loud\"\n", name)).unwrap();
+        assert!(matches!(configure_from_toml(&env, path.to_str().unwrap()), Err(Error::InvalidConfig(_))));
+
+        logger.reconfigure(|ctx| ctx.remove_all_appenders()).expect("Failed to remove appenders");
+    }
+}
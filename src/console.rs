@@ -0,0 +1,97 @@
+use crate::appender::{build_layout, AppenderHandle};
+use crate::error::Result;
+use crate::sys;
+use jni::objects::JValue;
+use jni::JNIEnv;
+use std::io::IsTerminal;
+
+/// Environment variable overriding the terminal detection of `smart_console_appender`, either `tty` or `pipe`
+pub const CONSOLE_MODE_ENV: &str = "LOG4J_RS_CONSOLE";
+const CONSOLE_APPENDER_CLASS: &str = "org/apache/log4j/ConsoleAppender";
+
+/// The stream a console appender writes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConsoleTarget {
+    Stdout,
+    Stderr,
+}
+
+impl ConsoleTarget {
+    /// The target name `ConsoleAppender` expects
+    fn java_name(self) -> &'static str {
+        match self {
+            Self::Stdout => "System.out",
+            Self::Stderr => "System.err",
+        }
+    }
+}
+
+/// Create a `ConsoleAppender` whose pattern depends on whether it writes to a terminal, without attaching it.
+/// Rich output goes to stdout if it is a terminal, otherwise to stderr if that is one, e.g. when stdout is piped into another tool.
+/// If neither is a terminal the compact pattern is written to stdout.
+/// Set `LOG4J_RS_CONSOLE` to `tty` or `pipe` to skip the detection and write the respective pattern to stdout
+///
+/// # Params
+/// - `tty_pattern` The conversion pattern used when writing to a terminal
+/// - `pipe_pattern` The conversion pattern used otherwise, e.g. a machine-readable one
+///
+/// # Error
+/// - If the chosen pattern is invalid
+/// - If one of the underlying JNI calls fail
+pub fn smart_console_appender<'a>(env: &JNIEnv<'a>, tty_pattern: &str, pipe_pattern: &str) -> Result<AppenderHandle<'a>> {
+    let mode = std::env::var(CONSOLE_MODE_ENV).ok();
+    let (tty, target) = choose_console(std::io::stdout().is_terminal(), std::io::stderr().is_terminal(), mode.as_deref());
+    let appender = sys::local_frame_object(env, || {
+        let layout = build_layout(env, if tty { tty_pattern } else { pipe_pattern }, false)?;
+        let target = env.new_string(target.java_name())?;
+        Ok(env.new_object(CONSOLE_APPENDER_CLASS, "(Lorg/apache/log4j/Layout;Ljava/lang/String;)V", &[JValue::Object(layout), JValue::Object(target.into())])?)
+    })?;
+
+    Ok(AppenderHandle::from_jobject(appender))
+}
+
+/// Decide whether to use the terminal pattern, and which stream to write to.
+/// Unknown values of `mode` are ignored
+fn choose_console(stdout_tty: bool, stderr_tty: bool, mode: Option<&str>) -> (bool, ConsoleTarget) {
+    match mode {
+        Some("tty") => (true, ConsoleTarget::Stdout),
+        Some("pipe") => (false, ConsoleTarget::Stdout),
+        _ if stdout_tty => (true, ConsoleTarget::Stdout),
+        _ if stderr_tty => (true, ConsoleTarget::Stderr),
+        _ => (false, ConsoleTarget::Stdout),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+
+    #[test]
+    fn decision_matrix() {
+        assert_eq!(choose_console(true, true, None), (true, ConsoleTarget::Stdout));
+        assert_eq!(choose_console(true, false, None), (true, ConsoleTarget::Stdout));
+        assert_eq!(choose_console(false, true, None), (true, ConsoleTarget::Stderr));
+        assert_eq!(choose_console(false, false, None), (false, ConsoleTarget::Stdout));
+
+        assert_eq!(choose_console(false, true, Some("pipe")), (false, ConsoleTarget::Stdout));
+        assert_eq!(choose_console(true, false, Some("pipe")), (false, ConsoleTarget::Stdout));
+        assert_eq!(choose_console(false, false, Some("tty")), (true, ConsoleTarget::Stdout));
+        assert_eq!(choose_console(false, true, Some("TTY")), (true, ConsoleTarget::Stderr));
+    }
+
+    #[test]
+    fn installed_pattern() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        let handle = smart_console_appender(&env, "%d %-5p [%t] %c - %m%n", "%p|%c|%m%n").expect("Failed to create console appender");
+        let mode = std::env::var(CONSOLE_MODE_ENV).ok();
+        let (tty, target) = choose_console(std::io::stdout().is_terminal(), std::io::stderr().is_terminal(), mode.as_deref());
+        let expected = if tty { "%d %-5p [%t] %c - %m%n" } else { "%p|%c|%m%n" };
+        assert_eq!(handle.layout_pattern(&env).expect("Failed to get pattern").as_deref(), Some(expected));
+
+        let installed_target = env.call_method(handle.as_jobject(), "getTarget", "()Ljava/lang/String;", &[]).unwrap().l().unwrap();
+        assert_eq!(String::from(env.get_string(installed_target.into()).unwrap()), target.java_name());
+    }
+}
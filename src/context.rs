@@ -0,0 +1,217 @@
+use crate::error::Result;
+use crate::mdc;
+use crate::runtime::global_vm;
+use crate::sys;
+use jni::objects::{GlobalRef, JValue};
+use jni::{JNIEnv, JavaVM};
+use std::collections::HashMap;
+
+const NDC_CLASS: &str = "org/apache/log4j/NDC";
+
+/// The diagnostic context of a thread: its MDC and NDC stack
+struct ThreadContext {
+    mdc: HashMap<String, String>,
+    /// A copy of the NDC stack, `None` if it is empty
+    ndc: Option<GlobalRef>,
+}
+
+/// Wrap `f` so it runs with the diagnostic context of the calling thread, whichever thread ends up running it.
+/// The MDC, including correlation ids kept in it, and the NDC stack are captured when `propagate_context` is called,
+/// installed before `f` runs, and the context the running thread had before is restored afterwards, also if `f` panics.
+/// Meant for closures handed to thread pools, e.g. `std::thread::spawn`, rayon or tokio's `spawn_blocking`.
+///
+/// The JavaVM set with `set_global_vm` is used, the running thread is attached to it for the duration of `f` if needed.
+/// If no global JavaVM is set or the context cannot be captured or installed, `f` runs without it.
+/// With debug assertions enabled, failing to install or restore a context panics instead
+pub fn propagate_context<F: FnOnce() -> R, R>(f: F) -> impl FnOnce() -> R {
+    let captured = global_vm().and_then(|vm| {
+        let context = vm.attach_current_thread().ok().and_then(|env| capture(&env).ok())?;
+        Some((vm, context))
+    });
+
+    move || match captured {
+        Some((vm, context)) => run_in_context(&vm, &context, f),
+        None => f(),
+    }
+}
+
+/// Run `f` with `context` installed on the current thread, restoring the previous context afterwards
+fn run_in_context<F: FnOnce() -> R, R>(vm: &JavaVM, context: &ThreadContext, f: F) -> R {
+    let env = match vm.attach_current_thread() {
+        Ok(env) => env,
+        Err(_) => return f(),
+    };
+
+    let previous = match capture(&env) {
+        Ok(previous) => previous,
+        Err(_) => return f(),
+    };
+
+    let _restore = Restore {
+        env: &env,
+        previous,
+    };
+    // A partially installed context is still restored by the guard
+    let installed = install(&env, context);
+    report_install_failure(&env, installed, "propagated");
+    f()
+}
+
+/// Panic if installing a context failed and debug assertions are enabled, otherwise clear the pending exception so the thread can go on.
+/// Doesn't panic while the thread is already panicking
+fn report_install_failure(env: &JNIEnv, installed: Result<()>, which: &str) {
+    if let Err(e) = installed {
+        let _ = env.exception_clear();
+        if !std::thread::panicking() {
+            debug_assert!(false, "Failed to install the {} diagnostic context: {}", which, e);
+        }
+    }
+}
+
+/// Restores a thread's previous context when dropped, including while unwinding
+struct Restore<'e, 'a> {
+    env: &'e JNIEnv<'a>,
+    previous: ThreadContext,
+}
+
+impl Drop for Restore<'_, '_> {
+    fn drop(&mut self) {
+        // There is no caller to report a failure to
+        let restored = install(self.env, &self.previous);
+        report_install_failure(self.env, restored, "previous");
+    }
+}
+
+/// Capture the MDC and a copy of the NDC stack of the current thread, using `NDC#cloneStack()`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+fn capture(env: &JNIEnv) -> Result<ThreadContext> {
+    let ndc = sys::local_frame(env, || {
+        if env.call_static_method(NDC_CLASS, "getDepth", "()I", &[])?.i()? == 0 {
+            return Ok(None);
+        }
+
+        let stack = env.call_static_method(NDC_CLASS, "cloneStack", "()Ljava/util/Stack;", &[])?.l()?;
+        Ok(Some(env.new_global_ref(stack)?))
+    })?;
+
+    Ok(ThreadContext {
+        mdc: mdc::snapshot(env)?,
+        ndc,
+    })
+}
+
+/// Replace the MDC and NDC stack of the current thread with `context`.
+/// The NDC stack is copied before it is installed with `NDC#inherit(Stack)`, which uses the stack as is
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+fn install(env: &JNIEnv, context: &ThreadContext) -> Result<()> {
+    mdc::replace(env, &context.mdc)?;
+    sys::local_frame(env, || {
+        env.call_static_method(NDC_CLASS, "clear", "()V", &[])?;
+        if let Some(stack) = &context.ndc {
+            let copy = env.call_method(stack.as_obj(), "clone", "()Ljava/lang/Object;", &[])?.l()?;
+            env.call_static_method(NDC_CLASS, "inherit", "(Ljava/util/Stack;)V", &[JValue::Object(copy)])?;
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support;
+    use crate::{set_global_vm, JavaLogger, LogLevel};
+    use std::sync::Mutex;
+
+    /// Set in the child process running `rayon_workers`
+    const CONTEXT_CHILD_ENV: &str = "LOG4J_RS_CONTEXT_CHILD";
+    const WORKERS: usize = 10;
+
+    fn push_ndc(env: &JNIEnv, message: &str) {
+        env.call_static_method(NDC_CLASS, "push", "(Ljava/lang/String;)V", &[JValue::Object(env.new_string(message).unwrap().into())]).unwrap();
+    }
+
+    #[test]
+    fn rayon_workers() {
+        if std::env::var(CONTEXT_CHILD_ENV).is_err() {
+            // The global JavaVM can only be set once per process, and runtime::test::global_vm expects to set it
+            let status = std::process::Command::new(std::env::current_exe().expect("Failed to get test executable"))
+                .args(["--exact", "context::test::rayon_workers"])
+                .env(CONTEXT_CHILD_ENV, "1")
+                .status()
+                .expect("Failed to run child process");
+            assert!(status.success());
+            return;
+        }
+
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        set_global_vm(jvm.clone()).expect("Failed to set global VM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (_logger, output) = scope.capture_with_pattern("Propagate", "%X{requestId} %x %m%n").expect("Failed to create capturing logger");
+        let name = scope.logger_name("Propagate");
+
+        mdc::put(&env, "requestId", "req-7").unwrap();
+        push_ndc(&env, "checkout");
+        let tasks: Vec<_> = (0..WORKERS)
+            .map(|i| {
+                let name = name.clone();
+                Some(propagate_context(move || {
+                    let vm = global_vm().expect("Global VM is not set");
+                    let env = vm.attach_current_thread().expect("Failed to attach worker thread to the JVM");
+                    let logger = JavaLogger::new(&env, &name).expect("Failed to create JavaLogger");
+                    logger.log(LogLevel::Info, format!("Task {}", i)).expect("Failed to log to INFO level");
+                }))
+            })
+            .collect();
+        let tasks = Mutex::new(tasks);
+
+        // Broadcasting runs one task on each of the worker threads
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(WORKERS).build().expect("Failed to build thread pool");
+        pool.broadcast(|worker| {
+            let task = tasks.lock().unwrap()[worker.index()].take().expect("Task ran twice");
+            task();
+        });
+
+        let mut lines = output.lines().expect("Failed to read captured output");
+        lines.sort();
+        let expected: Vec<String> = (0..WORKERS).map(|i| format!("req-7 checkout Task {}", i)).collect();
+        assert_eq!(lines, expected);
+
+        // The running thread gets its own context back, even if the task panics
+        let task = propagate_context(|| panic!("Task failed"));
+        mdc::replace(&env, &std::iter::once(("requestId".to_string(), "req-8".to_string())).collect()).unwrap();
+        env.call_static_method(NDC_CLASS, "clear", "()V", &[]).unwrap();
+        push_ndc(&env, "refund");
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(task)).is_err());
+
+        assert_eq!(mdc::get(&env, "requestId").unwrap().as_deref(), Some("req-8"));
+        let ndc = env.call_static_method(NDC_CLASS, "get", "()Ljava/lang/String;", &[]).unwrap().l().unwrap();
+        assert_eq!(String::from(env.get_string(ndc.into()).unwrap()), "refund");
+    }
+
+    #[test]
+    fn install_and_restore() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let _scope = test_support::isolated(&env, "com.example");
+
+        mdc::put(&env, "requestId", "req-9").unwrap();
+        let context = capture(&env).expect("Failed to capture context");
+        mdc::put(&env, "requestId", "req-10").unwrap();
+        mdc::put(&env, "stale", "value").unwrap();
+
+        // Install failures panic in debug builds, so this also checks that installing works with the harness log4j
+        let seen = run_in_context(&jvm, &context, || mdc::snapshot(&env).expect("Failed to take MDC snapshot"));
+        assert_eq!(seen, context.mdc);
+
+        let restored = mdc::snapshot(&env).expect("Failed to take MDC snapshot");
+        assert_eq!(restored.get("requestId").map(String::as_str), Some("req-10"));
+        assert_eq!(restored.get("stale").map(String::as_str), Some("value"));
+    }
+}
@@ -0,0 +1,314 @@
+use crate::error::{Error, Result};
+use crate::level::LogLevel;
+use crate::logger::{logging_paused, JavaLogger};
+use crate::mdc::{self, MdcContext};
+use crate::sys;
+use jni::objects::{GlobalRef, JValue};
+use jni::{JNIEnv, JavaVM};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// Number of threads delivering messages for `log_with_deadline`
+const DELIVERY_THREADS: usize = 2;
+
+/// A message delivery, run on one of the delivery threads
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The queue of the delivery threads, started on the first `log_with_deadline` call
+static DELIVERY_POOL: OnceLock<Mutex<Sender<Job>>> = OnceLock::new();
+
+/// The result of `JavaLogger::log_with_deadline`
+#[derive(Debug)]
+pub enum LogOutcome {
+    /// The message was delivered to log4j within the deadline
+    Delivered,
+    /// The message was not delivered within the deadline, delivery continues in the background
+    TimedOut,
+    /// Delivery failed within the deadline
+    Failed(Error),
+    /// The circuit breaker is open, the message was sent to its fallback, see `JavaLogger::set_circuit_breaker`
+    Diverted,
+    /// Logging is paused, the message was dropped, see `set_logging_paused`
+    Paused,
+}
+
+impl<'a> JavaLogger<'a> {
+    /// Log without blocking the caller for longer than `deadline`, e.g. from a watchdog which must not hang on a wedged appender.
+    /// The message is delivered by one of a small pool of threads, which are attached to the JVM when first used and reused afterwards.
+    /// The MDC of the calling thread, including the trace id configured with `with_trace_id`, is installed on the delivering thread.
+    ///
+    /// When the deadline passes, delivery continues in the background and its outcome is discarded.
+    /// While all delivery threads are blocked, further messages are queued until one of them is freed.
+    /// The circuit breaker is respected. When delivery completes, even after the deadline, its outcome is recorded with the breaker,
+    /// and a delivered message is counted and recorded like one logged with `log`, see `counts` and `HistoryLogger`
+    ///
+    /// # Params
+    /// - `level` The level to log at
+    /// - `message` The message, run through the message pipeline before it is handed to the delivery thread
+    /// - `deadline` The maximum time to wait for delivery
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls needed to hand over the message fail
+    pub fn log_with_deadline<S: AsRef<str>>(&self, level: LogLevel, message: S, deadline: Duration) -> Result<LogOutcome> {
+        let delivered = self.hand_over(level, message.as_ref())?;
+        Ok(match delivered.recv_timeout(deadline) {
+            Ok(outcome) => outcome,
+            Err(RecvTimeoutError::Timeout) => LogOutcome::TimedOut,
+            Err(RecvTimeoutError::Disconnected) => LogOutcome::Failed(Error::DeliveryAborted),
+        })
+    }
+
+    /// Log without waiting for delivery, using the delivery threads of `log_with_deadline`.
+    /// The returned receiver yields `LogOutcome::Delivered` once log4j has processed this specific message, or `LogOutcome::Failed`.
+    /// When the message isn't handed to log4j at all, `LogOutcome::Diverted` or `LogOutcome::Paused` is available right away.
+    /// Waiting on the receiver before a deliberate shutdown ensures critical messages were written.
+    /// The message is counted and recorded before `LogOutcome::Delivered` is sent
+    ///
+    /// # Params
+    /// - `level` The level to log at
+    /// - `message` The message, run through the message pipeline before it is handed to the delivery thread
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls needed to hand over the message fail
+    pub fn log_acked<S: AsRef<str>>(&self, level: LogLevel, message: S) -> Result<Receiver<LogOutcome>> {
+        self.hand_over(level, message.as_ref())
+    }
+
+    /// Queue `message` for delivery with the MDC of the calling thread.
+    /// The returned receiver yields the outcome when delivery completes, or right away if the message isn't delivered
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls needed to hand over the message fail
+    fn hand_over(&self, level: LogLevel, message: &str) -> Result<Receiver<LogOutcome>> {
+        let (done, outcome) = mpsc::channel();
+        if logging_paused() {
+            let _ = done.send(LogOutcome::Paused);
+            return Ok(outcome);
+        }
+
+        let (vm, logger, message, context, pipeline) = {
+            let inner = self.inner.lock().expect("Failed to lock inner logger");
+            let message = inner.pipeline.transform(level, message).into_owned();
+            if let Some(circuit) = &inner.pipeline.circuit {
+                let mut breaker = circuit.lock().expect("Failed to lock circuit breaker");
+                if !breaker.allow(inner.pipeline.clock.now()) {
+                    breaker.divert(level, &message, inner.pipeline.clock.system_now());
+                    let _ = done.send(LogOutcome::Diverted);
+                    return Ok(outcome);
+                }
+            }
+
+            let mut context = MdcContext::default();
+            inner.pipeline.traced(inner.env, || {
+                context = mdc::capture_mdc(inner.env)?;
+                Ok(())
+            })?;
+
+            (inner.env.get_java_vm()?, inner.env.new_global_ref(inner.logger)?, message, context, inner.pipeline.clone())
+        };
+
+        submit(Box::new(move || {
+            let result = deliver(&vm, &logger, level, &message, &context);
+            pipeline.settle(level.resolved(), || message, result.is_ok());
+
+            // The receiver is gone if the caller stopped waiting
+            let _ = done.send(match result {
+                Ok(()) => LogOutcome::Delivered,
+                Err(e) => LogOutcome::Failed(e),
+            });
+        }));
+
+        Ok(outcome)
+    }
+}
+
+/// Queue `job` on the delivery pool, starting the pool if needed
+fn submit(job: Job) {
+    let pool = DELIVERY_POOL.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..DELIVERY_THREADS {
+            let receiver = receiver.clone();
+            std::thread::Builder::new()
+                .name("log4j-deadline-delivery".to_string())
+                .spawn(move || run_jobs(&receiver))
+                .expect("Failed to spawn delivery thread");
+        }
+
+        Mutex::new(sender)
+    });
+
+    // The delivery threads never exit, so the receiver is never dropped
+    let _ = pool.lock().expect("Failed to lock delivery pool").send(job);
+}
+
+/// Run queued jobs, one at a time
+fn run_jobs(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match receiver.lock().expect("Failed to lock delivery queue").recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        job();
+    }
+}
+
+/// Log `message` with `Category#log(Priority, Object)`, with `context` installed in the MDC.
+/// The current thread is attached permanently, so the attachment is reused by later deliveries
+///
+/// # Error
+/// - If one of the underlying JNI calls fail, a failed delivery is reported ahead of a failure to clean up the MDC
+fn deliver(vm: &JavaVM, logger: &GlobalRef, level: LogLevel, message: &str, context: &MdcContext) -> Result<()> {
+    let env = vm.attach_current_thread_permanently()?;
+    sys::local_frame(&env, || {
+        let installed = InstalledContext {
+            env: &env,
+            context,
+            removed: false,
+        };
+
+        let delivered = context.install(&env).and_then(|()| {
+            let priority = level.to_java_level(&env)?;
+            let message = JavaLogger::jstring(&env, message)?;
+            env.call_method(logger.as_obj(), "log", "(Lorg/apache/log4j/Priority;Ljava/lang/Object;)V", &[JValue::Object(priority), message])?;
+            Ok(())
+        });
+
+        // The failure is reported through `delivered`, the exception must not stay pending on a reused thread
+        if delivered.is_err() {
+            let _ = env.exception_clear();
+        }
+
+        let removed = installed.finish();
+        delivered.and(removed)
+    })
+}
+
+/// Removes the keys of an installed `MdcContext` from the MDC, on Drop if not done explicitly with `finish`.
+/// The delivery threads are reused, the MDC of one delivery must not leak into the next.
+/// Only the installed keys are removed, as `MDC#clear()` is missing before log4j 1.2.16
+struct InstalledContext<'a> {
+    env: &'a JNIEnv<'a>,
+    context: &'a MdcContext,
+    removed: bool,
+}
+
+impl<'a> InstalledContext<'a> {
+    /// Remove the installed keys, reporting failures
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    fn finish(mut self) -> Result<()> {
+        self.removed = true;
+        self.remove()
+    }
+
+    /// Remove every installed key, even if removing one of them fails
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    fn remove(&self) -> Result<()> {
+        let mut result = Ok(());
+        for key in self.context.entries().keys() {
+            let removed = mdc::remove(self.env, key);
+            if result.is_ok() {
+                result = removed;
+            }
+        }
+
+        result
+    }
+}
+
+impl<'a> Drop for InstalledContext<'a> {
+    fn drop(&mut self) {
+        // Errors can't be propagated from Drop, remove as much as possible
+        if !self.removed && self.remove().is_err() {
+            let _ = self.env.exception_clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support;
+    use jni::objects::JObject;
+
+    const PIPE_SIZE: i32 = 16;
+
+    /// Read `length` characters from a `java.io.PipedReader`, blocking until they are available
+    fn read_pipe(env: &JNIEnv, reader: JObject, length: usize) -> String {
+        let buffer = env.new_char_array(length as i32).unwrap();
+        let mut read = 0;
+        while read < length {
+            let count = env.call_method(reader, "read", "([CII)I", &[JValue::Object(buffer.into()), JValue::Int(read as i32), JValue::Int((length - read) as i32)]).unwrap().i().unwrap();
+            assert!(count > 0, "Pipe was closed");
+            read += count as usize;
+        }
+
+        let mut chars = vec![0; length];
+        env.get_char_array_region(buffer, 0, &mut chars).unwrap();
+        String::from_utf16(&chars).unwrap()
+    }
+
+    #[test]
+    fn delivered() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture_with_pattern("Deadline", "%p %X{request} %m%n").expect("Failed to create capturing logger");
+
+        mdc::put(&env, "request", "req-1").unwrap();
+        let outcome = logger.log_with_deadline(LogLevel::Warn, "On time", Duration::from_secs(10)).expect("Failed to hand over message");
+        mdc::remove(&env, "request").unwrap();
+        assert!(matches!(outcome, LogOutcome::Delivered), "Unexpected outcome {:?}", outcome);
+
+        // Later deliveries on the reused threads must not see the MDC of the first one
+        for _ in 0..DELIVERY_THREADS {
+            let outcome = logger.log_with_deadline(LogLevel::Warn, "Later", Duration::from_secs(10)).expect("Failed to hand over message");
+            assert!(matches!(outcome, LogOutcome::Delivered), "Unexpected outcome {:?}", outcome);
+        }
+
+        let mut expected = vec!["WARN req-1 On time".to_string()];
+        expected.extend(std::iter::repeat("WARN  Later".to_string()).take(DELIVERY_THREADS));
+        assert_eq!(output.lines().expect("Failed to read captured output"), expected);
+    }
+
+    #[test]
+    fn acked() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Acked").expect("Failed to create capturing logger");
+
+        let acked = logger.log_acked(LogLevel::Error, "Shutting down").expect("Failed to hand over message");
+        let outcome = acked.recv_timeout(Duration::from_secs(10)).expect("Message was not acknowledged");
+        assert!(matches!(outcome, LogOutcome::Delivered), "Unexpected outcome {:?}", outcome);
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec!["ERROR Shutting down"]);
+    }
+
+    #[test]
+    fn timed_out() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("DeadlineWedged").expect("Failed to create JavaLogger");
+
+        // Writing more than PIPE_SIZE characters blocks the appender until the pipe is read from
+        let reader = env.new_object("java/io/PipedReader", "(I)V", &[JValue::Int(PIPE_SIZE)]).unwrap();
+        let writer = env.new_object("java/io/PipedWriter", "(Ljava/io/PipedReader;)V", &[JValue::Object(reader)]).unwrap();
+        let layout = env.new_object("org/apache/log4j/PatternLayout", "(Ljava/lang/String;)V", &[JValue::Object(env.new_string("%m%n").unwrap().into())]).unwrap();
+        let appender = env.new_object("org/apache/log4j/WriterAppender", "(Lorg/apache/log4j/Layout;Ljava/io/Writer;)V", &[JValue::Object(layout), JValue::Object(writer)]).unwrap();
+        scope.add_appender(&logger, appender).expect("Failed to add WriterAppender");
+
+        let message = "A message too long for the pipe";
+        let outcome = logger.log_with_deadline(LogLevel::Info, message, Duration::from_millis(200)).expect("Failed to hand over message");
+        assert!(matches!(outcome, LogOutcome::TimedOut), "Unexpected outcome {:?}", outcome);
+
+        // Unblocking the appender lets the late delivery land
+        assert_eq!(read_pipe(&env, reader, message.len() + 1), format!("{}\n", message));
+    }
+}
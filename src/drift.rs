@@ -0,0 +1,197 @@
+use crate::appender;
+use crate::error::Result;
+use crate::sys;
+use jni::objects::JObject;
+use jni::JNIEnv;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+const LOG_MANAGER_CLASS: &str = "org/apache/log4j/LogManager";
+/// The key of the root logger in a `ConfigSnapshot`
+pub const ROOT_LOGGER: &str = "root";
+
+/// The configuration of a single logger, as recorded by `ConfigSnapshot::capture`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggerConfig {
+    /// The level set on the logger, `None` if it is inherited from its parent
+    pub level: Option<String>,
+    /// Whether events are passed on to the parent's appenders
+    pub additivity: bool,
+    /// Names of the attached appenders, sorted. Unnamed appenders are recorded as `<unnamed>`
+    pub appenders: Vec<String>,
+}
+
+impl Default for LoggerConfig {
+    /// The configuration of a logger log4j created on demand: inherited level, additive and no appenders
+    fn default() -> Self {
+        Self {
+            level: None,
+            additivity: true,
+            appenders: Vec::new(),
+        }
+    }
+}
+
+/// The effective configuration of the root logger and every logger in the repository, keyed by logger name.
+/// The root logger is recorded as `ROOT_LOGGER`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigSnapshot {
+    pub loggers: BTreeMap<String, LoggerConfig>,
+}
+
+impl ConfigSnapshot {
+    /// Record the level, additivity and appenders of the root logger and of every logger returned by `LogManager.getCurrentLoggers()`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn capture(env: &JNIEnv) -> Result<Self> {
+        sys::local_frame(env, || {
+            let mut loggers = BTreeMap::new();
+            let root = env.call_static_method(LOG_MANAGER_CLASS, "getRootLogger", "()Lorg/apache/log4j/Logger;", &[])?.l()?;
+            loggers.insert(ROOT_LOGGER.to_string(), logger_config(env, root)?);
+
+            let enumeration = env.call_static_method(LOG_MANAGER_CLASS, "getCurrentLoggers", "()Ljava/util/Enumeration;", &[])?.l()?;
+            while env.call_method(enumeration, "hasMoreElements", "()Z", &[])?.z()? {
+                let (name, config) = sys::local_frame(env, || {
+                    let logger = env.call_method(enumeration, "nextElement", "()Ljava/lang/Object;", &[])?.l()?;
+                    let name = env.call_method(logger, "getName", "()Ljava/lang/String;", &[])?.l()?;
+                    Ok((String::from(env.get_string(name.into())?), logger_config(env, logger)?))
+                })?;
+                loggers.insert(name, config);
+            }
+
+            Ok(Self {
+                loggers,
+            })
+        })
+    }
+
+    /// Describe every difference between `self`, the actual configuration, and `expected`.
+    /// A logger missing from either snapshot is compared as an unconfigured logger, see `LoggerConfig::default()`
+    pub fn differences(&self, expected: &ConfigSnapshot) -> Vec<String> {
+        let unconfigured = LoggerConfig::default();
+        let names: BTreeSet<&String> = self.loggers.keys().chain(expected.loggers.keys()).collect();
+
+        let mut differences = Vec::new();
+        for name in names {
+            let actual = self.loggers.get(name).unwrap_or(&unconfigured);
+            let expected = expected.loggers.get(name).unwrap_or(&unconfigured);
+            if actual.level != expected.level {
+                differences.push(format!("{}: level is {}, expected {}", name, LevelDisplay(&actual.level), LevelDisplay(&expected.level)));
+            }
+
+            if actual.additivity != expected.additivity {
+                differences.push(format!("{}: additivity is {}, expected {}", name, actual.additivity, expected.additivity));
+            }
+
+            if actual.appenders != expected.appenders {
+                differences.push(format!("{}: appenders are [{}], expected [{}]", name, actual.appenders.join(", "), expected.appenders.join(", ")));
+            }
+        }
+
+        differences
+    }
+}
+
+/// Compare the current log4j configuration against `expected`, to detect accidental reconfiguration.
+/// Returns a human readable description of every difference, empty if the configuration matches
+///
+/// # Params
+/// - `expected` The baseline, usually captured with `ConfigSnapshot::capture` after configuring log4j
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub fn assert_configuration(env: &JNIEnv, expected: &ConfigSnapshot) -> Result<Vec<String>> {
+    Ok(ConfigSnapshot::capture(env)?.differences(expected))
+}
+
+/// Record the configuration of a single logger
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+fn logger_config(env: &JNIEnv, logger: JObject) -> Result<LoggerConfig> {
+    let level = env.call_method(logger, "getLevel", "()Lorg/apache/log4j/Level;", &[])?.l()?;
+    let level = if level.is_null() {
+        None
+    } else {
+        let name = env.call_method(level, "toString", "()Ljava/lang/String;", &[])?.l()?;
+        Some(String::from(env.get_string(name.into())?))
+    };
+
+    let mut appenders = Vec::new();
+    for appender in appender::all_appenders(env, logger)? {
+        let name = env.call_method(appender, "getName", "()Ljava/lang/String;", &[])?.l()?;
+        appenders.push(if name.is_null() {
+            "<unnamed>".to_string()
+        } else {
+            String::from(env.get_string(name.into())?)
+        });
+    }
+    appenders.sort();
+
+    Ok(LoggerConfig {
+        level,
+        additivity: appender::get_additivity(env, logger)?,
+        appenders,
+    })
+}
+
+/// Displays an inherited level as `inherited`
+struct LevelDisplay<'a>(&'a Option<String>);
+
+impl fmt::Display for LevelDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(level) => f.write_str(level),
+            None => f.write_str("inherited"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::level::LogLevel;
+    use crate::test::JVM;
+    use crate::test_support;
+
+    #[test]
+    fn differences() {
+        let mut expected = ConfigSnapshot::default();
+        expected.loggers.insert(ROOT_LOGGER.to_string(), LoggerConfig {
+            level: Some("INFO".to_string()),
+            additivity: true,
+            appenders: vec!["console".to_string()],
+        });
+
+        let mut actual = expected.clone();
+        assert!(actual.differences(&expected).is_empty());
+
+        actual.loggers.insert("com.example".to_string(), LoggerConfig {
+            additivity: false,
+            ..LoggerConfig::default()
+        });
+        actual.loggers.insert("com.example.Quiet".to_string(), LoggerConfig::default());
+        actual.loggers.get_mut(ROOT_LOGGER).unwrap().appenders.clear();
+        assert_eq!(actual.differences(&expected), vec![
+            "com.example: additivity is false, expected true".to_string(),
+            "root: appenders are [], expected [console]".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn level_drift() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("Drift").expect("Failed to create logger");
+        scope.set_level(&logger, LogLevel::Info).expect("Failed to set level");
+
+        let baseline = ConfigSnapshot::capture(&env).expect("Failed to capture configuration");
+        assert_eq!(assert_configuration(&env, &baseline).expect("Failed to compare configuration"), Vec::<String>::new());
+
+        scope.set_level(&logger, LogLevel::Debug).expect("Failed to set level");
+        let differences = assert_configuration(&env, &baseline).expect("Failed to compare configuration");
+        assert_eq!(differences, vec![format!("{}: level is DEBUG, expected INFO", scope.logger_name("Drift"))]);
+    }
+}
@@ -0,0 +1,85 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+/// A file opened ahead of time, for a last message when calling into the JVM is not possible,
+/// e.g. from a signal handler after a crash.
+/// On Unix, `write` is async-signal-safe: it only calls `write(2)`, without allocating, locking or using JNI
+#[derive(Debug)]
+pub struct EmergencyLog {
+    file: File,
+}
+
+impl EmergencyLog {
+    /// Open, or create, the file at `path` for appending. Call this during startup, not when the emergency happens
+    ///
+    /// # Error
+    /// - If the file cannot be opened
+    pub fn preopen<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self {
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+        })
+    }
+
+    /// Append `message` followed by a line feed
+    ///
+    /// # Error
+    /// - If writing to the file fails
+    pub fn write(&self, message: &str) -> io::Result<()> {
+        write_all(&self.file, message.as_bytes())?;
+        write_all(&self.file, b"\n")
+    }
+}
+
+/// Write all of `bytes` using `write(2)` directly, retrying on partial writes and `EINTR`
+#[cfg(unix)]
+fn write_all(file: &File, mut bytes: &[u8]) -> io::Result<()> {
+    use std::os::raw::{c_int, c_void};
+    use std::os::unix::io::AsRawFd;
+
+    extern "C" {
+        fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+    }
+
+    while !bytes.is_empty() {
+        // SAFETY: the file descriptor is open for as long as `file` lives, and the buffer is valid for `bytes.len()` bytes
+        let written = unsafe { write(file.as_raw_fd(), bytes.as_ptr() as *const c_void, bytes.len()) };
+        if written < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+
+            return Err(error);
+        }
+
+        bytes = &bytes[written as usize..];
+    }
+
+    Ok(())
+}
+
+/// Outside of Unix there is no async-signal-safe guarantee, the standard library's write is used
+#[cfg(not(unix))]
+fn write_all(mut file: &File, bytes: &[u8]) -> io::Result<()> {
+    io::Write::write_all(&mut file, bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn emergency_write() {
+        // No JavaVM is used at all, as if it were unavailable
+        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = tmpdir.path().join("emergency.log");
+        std::fs::write(&path, "Earlier run\n").unwrap();
+
+        let emergency_log = EmergencyLog::preopen(&path).expect("Failed to open emergency log");
+        emergency_log.write("Received SIGSEGV").expect("Failed to write emergency log");
+        emergency_log.write("Last gasp").expect("Failed to write emergency log");
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Earlier run\nReceived SIGSEGV\nLast gasp\n");
+    }
+}
@@ -2,6 +2,10 @@ use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// The number of message bytes included in a `MessageEncoding` error
+const PREVIEW_LEN: usize = 40;
+/// The largest message, in bytes, that is handed to the JVM
+const MAX_MESSAGE_LEN: usize = i32::MAX as usize;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -11,4 +15,138 @@ pub enum Error {
         #[source]
         jni::errors::Error,
     ),
+    #[error("Unsupported Java version: found {found}, required at least {required}")]
+    UnsupportedJavaVersion {
+        found: String,
+        required: String,
+    },
+    #[error("Failed to convert message of {length} bytes to a Java string ({problem}), message starts with: \"{preview}\"")]
+    MessageEncoding {
+        /// Length of the message in bytes
+        length: usize,
+        /// The first bytes of the message, with anything but printable ASCII hex-escaped
+        preview: String,
+        /// Why the conversion failed, e.g. the error reported by JNI
+        problem: String,
+    },
+    #[error("Invalid conversion pattern \"{pattern}\": {reason}")]
+    InvalidPattern {
+        pattern: String,
+        reason: String,
+    },
+    #[error("log4j is not configured: {0}")]
+    Unconfigured(String),
+    #[error("{0} is not available in this version of log4j")]
+    Unsupported(&'static str),
+    #[error("Unknown checkpoint \"{0}\"")]
+    UnknownCheckpoint(String),
+    #[error("No appender named \"{0}\" is attached")]
+    AppenderNotFound(String),
+    #[error("Appender \"{0}\" is not a WriterAppender and cannot be flushed")]
+    NotFlushable(String),
+    #[error("Appender \"{0}\" is not a RollingFileAppender and cannot be rolled over")]
+    NotRollable(String),
+    #[error("Path {} is not valid Unicode", .0.display())]
+    NonUnicodePath(std::path::PathBuf),
+    #[error("{0} is not available on this platform or log4j build")]
+    AppenderUnavailable(&'static str),
+    #[error("{0} does not exist or is not a java.lang.Throwable")]
+    InvalidThrowableClass(String),
+    #[error("The object is null or not an org.apache.log4j.Category")]
+    NotALogger,
+    #[error("The logging thread stopped before delivering the message")]
+    DeliveryAborted,
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Failed to read configuration file {}", path.display())]
+    ReadConfig {
+        path: std::path::PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to create directory {}", path.display())]
+    CreateDirectory {
+        path: std::path::PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// Returned by `set_global_vm` when a global JavaVM has already been set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("A global JavaVM has already been set")]
+pub struct AlreadySet;
+
+impl Error {
+    /// Create a `MessageEncoding` error for `message`, which could not be converted to a Java string because of `problem`
+    pub(crate) fn message_encoding<P: ToString>(message: &str, problem: P) -> Self {
+        Self::MessageEncoding {
+            length: message.len(),
+            preview: escape_preview(message.as_bytes()),
+            problem: problem.to_string(),
+        }
+    }
+
+    /// Create a `MessageEncoding` error for a UTF-16 message with more code units than a Java string can hold
+    pub(crate) fn utf16_message_encoding(message: &[u16]) -> Self {
+        let preview: String = std::char::decode_utf16(message.iter().copied().take(PREVIEW_LEN)).map(|c| c.unwrap_or(std::char::REPLACEMENT_CHARACTER)).collect();
+        Self::MessageEncoding {
+            length: message.len() * 2,
+            preview: escape_preview(preview.as_bytes()),
+            problem: format!("exceeds the maximum length of {} UTF-16 code units", i32::MAX),
+        }
+    }
+
+    /// Verify that `message` can be handed to the JVM
+    ///
+    /// # Error
+    /// - If the message is longer than the JVM accepts
+    pub(crate) fn check_message(message: &str) -> Result<()> {
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(Self::message_encoding(message, format!("exceeds the maximum length of {} bytes", MAX_MESSAGE_LEN)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Escape the first `PREVIEW_LEN` bytes for display, anything but printable ASCII is written as `\xNN`
+fn escape_preview(bytes: &[u8]) -> String {
+    let mut preview = String::with_capacity(PREVIEW_LEN * 4);
+    for &byte in bytes.iter().take(PREVIEW_LEN) {
+        match byte {
+            b'\\' => preview.push_str("\\\\"),
+            0x20..=0x7e => preview.push(byte as char),
+            _ => preview.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+
+    preview
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn jni_problem() {
+        let error = Error::message_encoding("Hello\0World", jni::errors::Error::NullPtr("NewStringUTF result"));
+        let display = error.to_string();
+        assert!(display.contains("(Null pointer in NewStringUTF result)"), "{}", display);
+        assert!(display.contains("11 bytes"));
+        assert!(display.contains("Hello\\x00World"));
+    }
+
+    #[test]
+    fn preview_is_truncated_and_escaped() {
+        let message = format!("é\\{}", "a".repeat(100));
+        match Error::message_encoding(&message, "rejected") {
+            Error::MessageEncoding { length, preview, problem } => {
+                assert_eq!(length, 103);
+                assert_eq!(preview, format!("\\xc3\\xa9\\\\{}", "a".repeat(37)));
+                assert_eq!(problem, "rejected");
+            }
+            _ => panic!("Expected MessageEncoding"),
+        }
+    }
 }
@@ -0,0 +1,146 @@
+use crate::error::{Error, Result};
+use crate::logger::JavaLogger;
+use crate::sys;
+use jni::objects::{GlobalRef, JObject};
+use jni::{JNIEnv, JavaVM};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const WRITER_APPENDER_CLASS: &str = "org/apache/log4j/WriterAppender";
+
+/// Flushes an appender periodically, created with `JavaLogger::enable_timed_flush`.
+/// The appender is flushed one last time and the flush thread is stopped when the guard is dropped
+pub struct FlushGuard {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<'a> JavaLogger<'a> {
+    /// Flush an appender attached to this logger every `interval`, from a background thread attached to the JVM.
+    /// Intended for appenders with buffered IO, such as a `FileAppender` with `bufferedIO` enabled,
+    /// so output appears promptly without flushing on every message
+    ///
+    /// # Params
+    /// - `appender_name` The name of the appender, which must be a `WriterAppender`
+    /// - `interval` The time between flushes
+    ///
+    /// # Error
+    /// - If no appender named `appender_name` is attached to this logger
+    /// - If the appender is not a `WriterAppender`
+    /// - If one of the underlying JNI calls fail
+    pub fn enable_timed_flush(&self, appender_name: &str, interval: Duration) -> Result<FlushGuard> {
+        let env = self.inner.lock().expect("Failed to lock inner logger").env;
+        let appender = sys::local_frame(env, || {
+            let handle = self.get_appender(appender_name)?.ok_or_else(|| Error::AppenderNotFound(appender_name.to_string()))?;
+            if !env.is_instance_of(handle.as_jobject(), WRITER_APPENDER_CLASS)? {
+                return Err(Error::NotFlushable(appender_name.to_string()));
+            }
+
+            Ok(env.new_global_ref(handle.as_jobject())?)
+        })?;
+
+        let vm = env.get_java_vm()?;
+        let (stop, stopped) = mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            // The thread stops when flushing fails, there is no caller to report the error to
+            let _ = flush_periodically(&vm, &appender, || stopped.recv_timeout(interval) == Err(RecvTimeoutError::Timeout));
+        });
+
+        Ok(FlushGuard {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Flush `appender` every time `wait` returns `true`, and once more after it returns `false`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+fn flush_periodically<F: Fn() -> bool>(vm: &JavaVM, appender: &GlobalRef, wait: F) -> Result<()> {
+    let env = vm.attach_current_thread()?;
+    while wait() {
+        flush(&env, appender.as_obj())?;
+    }
+
+    flush(&env, appender.as_obj())
+}
+
+/// Flush a `WriterAppender` using the `flush()` method of its `QuietWriter`.
+/// log4j 1.2 has no public flush method on the appender itself
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+fn flush(env: &JNIEnv, appender: JObject) -> Result<()> {
+    sys::local_frame(env, || {
+        let quiet_writer = env.get_field(appender, "qw", "Lorg/apache/log4j/helpers/QuietWriter;")?.l()?;
+        // The writer is null once the appender is closed
+        if !quiet_writer.is_null() {
+            env.call_method(quiet_writer, "flush", "()V", &[])?;
+        }
+
+        Ok(())
+    })
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        // Dropping the sender wakes up the flush thread
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support;
+    use crate::LogLevel;
+    use jni::objects::JValue;
+    use std::time::Instant;
+
+    #[test]
+    fn timed_flush() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("TimedFlush").expect("Failed to create JavaLogger");
+
+        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = tmpdir.path().join("buffered.log");
+        let layout = env.new_object("org/apache/log4j/PatternLayout", "(Ljava/lang/String;)V", &[JValue::Object(env.new_string("%m%n").unwrap().into())]).unwrap();
+        let appender = env
+            .new_object(
+                "org/apache/log4j/FileAppender",
+                "(Lorg/apache/log4j/Layout;Ljava/lang/String;ZZI)V",
+                &[JValue::Object(layout), JValue::Object(env.new_string(path.to_str().unwrap()).unwrap().into()), JValue::Bool(1), JValue::Bool(1), JValue::Int(64 * 1024)],
+            )
+            .expect("Failed to create FileAppender");
+        env.call_method(appender, "setName", "(Ljava/lang/String;)V", &[JValue::Object(env.new_string("buffered").unwrap().into())]).unwrap();
+        scope.add_appender(&logger, appender).expect("Failed to add FileAppender");
+
+        logger.log(LogLevel::Info, "Buffered").expect("Failed to log to INFO level");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+        match logger.enable_timed_flush("missing", Duration::from_millis(50)) {
+            Err(Error::AppenderNotFound(name)) => assert_eq!(name, "missing"),
+            _ => panic!("Expected AppenderNotFound"),
+        }
+
+        let guard = logger.enable_timed_flush("buffered", Duration::from_millis(50)).expect("Failed to enable timed flush");
+        let start = Instant::now();
+        while std::fs::read_to_string(&path).unwrap().is_empty() {
+            assert!(start.elapsed() < Duration::from_secs(5), "Appender was not flushed");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Buffered\n");
+
+        logger.log(LogLevel::Info, "On drop").expect("Failed to log to INFO level");
+        drop(guard);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Buffered\nOn drop\n");
+    }
+}
@@ -0,0 +1,107 @@
+use crate::error::Result;
+use crate::level::LogLevel;
+use crate::logger::JavaLogger;
+use crate::pipeline::RemoteLogger;
+use jni::JNIEnv;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Logs a heartbeat periodically, created with `JavaLogger::start_heartbeat`.
+/// The heartbeat thread is stopped when the guard is dropped
+pub struct HeartbeatGuard {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<'a> JavaLogger<'a> {
+    /// Log `message` at INFO level every `interval` from a background thread attached to the JVM, for liveness monitoring.
+    /// Every heartbeat goes through the logger's current message pipeline and circuit breaker, and is counted and recorded
+    /// like a message logged with `log`. The first heartbeat is logged after one interval.
+    /// A heartbeat which fails to be logged doesn't stop the next ones, see `set_circuit_breaker` to handle repeated failures
+    ///
+    /// # Params
+    /// - `interval` The time between heartbeats
+    /// - `message` The message to log
+    ///
+    /// # Error
+    /// - If the heartbeat thread could not be attached to the JVM
+    /// - If one of the underlying JNI calls fail
+    pub fn start_heartbeat(&self, interval: Duration, message: &str) -> Result<HeartbeatGuard> {
+        let remote = self.remote()?;
+        let message = message.to_string();
+
+        let (stop, stopped) = mpsc::channel();
+        let (started_tx, started_rx) = mpsc::channel::<Result<()>>();
+        let thread = std::thread::spawn(move || {
+            let env = match remote.vm().attach_current_thread() {
+                Ok(env) => env,
+                Err(e) => return started_tx.send(Err(e.into())).unwrap_or_default(),
+            };
+            started_tx.send(Ok(())).unwrap_or_default();
+            beat(&remote, &env, &message, || stopped.recv_timeout(interval) == Err(RecvTimeoutError::Timeout));
+        });
+
+        started_rx.recv().expect("Heartbeat thread exited before starting")?;
+        Ok(HeartbeatGuard {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Log `message` at INFO level through `remote` every time `wait` returns `true`
+fn beat<F: Fn() -> bool>(remote: &RemoteLogger, env: &JNIEnv, message: &str, wait: F) {
+    while wait() {
+        // There is nobody to report a failure to, the next heartbeat will try again
+        if remote.log(env, LogLevel::Info, message).is_err() {
+            let _ = env.exception_clear();
+        }
+    }
+}
+
+impl Drop for HeartbeatGuard {
+    fn drop(&mut self) {
+        // Dropping the sender wakes up the heartbeat thread
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support;
+    use crate::LocationPrefix;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    #[test]
+    fn heartbeat() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Heartbeat").expect("Failed to create capturing logger");
+        logger.push_transform(Arc::new(LocationPrefix::new("liveness")));
+
+        let guard = logger.start_heartbeat(Duration::from_millis(20), "heartbeat").expect("Failed to start heartbeat");
+        let start = Instant::now();
+        while output.lines().expect("Failed to read captured output").len() < 3 {
+            assert!(start.elapsed() < Duration::from_secs(5), "Too few heartbeats were logged");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        drop(guard);
+
+        // Every heartbeat went through the logger's pipeline
+        let lines = output.lines().expect("Failed to read captured output");
+        assert!(lines.iter().all(|line| line == "INFO [liveness] heartbeat"), "Unexpected output {:?}", lines);
+        assert_eq!(logger.counts().info, lines.len() as u64);
+
+        // No heartbeats after the guard is dropped
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(output.lines().expect("Failed to read captured output").len(), lines.len());
+    }
+}
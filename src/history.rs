@@ -0,0 +1,252 @@
+use crate::error::Result;
+use crate::level::LogLevel;
+use crate::logger::JavaLogger;
+use crate::sys;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// The default bound on the total size of the messages kept by a `HistoryLogger`
+const DEFAULT_MAX_BYTES: usize = 1024 * 1024;
+
+/// A message kept by a `HistoryLogger`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// When the message was logged, according to the logger's clock
+    pub time: SystemTime,
+    pub level: LogLevel,
+    /// The name of the logger
+    pub logger: Arc<str>,
+    /// The message as handed to log4j, after the message pipeline has run
+    pub message: String,
+}
+
+/// Wrapper around a JavaLogger which keeps the most recent delivered messages in memory, e.g. for an admin endpoint.
+/// Every message delivered through the logger or any of its clones is recorded, not only those logged through the wrapper.
+/// The history is bounded by both the number of entries and the total size of the messages, the oldest entries are evicted first.
+/// Querying the history doesn't involve the JVM. Recording stops when the wrapper is dropped
+pub struct HistoryLogger<'a> {
+    logger: JavaLogger<'a>,
+    history: Arc<Mutex<History>>,
+}
+
+/// The ring buffer of a `HistoryLogger`, shared with the `InnerLogger` which records into it
+#[derive(Debug)]
+pub(crate) struct History {
+    logger_name: Arc<str>,
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+    max_bytes: usize,
+    /// The total length of the messages in `entries`
+    bytes: usize,
+}
+
+impl History {
+    fn new(logger_name: Arc<str>, capacity: usize) -> Self {
+        Self {
+            logger_name,
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            max_bytes: DEFAULT_MAX_BYTES,
+            bytes: 0,
+        }
+    }
+
+    /// Add a message, evicting the oldest entries until both bounds are met.
+    /// A message longer than the byte bound is not kept
+    pub(crate) fn record(&mut self, time: SystemTime, level: LogLevel, message: String) {
+        self.bytes += message.len();
+        self.entries.push_back(HistoryEntry {
+            time,
+            level,
+            logger: self.logger_name.clone(),
+            message,
+        });
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity || self.bytes > self.max_bytes {
+            match self.entries.pop_front() {
+                Some(evicted) => self.bytes -= evicted.message.len(),
+                None => break,
+            }
+        }
+    }
+}
+
+impl<'a> HistoryLogger<'a> {
+    /// Start recording the messages delivered by `logger`, keeping at most `capacity` entries and 1 MiB of messages.
+    /// Replaces the history of an earlier `HistoryLogger` of the same logger
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn wrap(logger: &JavaLogger<'a>, capacity: usize) -> Result<Self> {
+        let mut inner = logger.inner.lock().expect("Failed to lock inner logger");
+        let env = inner.env;
+        let name = sys::local_frame(env, || {
+            let name = env.call_method(inner.logger, "getName", "()Ljava/lang/String;", &[])?.l()?;
+            Ok(String::from(env.get_string(name.into())?))
+        })?;
+
+        let history = Arc::new(Mutex::new(History::new(name.into(), capacity)));
+        inner.update_pipeline(|pipeline| pipeline.history = Some(history.clone()));
+        drop(inner);
+
+        Ok(Self {
+            logger: logger.clone(),
+            history,
+        })
+    }
+
+    /// Bound the total length of the kept messages in bytes, defaults to 1 MiB
+    pub fn max_bytes(self, max_bytes: usize) -> Self {
+        {
+            let mut history = self.history.lock().expect("Failed to lock history");
+            history.max_bytes = max_bytes;
+            history.evict();
+        }
+
+        self
+    }
+
+    /// The wrapped logger
+    pub fn logger(&self) -> &JavaLogger<'a> {
+        &self.logger
+    }
+
+    /// Log to log4j and record the message
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log<S: AsRef<str>>(&self, level: LogLevel, message: S) -> Result<()> {
+        self.logger.log(level, message)
+    }
+
+    /// The `n` most recent entries, oldest first
+    pub fn recent(&self, n: usize) -> Vec<HistoryEntry> {
+        let history = self.history.lock().expect("Failed to lock history");
+        history.entries.iter().skip(history.entries.len().saturating_sub(n)).cloned().collect()
+    }
+
+    /// The entries matching all provided criteria, oldest first
+    ///
+    /// # Params
+    /// - `level_min` Only entries at least as severe as this level
+    /// - `substring` Only entries whose message contains this text
+    /// - `since` Only entries logged at or after this time
+    pub fn filter(&self, level_min: Option<LogLevel>, substring: Option<&str>, since: Option<SystemTime>) -> Vec<HistoryEntry> {
+        let history = self.history.lock().expect("Failed to lock history");
+        history
+            .entries
+            .iter()
+            .filter(|entry| level_min.map_or(true, |level_min| entry.level.is_greater_or_equal(&level_min)))
+            .filter(|entry| substring.map_or(true, |substring| entry.message.contains(substring)))
+            .filter(|entry| since.map_or(true, |since| entry.time >= since))
+            .cloned()
+            .collect()
+    }
+
+    /// Remove all entries
+    pub fn clear(&self) {
+        let mut history = self.history.lock().expect("Failed to lock history");
+        history.entries.clear();
+        history.bytes = 0;
+    }
+}
+
+impl<'a> Drop for HistoryLogger<'a> {
+    fn drop(&mut self) {
+        let mut inner = self.logger.inner.lock().expect("Failed to lock inner logger");
+        // A later HistoryLogger may have replaced this one
+        if inner.pipeline.history.as_ref().is_some_and(|history| Arc::ptr_eq(history, &self.history)) {
+            inner.update_pipeline(|pipeline| pipeline.history = None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::deadline::LogOutcome;
+    use crate::test::JVM;
+    use crate::test_support::{self, ManualClock};
+    use std::time::Duration;
+
+    fn messages(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.message.as_str()).collect()
+    }
+
+    #[test]
+    fn eviction() {
+        let now = SystemTime::now();
+        let mut history = History::new("com.example".into(), 3);
+        for message in ["one", "two", "three", "four"] {
+            history.record(now, LogLevel::Info, message.to_string());
+        }
+        assert_eq!(history.entries.iter().map(|entry| entry.message.as_str()).collect::<Vec<_>>(), vec!["two", "three", "four"]);
+        assert_eq!(history.bytes, 12);
+
+        history.max_bytes = 10;
+        history.record(now, LogLevel::Info, "five".to_string());
+        assert_eq!(history.entries.iter().map(|entry| entry.message.as_str()).collect::<Vec<_>>(), vec!["four", "five"]);
+        assert_eq!(history.bytes, 8);
+
+        history.record(now, LogLevel::Info, "far too long".to_string());
+        assert!(history.entries.is_empty());
+        assert_eq!(history.bytes, 0);
+    }
+
+    #[test]
+    fn history_logger() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, _output) = scope.capture("History").expect("Failed to create capturing logger");
+        let clock = Arc::new(ManualClock::new());
+        logger.set_clock(clock.clone());
+
+        let history = HistoryLogger::wrap(&logger, 4).expect("Failed to wrap logger");
+        history.log(LogLevel::Info, "Started").expect("Failed to log");
+        history.log(LogLevel::Error, "Disk failed").expect("Failed to log");
+        clock.advance(Duration::from_secs(5));
+        let later = clock.system_now();
+        // Messages logged through the wrapped logger are recorded as well
+        logger.log(LogLevel::Warn, "Disk almost full").expect("Failed to log");
+        history.log(LogLevel::Debug, "Polling").expect("Failed to log");
+        history.log(LogLevel::Error, "Disk failed again").expect("Failed to log");
+
+        assert_eq!(messages(&history.recent(10)), vec!["Disk failed", "Disk almost full", "Polling", "Disk failed again"]);
+        assert_eq!(messages(&history.recent(2)), vec!["Polling", "Disk failed again"]);
+        assert_eq!(&*history.recent(1)[0].logger, scope.logger_name("History"));
+
+        assert_eq!(messages(&history.filter(Some(LogLevel::Warn), None, None)), vec!["Disk failed", "Disk almost full", "Disk failed again"]);
+        assert_eq!(messages(&history.filter(Some(LogLevel::Error), Some("again"), None)), vec!["Disk failed again"]);
+        assert_eq!(messages(&history.filter(None, Some("Disk"), Some(later))), vec!["Disk almost full", "Disk failed again"]);
+
+        history.clear();
+        assert!(history.recent(10).is_empty());
+
+        drop(history);
+        assert!(logger.inner.lock().unwrap().pipeline.history.is_none());
+    }
+
+    #[test]
+    fn acked() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, _output) = scope.capture("HistoryAcked").expect("Failed to create capturing logger");
+        let history = HistoryLogger::wrap(&logger, 4).expect("Failed to wrap logger");
+
+        // Messages delivered by the delivery threads are recorded before they are acknowledged
+        let acked = logger.log_acked(LogLevel::Error, "Shutting down").expect("Failed to hand over message");
+        let outcome = acked.recv_timeout(Duration::from_secs(10)).expect("Message was not acknowledged");
+        assert!(matches!(outcome, LogOutcome::Delivered), "Unexpected outcome {:?}", outcome);
+
+        assert_eq!(messages(&history.recent(1)), vec!["Shutting down"]);
+        assert_eq!(history.recent(1)[0].level, LogLevel::Error);
+        assert_eq!(logger.counts().error, 1);
+    }
+}
@@ -0,0 +1,303 @@
+use crate::error::Result;
+use crate::sys;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const LEVEL_CLASS: &str = "org/apache/log4j/Level";
+const LEVEL_SIGNATURE: &str = "Lorg/apache/log4j/Level;";
+
+/// Integer value of `Priority.ERROR_INT`
+const ERROR_INT: i32 = 40000;
+/// Integer value of `Priority.WARN_INT`
+const WARN_INT: i32 = 30000;
+/// Integer value of `Priority.INFO_INT`
+const INFO_INT: i32 = 20000;
+/// Integer value of `Priority.DEBUG_INT`
+const DEBUG_INT: i32 = 10000;
+/// Integer value of `Level.TRACE_INT`, only available in log4j 1.2.12 and newer
+const TRACE_INT: i32 = 5000;
+/// Integer value of `Priority.FATAL_INT`
+const FATAL_INT: i32 = 50000;
+/// Integer value of `Priority.OFF_INT`
+const OFF_INT: i32 = i32::MAX;
+/// Integer value of `Priority.ALL_INT`
+const ALL_INT: i32 = i32::MIN;
+
+/// The log level to output to.
+/// More levels may be added, so matches must include a wildcard arm
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogLevel {
+    /// ERROR level
+    Error,
+    /// WARN level
+    Warn,
+    /// INFO level
+    Info,
+    /// DEBUG level, also applicable to TRACE logging
+    Debug,
+    /// Any other level, identified by its integer value as returned by `Priority#toInt()`
+    Custom(i32),
+}
+
+impl LogLevel {
+    /// The integer value of this level, matching `Priority#toInt()`
+    pub fn to_int(&self) -> i32 {
+        match self {
+            Self::Error => ERROR_INT,
+            Self::Warn => WARN_INT,
+            Self::Info => INFO_INT,
+            Self::Debug => DEBUG_INT,
+            Self::Custom(value) => *value,
+        }
+    }
+
+    /// Get the level from its integer value.
+    /// TRACE maps to `Debug`, every other non-standard value maps to `Custom`
+    pub fn from_int(value: i32) -> Self {
+        match value {
+            ERROR_INT => Self::Error,
+            WARN_INT => Self::Warn,
+            INFO_INT => Self::Info,
+            DEBUG_INT | TRACE_INT => Self::Debug,
+            value => Self::Custom(value),
+        }
+    }
+
+    /// The level log4j logs at for this level, see `to_java_level`.
+    /// `Custom` values without a predefined log4j level, e.g. 35000, resolve to the nearest standard level below them.
+    /// FATAL, OFF and ALL are predefined, so they stay `Custom`
+    pub(crate) fn resolved(&self) -> Self {
+        match *self {
+            Self::Custom(value @ (FATAL_INT | OFF_INT | ALL_INT)) => Self::Custom(value),
+            Self::Custom(value) => match Self::from_int(value) {
+                Self::Custom(_) => Self::nearest_standard(value),
+                level => level,
+            },
+            level => level,
+        }
+    }
+
+    /// The highest standard level whose value is at most `value`, `Debug` if there is none
+    fn nearest_standard(value: i32) -> Self {
+        [Self::Error, Self::Warn, Self::Info].iter().find(|level| level.to_int() <= value).copied().unwrap_or(Self::Debug)
+    }
+
+    /// Whether this level is greater than or equal to `other`, with the same semantics as `Priority#isGreaterOrEqual`
+    pub fn is_greater_or_equal(&self, other: &LogLevel) -> bool {
+        self.to_int() >= other.to_int()
+    }
+
+    /// Convert an `org.apache.log4j.Level` (or any `org.apache.log4j.Priority`) to a LogLevel
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn from_java_level(env: &JNIEnv, level: JObject) -> Result<Self> {
+        let value = env.call_method(level, "toInt", "()I", &[])?.i()?;
+        Ok(Self::from_int(value))
+    }
+
+    /// Convert to an `org.apache.log4j.Level`.
+    /// `Custom` levels resolve to the standard level with the same value, or else to the nearest standard level below it
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn to_java_level<'a>(&self, env: &JNIEnv<'a>) -> Result<JObject<'a>> {
+        let level = match self {
+            Self::Error => Self::static_level(env, "ERROR")?,
+            Self::Warn => Self::static_level(env, "WARN")?,
+            Self::Info => Self::static_level(env, "INFO")?,
+            Self::Debug => Self::static_level(env, "DEBUG")?,
+            Self::Custom(value) => {
+                let nearest = Self::nearest_standard(*value);
+
+                sys::local_frame_object(env, || {
+                    let default = nearest.to_java_level(env)?;
+                    Ok(env.call_static_method(LEVEL_CLASS, "toLevel", format!("(I{}){}", LEVEL_SIGNATURE, LEVEL_SIGNATURE), &[JValue::Int(*value), JValue::Object(default)])?.l()?)
+                })?
+            }
+        };
+
+        Ok(level)
+    }
+
+    /// Get one of the static Level fields, e.g. `Level.ERROR`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    fn static_level<'a>(env: &JNIEnv<'a>, name: &str) -> Result<JObject<'a>> {
+        Ok(env.get_static_field(LEVEL_CLASS, name, LEVEL_SIGNATURE)?.l()?)
+    }
+}
+
+/// A custom `org.apache.log4j.Level`, e.g. `AUDIT`, created with `register_custom_level`
+#[derive(Clone, Copy)]
+pub struct CustomLevel<'a> {
+    pub(crate) level: JObject<'a>,
+    value: i32,
+}
+
+impl<'a> CustomLevel<'a> {
+    /// The integer value of this level
+    pub fn to_int(&self) -> i32 {
+        self.value
+    }
+
+    /// This level as a LogLevel, for comparisons
+    pub fn as_log_level(&self) -> LogLevel {
+        LogLevel::from_int(self.value)
+    }
+
+    /// The underlying `org.apache.log4j.Level`
+    pub fn as_jobject(&self) -> JObject<'a> {
+        self.level
+    }
+}
+
+/// Create a custom level, which patterns render with its own name, e.g. `%p` renders `AUDIT`.
+/// Log to it with `JavaLogger::log_at_custom`
+///
+/// # Params
+/// - `name` The name of the level
+/// - `value` The integer value of the level, e.g. 35000 to sit between WARN and ERROR
+/// - `syslog_equivalent` The syslog severity, used by the SyslogAppender
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub fn register_custom_level<'a>(env: &JNIEnv<'a>, name: &str, value: i32, syslog_equivalent: i32) -> Result<CustomLevel<'a>> {
+    // The constructor is protected, which JNI does not enforce
+    let level = sys::local_frame_object(env, || {
+        let name = env.new_string(name)?;
+        Ok(env.new_object(LEVEL_CLASS, "(ILjava/lang/String;I)V", &[JValue::Int(value), JValue::Object(name.into()), JValue::Int(syslog_equivalent)])?)
+    })?;
+    Ok(CustomLevel {
+        level,
+        value,
+    })
+}
+
+/// The number of log calls per level, returned by `JavaLogger::counts`.
+/// Calls are counted at the level log4j logged them at: a `LogLevel::Custom` value without a log4j level of its own,
+/// e.g. 35000, is counted at the nearest standard level below it, like `LogLevel::to_java_level` resolves it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LevelCounts {
+    /// Calls at the ERROR level
+    pub error: u64,
+    /// Calls at the WARN level
+    pub warn: u64,
+    /// Calls at the INFO level
+    pub info: u64,
+    /// Calls at the DEBUG and TRACE levels
+    pub debug: u64,
+    /// Calls at any other level, e.g. FATAL or a level created with `register_custom_level`
+    pub custom: u64,
+}
+
+impl LevelCounts {
+    /// The number of calls at all levels
+    pub fn total(&self) -> u64 {
+        self.error + self.warn + self.info + self.debug + self.custom
+    }
+}
+
+/// Counters behind `LevelCounts`, which can be incremented through a shared reference
+#[derive(Debug, Default)]
+pub(crate) struct LevelCounters {
+    error: AtomicU64,
+    warn: AtomicU64,
+    info: AtomicU64,
+    debug: AtomicU64,
+    custom: AtomicU64,
+}
+
+impl LevelCounters {
+    /// Count a call at `level`
+    pub(crate) fn record(&self, level: LogLevel) {
+        let counter = match level {
+            LogLevel::Error => &self.error,
+            LogLevel::Warn => &self.warn,
+            LogLevel::Info => &self.info,
+            LogLevel::Debug => &self.debug,
+            LogLevel::Custom(_) => &self.custom,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The current counts
+    pub(crate) fn snapshot(&self) -> LevelCounts {
+        LevelCounts {
+            error: self.error.load(Ordering::Relaxed),
+            warn: self.warn.load(Ordering::Relaxed),
+            info: self.info.load(Ordering::Relaxed),
+            debug: self.debug.load(Ordering::Relaxed),
+            custom: self.custom.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+
+    const STANDARD: [LogLevel; 4] = [LogLevel::Error, LogLevel::Warn, LogLevel::Info, LogLevel::Debug];
+
+    #[test]
+    fn integer_mapping() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        for (level, field) in STANDARD.iter().zip(["ERROR_INT", "WARN_INT", "INFO_INT", "DEBUG_INT"].iter()) {
+            let java_int = env.get_static_field("org/apache/log4j/Priority", *field, "I").unwrap().i().unwrap();
+            assert_eq!(level.to_int(), java_int);
+
+            let java_level = level.to_java_level(&env).expect("Failed to convert to Java level");
+            assert_eq!(LogLevel::from_java_level(&env, java_level).expect("Failed to convert from Java level"), *level);
+        }
+
+        let fatal = env.get_static_field(LEVEL_CLASS, "FATAL", LEVEL_SIGNATURE).unwrap().l().unwrap();
+        assert_eq!(LogLevel::from_java_level(&env, fatal).unwrap(), LogLevel::Custom(50000));
+
+        let fatal = LogLevel::Custom(50000).to_java_level(&env).unwrap();
+        assert_eq!(LogLevel::from_java_level(&env, fatal).unwrap(), LogLevel::Custom(50000));
+
+        let between = LogLevel::Custom(35000).to_java_level(&env).unwrap();
+        assert_eq!(LogLevel::from_java_level(&env, between).unwrap(), LogLevel::Warn);
+    }
+
+    #[test]
+    fn greater_or_equal_matches_java() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        for a in STANDARD.iter() {
+            for b in STANDARD.iter() {
+                let java_a = a.to_java_level(&env).unwrap();
+                let java_b = b.to_java_level(&env).unwrap();
+                let java_result = env.call_method(java_a, "isGreaterOrEqual", "(Lorg/apache/log4j/Priority;)Z", &[JValue::Object(java_b)]).unwrap().z().unwrap();
+                assert_eq!(a.is_greater_or_equal(b), java_result);
+            }
+        }
+    }
+
+    #[test]
+    fn from_int() {
+        assert_eq!(LogLevel::from_int(40000), LogLevel::Error);
+        assert_eq!(LogLevel::from_int(5000), LogLevel::Debug);
+        assert_eq!(LogLevel::from_int(50000), LogLevel::Custom(50000));
+        assert!(LogLevel::Error.is_greater_or_equal(&LogLevel::Error));
+        assert!(LogLevel::Custom(35000).is_greater_or_equal(&LogLevel::Warn));
+        assert!(!LogLevel::Debug.is_greater_or_equal(&LogLevel::Info));
+    }
+
+    #[test]
+    fn resolved() {
+        assert_eq!(LogLevel::Custom(35000).resolved(), LogLevel::Warn);
+        assert_eq!(LogLevel::Custom(40000).resolved(), LogLevel::Error);
+        assert_eq!(LogLevel::Custom(7).resolved(), LogLevel::Debug);
+        assert_eq!(LogLevel::Custom(50000).resolved(), LogLevel::Custom(50000));
+        assert_eq!(LogLevel::Custom(i32::MAX).resolved(), LogLevel::Custom(i32::MAX));
+        assert_eq!(LogLevel::Info.resolved(), LogLevel::Info);
+    }
+}
@@ -1,50 +1,120 @@
 mod logger;
 pub use logger::*;
 
+mod sys;
+
+mod pipeline;
+
+mod level;
+pub use level::*;
+
 mod error;
 pub use error::*;
 
+mod runtime;
+pub use runtime::*;
+
+pub mod mdc;
+pub use mdc::{capture_mdc, mdc_snapshot, MdcContext};
+
+mod appender;
+pub use appender::*;
+
+mod pattern;
+pub use pattern::*;
+
+mod preflight;
+pub use preflight::*;
+
+mod audit;
+pub use audit::*;
+
+mod summarizing;
+pub use summarizing::*;
+
+mod benchmark;
+pub use benchmark::*;
+
+mod capabilities;
+pub use capabilities::*;
+
+mod writer;
+pub use writer::*;
+
+mod flush;
+pub use flush::*;
+
+mod path;
+pub use path::*;
+
+mod nt_event_log;
+pub use nt_event_log::*;
+
+mod emergency;
+pub use emergency::*;
+
+mod throwable;
+pub use throwable::*;
+
+mod clock;
+pub use clock::*;
+
+mod plain;
+pub use plain::*;
+
+mod banner;
+pub use banner::*;
+
+mod deadline;
+pub use deadline::*;
+
+mod transform;
+pub use transform::*;
+
+mod circuit;
+pub use circuit::*;
+
+mod context;
+pub use context::*;
+
+mod heartbeat;
+pub use heartbeat::*;
+
+mod drift;
+pub use drift::*;
+
+mod stamp;
+pub use stamp::*;
+
+mod sampling;
+pub use sampling::*;
+
+mod result_ext;
+pub use result_ext::*;
+
+mod console;
+pub use console::*;
+
+mod history;
+pub use history::*;
+
+#[cfg(feature = "toml")]
+mod config;
+#[cfg(feature = "toml")]
+pub use config::*;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_support;
+
 #[cfg(test)]
 mod test {
-    const SLF4J_BINDING: &str = "https://repo1.maven.org/maven2/org/slf4j/slf4j-log4j12/1.7.9/slf4j-log4j12-1.7.9.jar";
-    const SLF4J_API: &str = "https://repo1.maven.org/maven2/org/slf4j/slf4j-api/1.7.9/slf4j-api-1.7.9.jar";
-    const LOG4J: &str = "https://repo1.maven.org/maven2/log4j/log4j/1.2.9/log4j-1.2.9.jar";
-
-    use jni::{InitArgsBuilder, JNIVersion, JavaVM};
+    use crate::test_support::TestJvm;
+    use jni::JavaVM;
     use lazy_static::lazy_static;
-    use std::path::PathBuf;
-    use std::sync::Mutex;
+    use std::sync::{Arc, Mutex};
 
     lazy_static! {
-        pub static ref JVM: Mutex<JavaVM> = {
-            let binding = download_jar(SLF4J_BINDING);
-            let api = download_jar(SLF4J_API);
-            let log4j = download_jar(LOG4J);
-
-            let jvm_args = InitArgsBuilder::new()
-                .version(JNIVersion::V8)
-                .option("-Xcheck:jni")
-                .option(&format!("-Djava.class.path={}", binding.to_str().expect("Failed to convert slf4j.jar path to &str")))
-                .option(&format!("-Djava.class.path={}", api.to_str().expect("Failed to convert slf4j.jar path to &str")))
-                .option(&format!("-Djava.class.path={}", log4j.to_str().expect("Failed to convert slf4j.jar path to &str")))
-                .build()
-                .unwrap();
-
-            let java_vm = JavaVM::new(jvm_args).expect("Failed to create JavaVM");
-            Mutex::new(java_vm)
-        };
-    }
-
-    fn download_jar(url: &str) -> PathBuf {
-        let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
-        let response = reqwest::blocking::get(url).expect("Failed to donwload SLF4j");
-
-        let pathbuf = PathBuf::from(tmpdir.into_path()).join("slf4j.jar");
-        let mut dest = std::fs::File::create(&pathbuf).expect("Failed to create slf4j.jar");
-        let content = response
-            .bytes()
-            .expect("Failed to read response bytes");
-        std::io::copy(&mut &*content, &mut dest).expect("Failed to copy response bytes into slf4j.jar");
-        pathbuf
+        /// The shared JavaVM, locked so tests changing the global log4j configuration don't interfere with each other
+        pub static ref JVM: Mutex<Arc<JavaVM>> = Mutex::new(TestJvm::get());
     }
 }
@@ -1,24 +1,71 @@
-use crate::error::Result;
-use jni::objects::{JMethodID, JObject, JValue};
-use jni::signature::{JavaType, Primitive};
+use crate::appender;
+use crate::capabilities::{Capabilities, OptionalMethods};
+use crate::circuit::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+use crate::clock::Clock;
+use crate::error::{Error, Result};
+use crate::level::{CustomLevel, LevelCounts, LogLevel};
+use crate::mdc;
+use crate::pipeline::Pipeline;
+use crate::runtime::ensure_supported;
+use crate::sys::{self, MethodId};
+use base64::Engine;
+use jni::objects::{JObject, JString, JValue};
 use jni::JNIEnv;
+#[cfg(feature = "redact")]
+use crate::transform::Redactor;
+use crate::transform::MessageTransform;
+#[cfg(feature = "redact")]
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 const LOG_MANAGER_CLASS: &str = "org/apache/log4j/LogManager";
 const CATEGORY_CLASS: &str = "org/apache/log4j/Category";
+/// The MDC key under which `log_coded_error` stores the error code
+const ERROR_CODE_MDC_KEY: &str = "errorCode";
+/// The conversion pattern used by `JavaLogger::capture`
+const CAPTURE_PATTERN: &str = "%m%n";
+/// The number of bytes `log_base64` encodes, larger inputs are truncated
+const MAX_BASE64_BYTES: usize = 64 * 1024;
 
-struct InnerLogger<'a> {
-    env:            &'a JNIEnv<'a>,
-    logger:         JObject<'a>,         // This is an instance of org.apache.log4j.Logger
-    info_method:    JMethodID<'a>,  // Logger#info(Object)
-    error_method:   JMethodID<'a>, // Logger#error(Object)
-    warn_method:    JMethodID<'a>,  // Logger#warn(Object)
-    debug_method:   JMethodID<'a>, // Logger#debug(Object)
+/// Set with `set_logging_paused`, checked by every log call
+static LOGGING_PAUSED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) struct InnerLogger<'a> {
+    pub(crate) env:     &'a JNIEnv<'a>,
+    pub(crate) logger:  JObject<'a>,    // This is an instance of org.apache.log4j.Logger
+    info_method:        MethodId<'a>,   // Logger#info(Object)
+    error_method:       MethodId<'a>,   // Logger#error(Object)
+    warn_method:        MethodId<'a>,   // Logger#warn(Object)
+    debug_method:       MethodId<'a>,   // Logger#debug(Object)
+    optional_methods:   OptionalMethods<'a>, // Methods which depend on the log4j version
+    pub(crate) pipeline: Arc<Pipeline>, // The message pipeline and bookkeeping, replaced as a whole by update_pipeline
+    pub(crate) published: Arc<Mutex<Arc<Pipeline>>>, // The current pipeline, shared with RemoteLoggers
+    epoch:              Instant,        // Reference point for log_since_epoch
+    checkpoints:        HashMap<String, Instant>, // Named reference points for log_since_checkpoint
+    sampled_calls:      AtomicU64,      // Number of log_sampled calls so far
+    progress:           HashMap<String, Option<u64>>, // Last percentage logged by log_progress per label, None for an unknown total
+    pub(crate) simple_layout_fallback: bool, // Use a SimpleLayout instead of failing on invalid patterns
+}
+
+impl<'a> InnerLogger<'a> {
+    /// Replace the pipeline with a changed copy, and publish it to the threads logging on behalf of this logger
+    pub(crate) fn update_pipeline<F: FnOnce(&mut Pipeline)>(&mut self, f: F) {
+        let mut pipeline = Pipeline::clone(&self.pipeline);
+        f(&mut pipeline);
+        self.pipeline = Arc::new(pipeline);
+        *self.published.lock().expect("Failed to lock published pipeline") = self.pipeline.clone();
+    }
 }
 
-/// The JavaLogger
+/// The JavaLogger.
+/// Cloning is cheap, clones are handles to the same underlying logger and share its configuration
+#[derive(Clone)]
 pub struct JavaLogger<'a> {
-    inner: Arc<Mutex<InnerLogger<'a>>>,
+    pub(crate) inner: Arc<Mutex<InnerLogger<'a>>>,
 }
 
 // Required because the compiler does not pick up that JavaLogger can be Send+Sync
@@ -26,16 +73,16 @@ pub struct JavaLogger<'a> {
 unsafe impl<'a> Send for JavaLogger<'a> {}
 unsafe impl<'a> Sync for JavaLogger<'a> {}
 
-/// The log level to output to
-pub enum LogLevel {
-    /// ERROR level
-    Error,
-    /// WARN level
-    Warn,
-    /// INFO level
-    Info,
-    /// DEBUG level, also applicable to TRACE logging
-    Debug,
+/// Pause or resume all logging through this crate, e.g. during a maintenance window.
+/// While paused every log call returns `Ok(())` without logging, log4j's levels and appenders are left untouched.
+/// Paused calls are not counted, see `JavaLogger::counts`
+pub fn set_logging_paused(paused: bool) {
+    LOGGING_PAUSED.store(paused, Ordering::Relaxed);
+}
+
+/// Whether logging is paused with `set_logging_paused`
+pub fn logging_paused() -> bool {
+    LOGGING_PAUSED.load(Ordering::Relaxed)
 }
 
 impl<'a> JavaLogger<'a> {
@@ -45,18 +92,59 @@ impl<'a> JavaLogger<'a> {
     /// - `class_name` The name of Class which should be used by log4j on the Java side
     ///
     /// # Error
+    /// - If the JNI or Java version is older than supported
     /// - If one of the underlying JNI calls fail
     pub fn new<S: AsRef<str>>(env: &'a JNIEnv<'a>, class_name: S) -> Result<Self> {
-        let log_manager_class = env.find_class(LOG_MANAGER_CLASS)?;
-        let logger_value = env.call_static_method(log_manager_class,             "getLogger","(Ljava/lang/String;)Lorg/apache/log4j/Logger;",&[Self::jstring(env, class_name.as_ref())?])?;
-        let logger = logger_value.l()?;
+        ensure_supported(env)?;
+
+        let logger = sys::local_frame_object(env, || {
+            let log_manager_class = env.find_class(LOG_MANAGER_CLASS)?;
+            let logger_value = env.call_static_method(log_manager_class,             "getLogger","(Ljava/lang/String;)Lorg/apache/log4j/Logger;",&[Self::jstring(env, class_name.as_ref())?])?;
+            Ok(logger_value.l()?)
+        })?;
 
-        let category_class = env.find_class(CATEGORY_CLASS)?;
-        let info_method = env.get_method_id(category_class, "info", "(Ljava/lang/Object;)V")?;
-        let error_method = env.get_method_id(category_class, "error", "(Ljava/lang/Object;)V")?;
-        let warn_method = env.get_method_id(category_class, "warn", "(Ljava/lang/Object;)V")?;
-        let debug_method = env.get_method_id(category_class, "debug", "(Ljava/lang/Object;)V")?;
+        Self::wrap(env, logger)
+    }
+
+    /// Wrap an `org.apache.log4j.Logger` obtained elsewhere, e.g. handed over by Java code creating its own loggers.
+    /// The method IDs are resolved against the class of `logger`
+    ///
+    /// # Params
+    /// - `logger` The logger to wrap, it must remain valid for `'a`
+    ///
+    /// # Error
+    /// - If `logger` is null or not an `org.apache.log4j.Category`
+    /// - If the JNI or Java version is older than supported
+    /// - If one of the underlying JNI calls fail
+    pub fn from_jobject(env: &'a JNIEnv<'a>, logger: JObject<'a>) -> Result<Self> {
+        ensure_supported(env)?;
 
+        if logger.is_null() || !env.is_instance_of(logger, CATEGORY_CLASS)? {
+            return Err(Error::NotALogger);
+        }
+
+        Self::wrap(env, logger)
+    }
+
+    /// Resolve the method IDs against the class of `logger` and build the JavaLogger
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn wrap(env: &'a JNIEnv<'a>, logger: JObject<'a>) -> Result<Self> {
+        // Method IDs are not local references, they stay valid after the frame is popped
+        let (info_method, error_method, warn_method, debug_method, optional_methods) = sys::local_frame(env, || {
+            let logger_class = env.get_object_class(logger)?;
+            Ok((
+                sys::method_id(env, logger_class, "info", "(Ljava/lang/Object;)V")?,
+                sys::method_id(env, logger_class, "error", "(Ljava/lang/Object;)V")?,
+                sys::method_id(env, logger_class, "warn", "(Ljava/lang/Object;)V")?,
+                sys::method_id(env, logger_class, "debug", "(Ljava/lang/Object;)V")?,
+                OptionalMethods::resolve(env, logger_class)?,
+            ))
+        })?;
+
+        let pipeline = Arc::new(Pipeline::new());
         Ok(Self {
             inner: Arc::new(Mutex::new(InnerLogger {
                 env,
@@ -65,6 +153,14 @@ impl<'a> JavaLogger<'a> {
                 error_method,
                 warn_method,
                 debug_method,
+                optional_methods,
+                published: Arc::new(Mutex::new(pipeline.clone())),
+                pipeline,
+                epoch: Instant::now(),
+                checkpoints: HashMap::new(),
+                sampled_calls: AtomicU64::new(0),
+                progress: HashMap::new(),
+                simple_layout_fallback: false,
             })),
         })
     }
@@ -75,16 +171,428 @@ impl<'a> JavaLogger<'a> {
     /// - If one of the underlying JNI calls fail
     pub fn log<S: AsRef<str>>(&self, level: LogLevel, content: S) -> Result<()> {
         let logger = self.inner.lock().expect("Failed to lock inner logger");
-        match level {
-            LogLevel::Error => Self::log_error(&logger, content.as_ref())?,
-            LogLevel::Warn => Self::log_warn(&logger, content.as_ref())?,
-            LogLevel::Info => Self::log_info(&logger, content.as_ref())?,
-            LogLevel::Debug => Self::log_debug(&logger, content.as_ref())?,
-        };
+        let content = logger.pipeline.transform(level, content.as_ref());
+        logger.pipeline.guarded(level.resolved(), || content.to_string(), || {
+            sys::local_frame(logger.env, || {
+                logger.pipeline.traced(logger.env, || match level {
+                    LogLevel::Error => Self::log_error(&logger, &content),
+                    LogLevel::Warn => Self::log_warn(&logger, &content),
+                    LogLevel::Info => Self::log_info(&logger, &content),
+                    LogLevel::Debug => Self::log_debug(&logger, &content),
+                    LogLevel::Custom(_) => Self::log_priority(&logger, level, &content),
+                })
+            })
+        })
+    }
+
+    /// Log a message given as UTF-16 code units, e.g. received from Java, without converting it to a Rust `String` and back.
+    /// The Java string is created from the code units directly, so unpaired surrogates are passed on unchanged.
+    /// When the message pipeline has stages, the message is decoded and logged with `log` instead, as transforms work on `str`
+    ///
+    /// # Error
+    /// - If there are more code units than a Java string can hold
+    /// - If one of the underlying JNI calls fail
+    pub fn log_utf16(&self, level: LogLevel, message: &[u16]) -> Result<()> {
+        let logger = self.inner.lock().expect("Failed to lock inner logger");
+        if logger.pipeline.has_transforms() {
+            drop(logger);
+            return self.log(level, String::from_utf16_lossy(message));
+        }
+
+        logger.pipeline.guarded(level.resolved(), || String::from_utf16_lossy(message), || {
+            sys::local_frame(logger.env, || {
+                let message = sys::new_string_utf16(logger.env, message)?;
+                logger.pipeline.traced(logger.env, || Self::log_message(&logger, level, message.into()))
+            })
+        })
+    }
+
+    /// Log a message which already is a Java string, e.g. an argument of a native method.
+    /// This is the zero-copy option: the string is passed to log4j as is, without being converted.
+    /// The message pipeline, including redaction, is not applied
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_jstring(&self, level: LogLevel, message: JString) -> Result<()> {
+        let logger = self.inner.lock().expect("Failed to lock inner logger");
+        // Reading the string is a local operation, unlike delivering it
+        let fallback_message = || logger.env.get_string(message).map(String::from).unwrap_or_default();
+        logger.pipeline.guarded(level.resolved(), fallback_message, || sys::local_frame(logger.env, || logger.pipeline.traced(logger.env, || Self::log_message(&logger, level, message.into()))))
+    }
+
+    /// Log at a custom level created with `register_custom_level`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_at_custom<S: AsRef<str>>(&self, level: &CustomLevel<'a>, content: S) -> Result<()> {
+        let logger = self.inner.lock().expect("Failed to lock inner logger");
+        let content = logger.pipeline.transform(level.as_log_level(), content.as_ref());
+        logger.pipeline.guarded(level.as_log_level(), || content.to_string(), || {
+            sys::local_frame(logger.env, || logger.pipeline.traced(logger.env, || Self::log_java_priority(&logger, level.level, &content)))
+        })
+    }
+
+    /// Log to the TRACE level using `Logger#trace(Object)`
+    ///
+    /// # Error
+    /// - `Error::Unsupported` if the log4j version does not have `Logger#trace(Object)`, see `capabilities`
+    /// - If one of the underlying JNI calls fail
+    pub fn log_trace<S: AsRef<str>>(&self, content: S) -> Result<()> {
+        let logger = self.inner.lock().expect("Failed to lock inner logger");
+        let trace_method = logger.optional_methods.trace.ok_or(Error::Unsupported("Logger#trace(Object)"))?;
+        let content = logger.pipeline.transform(LogLevel::Debug, content.as_ref());
+        // TRACE is treated as DEBUG, like LogLevel::from_int does
+        logger.pipeline.guarded(LogLevel::Debug, || content.to_string(), || {
+            sys::local_frame(logger.env, || {
+                logger.pipeline.traced(logger.env, || sys::call_void_method(logger.env, logger.logger, trace_method, &[Self::jstring(logger.env, &content)?]))
+            })
+        })
+    }
+
+    /// The level and message of the last successful log call, shared by all clones of this JavaLogger.
+    /// The message is the one handed to log4j, after the message pipeline has run. TRACE calls are recorded as DEBUG.
+    /// Always `None` unless tracking is enabled with `set_track_last_message`
+    pub fn last_message(&self) -> Option<(LogLevel, String)> {
+        let logger = self.inner.lock().expect("Failed to lock inner logger");
+        logger.pipeline.last_message.as_ref().and_then(|last_message| last_message.lock().expect("Failed to lock last message").clone())
+    }
+
+    /// Enable or disable tracking of the last successful log call for `last_message`, e.g. for assertions in tests.
+    /// Tracking is disabled by default, as it copies every message. Disabling it forgets the last message
+    pub fn set_track_last_message(&self, enabled: bool) {
+        let mut logger = self.inner.lock().expect("Failed to lock inner logger");
+        if enabled == logger.pipeline.last_message.is_some() {
+            return;
+        }
+
+        logger.update_pipeline(|pipeline| pipeline.last_message = enabled.then(|| Arc::new(Mutex::new(None))));
+    }
+
+    /// The number of successful log calls per level so far, shared by all clones of this JavaLogger.
+    /// Calls are counted whether or not log4j is enabled for the level, TRACE calls are counted as DEBUG.
+    /// Messages sent to the fallback of an open circuit breaker are not counted
+    pub fn counts(&self) -> LevelCounts {
+        self.inner.lock().expect("Failed to lock inner logger").pipeline.counts.snapshot()
+    }
 
+    /// Protect this logger with a circuit breaker: after `failure_threshold` consecutive failed deliveries,
+    /// messages go to the configured fallback without calling into the JVM. Once the cool-down has passed,
+    /// the next message is delivered as a probe, closing the circuit if it succeeds or restarting the cool-down if it fails.
+    /// Every log method of this JavaLogger and its clones respects the breaker, including `log_to` and `log_with_deadline`.
+    /// Replaces any previously configured circuit breaker, in the closed state
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn set_circuit_breaker(&self, config: CircuitBreakerConfig) -> Result<()> {
+        let mut logger = self.inner.lock().expect("Failed to lock inner logger");
+        let name = sys::local_frame(logger.env, || {
+            let name = logger.env.call_method(logger.logger, "getName", "()Ljava/lang/String;", &[])?.l()?;
+            Ok(String::from(logger.env.get_string(name.into())?))
+        })?;
+
+        let circuit = Arc::new(Mutex::new(CircuitBreaker::new(config, name, logger.pipeline.clock.now())));
+        logger.update_pipeline(|pipeline| pipeline.circuit = Some(circuit));
         Ok(())
     }
 
+    /// The state of the circuit breaker, `CircuitState::Closed` if none is configured
+    pub fn circuit_state(&self) -> CircuitState {
+        match &self.inner.lock().expect("Failed to lock inner logger").pipeline.circuit {
+            Some(circuit) => circuit.lock().expect("Failed to lock circuit breaker").state(),
+            None => CircuitState::Closed,
+        }
+    }
+
+    /// The optional log4j methods available to this logger
+    pub fn capabilities(&self) -> Capabilities {
+        self.inner.lock().expect("Failed to lock inner logger").optional_methods.capabilities()
+    }
+
+    /// Get the effective level of this logger using `Category#getEffectiveLevel()`,
+    /// i.e. its own level or the level inherited from the closest ancestor which has one
+    ///
+    /// # Error
+    /// - `Error::Unsupported` if the log4j version does not have `Category#getEffectiveLevel()`, see `capabilities`
+    /// - If one of the underlying JNI calls fail
+    pub fn effective_level(&self) -> Result<LogLevel> {
+        let logger = self.inner.lock().expect("Failed to lock inner logger");
+        let method = logger.optional_methods.effective_level.ok_or(Error::Unsupported("Category#getEffectiveLevel()"))?;
+        Self::call_level_getter(&logger, method)
+    }
+
+    /// Whether this logger is enabled for `level` using `Category#isEnabledFor(Priority)`, taking the repository threshold into account.
+    /// Unlike `would_log`, appenders are not considered
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn is_enabled_for(&self, level: LogLevel) -> Result<bool> {
+        let logger = self.inner.lock().expect("Failed to lock inner logger");
+        sys::local_frame(logger.env, || {
+            let priority = level.to_java_level(logger.env)?;
+            Ok(logger.env.call_method(logger.logger, "isEnabledFor", "(Lorg/apache/log4j/Priority;)Z", &[JValue::Object(priority)])?.z()?)
+        })
+    }
+
+    /// Get the chained priority of this logger using the deprecated `Category#getChainedPriority()`.
+    /// Older log4j versions only have this method, newer versions treat it as an alias of `getEffectiveLevel`
+    ///
+    /// # Error
+    /// - `Error::Unsupported` if the log4j version does not have `Category#getChainedPriority()`, see `capabilities`
+    /// - If one of the underlying JNI calls fail
+    pub fn chained_priority(&self) -> Result<LogLevel> {
+        let logger = self.inner.lock().expect("Failed to lock inner logger");
+        let method = logger.optional_methods.chained_priority.ok_or(Error::Unsupported("Category#getChainedPriority()"))?;
+        Self::call_level_getter(&logger, method)
+    }
+
+    /// Call a method returning a level or priority and convert the result
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    fn call_level_getter<'b>(logger: &'b InnerLogger<'a>, method: MethodId<'a>) -> Result<LogLevel>
+    where
+        'a: 'b,
+    {
+        sys::local_frame(logger.env, || {
+            let level = sys::call_object_method(logger.env, logger.logger, method, &[])?;
+            LogLevel::from_java_level(logger.env, level)
+        })
+    }
+
+    /// Set the patterns redacted from every message before it is sent to Java, each match is replaced with `***`.
+    /// Only the message is redacted, other fields of the layout such as the logger name or MDC values are not.
+    /// Replaces any previously configured patterns. The `Redactor` always is the first stage of the message pipeline, see `MessageTransform`
+    #[cfg(feature = "redact")]
+    pub fn set_redactor(&self, redactor: Vec<Regex>) {
+        self.inner.lock().expect("Failed to lock inner logger").update_pipeline(|pipeline| pipeline.redactor = Some(Arc::new(Redactor::new(redactor))));
+    }
+
+    /// Add a stage to the end of the message pipeline, which runs on every message before it is converted to a Java string.
+    /// The pipeline is shared by all clones of this JavaLogger, see `MessageTransform` for the execution order
+    pub fn push_transform(&self, transform: Arc<dyn MessageTransform>) {
+        self.inner.lock().expect("Failed to lock inner logger").update_pipeline(|pipeline| pipeline.transforms.push(transform));
+    }
+
+    /// Put a trace id in the MDC under `traceId` for every log call, so a `%X{traceId}` conversion renders it.
+    /// `generator` is called once per log call; to share a trace id between calls, e.g. within a request,
+    /// return a stored id such as a thread-local one. Replaces any previously configured generator
+    pub fn with_trace_id<F: Fn() -> String + Send + Sync + 'static>(&self, generator: F) {
+        self.inner.lock().expect("Failed to lock inner logger").update_pipeline(|pipeline| pipeline.trace_id = Some(Arc::new(generator)));
+    }
+
+    /// Log the pretty-printed `Debug` representation of a value, formatted as `{label} = {value:#?}`.
+    /// Multi-line output is logged as a single message
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_debug_value<T: Debug>(&self, level: LogLevel, label: &str, value: &T) -> Result<()> {
+        self.log(level, format!("{} = {:#?}", label, value))
+    }
+
+    /// Log only one in every `sample_rate` calls, starting with the first call. Returns whether the message was logged.
+    /// A `sample_rate` of 0 or 1 logs every call. The calls are counted per logger, shared by its clones and across all rates
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_sampled<S: AsRef<str>>(&self, level: LogLevel, message: S, sample_rate: u32) -> Result<bool> {
+        let call = self.inner.lock().expect("Failed to lock inner logger").sampled_calls.fetch_add(1, Ordering::Relaxed);
+        if sample_rate > 1 && call % u64::from(sample_rate) != 0 {
+            return Ok(false);
+        }
+
+        self.log(level, message)?;
+        Ok(true)
+    }
+
+    /// Log the progress of an operation at INFO, formatted as `{label}: {percentage}% ({current}/{total})`.
+    /// Only logs when the integer percentage for `label` differs from the last one logged, so it can be called for every step.
+    /// A `total` of 0 is logged once as `{label}: {current} (unknown total)`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_progress(&self, label: &str, current: u64, total: u64) -> Result<()> {
+        let percentage = match total {
+            0 => None,
+            total => Some((u128::from(current) * 100 / u128::from(total)).min(100) as u64),
+        };
+
+        {
+            let mut logger = self.inner.lock().expect("Failed to lock inner logger");
+            if logger.progress.get(label) == Some(&percentage) {
+                return Ok(());
+            }
+            logger.progress.insert(label.to_string(), percentage);
+        }
+
+        match percentage {
+            Some(percentage) => self.log(LogLevel::Info, format!("{}: {}% ({}/{})", label, percentage, current, total)),
+            None => self.log(LogLevel::Info, format!("{}: {} (unknown total)", label, current)),
+        }
+    }
+
+    /// Log a change of a value, formatted as `{label} changed: {before:?} -> {after:?}`.
+    /// Nothing is logged if `before` and `after` are equal
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_diff<T: Debug + PartialEq>(&self, level: LogLevel, label: &str, before: &T, after: &T) -> Result<()> {
+        if before == after {
+            return Ok(());
+        }
+
+        self.log(level, format!("{} changed: {:?} -> {:?}", label, before, after))
+    }
+
+    /// Set the epoch used by `log_since_epoch`. Defaults to the moment the logger was created
+    pub fn set_epoch(&self, instant: Instant) {
+        self.inner.lock().expect("Failed to lock inner logger").epoch = instant;
+    }
+
+    /// Set the clock used by time-dependent methods, defaults to `SystemClock`.
+    /// The epoch is reset to the current time of `clock`, as instants of different clocks can't be compared
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
+        let mut logger = self.inner.lock().expect("Failed to lock inner logger");
+        logger.epoch = clock.now();
+        logger.update_pipeline(|pipeline| pipeline.clock = clock);
+    }
+
+    /// The current time according to the configured clock
+    pub(crate) fn now(&self) -> Instant {
+        self.inner.lock().expect("Failed to lock inner logger").pipeline.clock.now()
+    }
+
+    /// Configure what happens when an appender helper receives an invalid conversion pattern.
+    /// By default `Error::InvalidPattern` is returned, when `fallback` is enabled a `SimpleLayout` is used instead
+    pub fn set_simple_layout_fallback(&self, fallback: bool) {
+        self.inner.lock().expect("Failed to lock inner logger").simple_layout_fallback = fallback;
+    }
+
+    /// Log a message prefixed with the milliseconds elapsed since the epoch, formatted as `[+{elapsed_ms}ms] {message}`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_since_epoch<S: AsRef<str>>(&self, level: LogLevel, message: S) -> Result<()> {
+        let epoch = self.inner.lock().expect("Failed to lock inner logger").epoch;
+        let elapsed = self.now().saturating_duration_since(epoch);
+        self.log(level, format!("[+{}ms] {}", elapsed.as_millis(), message.as_ref()))
+    }
+
+    /// Log a message prefixed with the current time as an RFC 3339 timestamp in UTC with millisecond precision,
+    /// formatted as `{timestamp} {message}`, e.g. `2021-09-14T12:30:05.123Z Started`.
+    /// The timestamp is generated in Rust, so it is part of the message regardless of the layout
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    #[cfg(feature = "timestamp")]
+    pub fn log_timestamped<S: AsRef<str>>(&self, level: LogLevel, message: S) -> Result<()> {
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        self.log(level, format!("{} {}", timestamp, message.as_ref()))
+    }
+
+    /// Record the current time as the checkpoint `name`, replacing an earlier checkpoint with the same name
+    pub fn checkpoint(&self, name: &str) {
+        let now = self.now();
+        self.inner.lock().expect("Failed to lock inner logger").checkpoints.insert(name.to_string(), now);
+    }
+
+    /// Log a message prefixed with the milliseconds elapsed since the checkpoint `name`, formatted as `[{name} +{elapsed_ms}ms] {message}`
+    ///
+    /// # Error
+    /// - `Error::UnknownCheckpoint` if no checkpoint named `name` was recorded with `checkpoint`
+    /// - If one of the underlying JNI calls fail
+    pub fn log_since_checkpoint<S: AsRef<str>>(&self, level: LogLevel, name: &str, message: S) -> Result<()> {
+        let checkpoint = self.inner.lock().expect("Failed to lock inner logger").checkpoints.get(name).copied();
+        let checkpoint = checkpoint.ok_or_else(|| Error::UnknownCheckpoint(name.to_string()))?;
+        let elapsed = self.now().saturating_duration_since(checkpoint);
+        self.log(level, format!("[{} +{}ms] {}", name, elapsed.as_millis(), message.as_ref()))
+    }
+
+    /// Log an integer without allocating an intermediate Rust String
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_i64(&self, level: LogLevel, value: i64) -> Result<()> {
+        self.log(level, itoa::Buffer::new().format(value))
+    }
+
+    /// Log a float without allocating an intermediate Rust String.
+    /// The value is formatted by `ryu`, e.g. `1.5`, `1e20` or `NaN`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_f64(&self, level: LogLevel, value: f64) -> Result<()> {
+        self.log(level, ryu::Buffer::new().format(value))
+    }
+
+    /// Log a boolean as `true` or `false`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_bool(&self, level: LogLevel, value: bool) -> Result<()> {
+        self.log(level, if value { "true" } else { "false" })
+    }
+
+    /// Log binary data as standard, padded base64, formatted as `{label}: {base64}`.
+    /// Only the first 64 KiB are encoded, for larger inputs the message ends in `... ({encoded} of {total} bytes)`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_base64(&self, level: LogLevel, label: &str, bytes: &[u8]) -> Result<()> {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&bytes[..bytes.len().min(MAX_BASE64_BYTES)]);
+        if bytes.len() > MAX_BASE64_BYTES {
+            self.log(level, format!("{}: {}... ({} of {} bytes)", label, encoded, MAX_BASE64_BYTES, bytes.len()))
+        } else {
+            self.log(level, format!("{}: {}", label, encoded))
+        }
+    }
+
+    /// Log an error with an error code, formatted as `[{code}] {message}`.
+    /// For the duration of the call the code is also available in the MDC as `errorCode`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log_coded_error(&self, code: &str, message: &str) -> Result<()> {
+        let env = self.inner.lock().expect("Failed to lock inner logger").env;
+        mdc::with_value(env, ERROR_CODE_MDC_KEY, code, || self.log(LogLevel::Error, format!("[{}] {}", code, message)))
+    }
+
+    /// Capture the output of this logger while running `f`.
+    /// A StringWriter appender is attached and additivity is turned off for the duration of `f`,
+    /// afterwards the appender is removed and additivity is restored. Every message is captured as `%m%n`.
+    /// The logger is restored as well if `f` panics or attaching the appender fails halfway
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn capture<R, F: FnOnce() -> R>(&self, f: F) -> Result<(R, String)> {
+        let (env, logger) = {
+            let inner = self.inner.lock().expect("Failed to lock inner logger");
+            (inner.env, inner.logger)
+        };
+
+        let (string_appender, string_writer) = appender::string_appender(env, CAPTURE_PATTERN)?;
+        let restore = {
+            let _monitor = env.lock_obj(logger)?;
+            let restore = CaptureRestore {
+                env,
+                logger,
+                appender: string_appender,
+                additive: appender::get_additivity(env, logger)?,
+                restored: false,
+            };
+            appender::set_additivity(env, logger, false)?;
+            appender::add_appender(env, logger, string_appender)?;
+            restore
+        };
+
+        let result = f();
+
+        restore.finish()?;
+        let output = appender::read_string_writer(env, string_writer)?;
+        env.delete_local_ref(string_appender)?;
+        env.delete_local_ref(string_writer)?;
+        Ok((result, output))
+    }
+
     /// Log to the ERROR level
     ///
     /// # Error
@@ -93,8 +601,7 @@ impl<'a> JavaLogger<'a> {
     where
         'a: 'b,
     {
-        logger.env.call_method_unchecked(logger.logger,logger.error_method,JavaType::Primitive(Primitive::Void), &[Self::jstring(logger.env, msg)?])?;
-        Ok(())
+        sys::call_void_method(logger.env, logger.logger, logger.error_method, &[Self::jstring(logger.env, msg)?])
     }
 
     /// Log to the WARN level
@@ -105,8 +612,7 @@ impl<'a> JavaLogger<'a> {
     where
         'a: 'b,
     {
-        logger.env.call_method_unchecked(logger.logger,logger.warn_method,JavaType::Primitive(Primitive::Void),&[Self::jstring(logger.env, msg)?])?;
-        Ok(())
+        sys::call_void_method(logger.env, logger.logger, logger.warn_method, &[Self::jstring(logger.env, msg)?])
     }
 
     /// Log to the INFO level
@@ -117,8 +623,7 @@ impl<'a> JavaLogger<'a> {
     where
         'a: 'b,
     {
-        logger.env.call_method_unchecked(logger.logger,logger.info_method,JavaType::Primitive(Primitive::Void),&[Self::jstring(logger.env, msg)?])?;
-        Ok(())
+        sys::call_void_method(logger.env, logger.logger, logger.info_method, &[Self::jstring(logger.env, msg)?])
     }
 
     /// Log to the DEBUG level
@@ -129,47 +634,119 @@ impl<'a> JavaLogger<'a> {
     where
         'a: 'b,
     {
-        logger.env.call_method_unchecked(logger.logger,logger.debug_method,JavaType::Primitive(Primitive::Void),&[Self::jstring(logger.env, msg)?])?;
+        sys::call_void_method(logger.env, logger.logger, logger.debug_method, &[Self::jstring(logger.env, msg)?])
+    }
+
+    /// Log to the priority corresponding to `level` using `Category#log(Priority, Object)`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    fn log_priority<'b>(logger: &'b InnerLogger<'a>, level: LogLevel, msg: &str) -> Result<()>
+    where
+        'a: 'b,
+    {
+        Self::log_java_priority(logger, level.to_java_level(logger.env)?, msg)
+    }
+
+    /// Log to an `org.apache.log4j.Priority` object using `Category#log(Priority, Object)`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    fn log_java_priority<'b>(logger: &'b InnerLogger<'a>, priority: JObject<'a>, msg: &str) -> Result<()>
+    where
+        'a: 'b,
+    {
+        logger.env.call_method(logger.logger, "log", "(Lorg/apache/log4j/Priority;Ljava/lang/Object;)V", &[JValue::Object(priority), Self::jstring(logger.env, msg)?])?;
         Ok(())
     }
 
-    /// Turn a string into a JValue containing a JString
+    /// Log a message object to `level`
     ///
     /// # Error
     /// - If one of the underlying JNI calls fail
-    fn jstring(env: &'a JNIEnv<'a>, content: &str) -> Result<JValue<'a>> {
-        let str = env.new_string(content)?;
+    fn log_message<'b>(logger: &'b InnerLogger<'a>, level: LogLevel, message: JObject) -> Result<()>
+    where
+        'a: 'b,
+    {
+        let method = match level {
+            LogLevel::Error => logger.error_method,
+            LogLevel::Warn => logger.warn_method,
+            LogLevel::Info => logger.info_method,
+            LogLevel::Debug => logger.debug_method,
+            LogLevel::Custom(_) => {
+                let priority = level.to_java_level(logger.env)?;
+                logger.env.call_method(logger.logger, "log", "(Lorg/apache/log4j/Priority;Ljava/lang/Object;)V", &[JValue::Object(priority), JValue::Object(message)])?;
+                return Ok(());
+            }
+        };
+
+        sys::call_void_method(logger.env, logger.logger, method, &[JValue::Object(message)])
+    }
+
+    /// Turn a string into a JValue containing a JString
+    ///
+    /// # Error
+    /// - If the string cannot be converted to a Java string
+    pub(crate) fn jstring(env: &'a JNIEnv<'a>, content: &str) -> Result<JValue<'a>> {
+        Error::check_message(content)?;
+        let str = env.new_string(content).map_err(|e| Error::message_encoding(content, e))?;
         Ok(JValue::Object(str.into()))
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::test::JVM;
-
-    fn setup_log4j(logger: &JavaLogger) -> Result<()> {
-        let logger = logger.inner.lock().unwrap();
-        let env = logger.env;
+/// Undoes the changes `JavaLogger::capture` made to a logger, on Drop if not done explicitly with `finish`
+struct CaptureRestore<'a> {
+    env: &'a JNIEnv<'a>,
+    logger: JObject<'a>,
+    appender: JObject<'a>,
+    /// The additivity of the logger before capturing
+    additive: bool,
+    restored: bool,
+}
 
-        let pattern_layout_class = env.find_class("org/apache/log4j/PatternLayout")?;
-        let pattern_layout = env.new_object(pattern_layout_class,"(Ljava/lang/String;)V",&[JValue::Object(env.new_string("%r [%t] %p %c %x - %m%n")?.into())])?;
+impl<'a> CaptureRestore<'a> {
+    /// Restore the logger, reporting failures
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    fn finish(mut self) -> Result<()> {
+        self.restored = true;
+        self.restore()
+    }
 
-        let console_appender_class = env.find_class("org/apache/log4j/ConsoleAppender")?;
-        let console_appender = env.new_object(console_appender_class,"(Lorg/apache/log4j/Layout;)V",&[JValue::Object(pattern_layout)])?;
+    /// Remove the capturing appender and restore the additivity
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    fn restore(&self) -> Result<()> {
+        let _monitor = self.env.lock_obj(self.logger)?;
+        // Attempt both, so a failure to remove the appender doesn't leave additivity turned off
+        let removed = appender::remove_appender(self.env, self.logger, self.appender);
+        appender::set_additivity(self.env, self.logger, self.additive)?;
+        removed
+    }
+}
 
-        let category_class = env.find_class(CATEGORY_CLASS)?;
-        let add_apender_method = env.get_method_id(category_class,"addAppender","(Lorg/apache/log4j/Appender;)V")?;
-        env.call_method_unchecked(logger.logger,add_apender_method,JavaType::Primitive(Primitive::Void),&[JValue::Object(console_appender)])?;
-        Ok(())
+impl<'a> Drop for CaptureRestore<'a> {
+    fn drop(&mut self) {
+        // Errors can't be propagated from Drop, restore as much as possible
+        if !self.restored && self.restore().is_err() {
+            let _ = self.env.exception_clear();
+        }
     }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support::{self, TestJvm};
 
     #[test]
     fn info() {
         let jvm = JVM.lock().expect("Failed to lock JVM");
         let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
-        let logger = JavaLogger::new(&env, "com.example.Info").expect("Failed to create JavaLogger");
-        setup_log4j(&logger).expect("Failed to set up log4j");
+        let logger = TestJvm::logger(&env, "com.example.Info").expect("Failed to create JavaLogger");
 
         let inner_logger = logger.inner.lock().expect("Failed to lock inner logger");
         JavaLogger::log_info(&inner_logger, "Info log!").expect("Failed to log to INFO");
@@ -179,8 +756,7 @@ mod test {
     fn warn() {
         let jvm = JVM.lock().expect("Failed to lock JVM");
         let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
-        let logger = JavaLogger::new(&env, "com.example.Warn").expect("Failed to create JavaLogger");
-        setup_log4j(&logger).expect("Failed to set up log4j");
+        let logger = TestJvm::logger(&env, "com.example.Warn").expect("Failed to create JavaLogger");
 
         let inner_logger = logger.inner.lock().expect("Failed to lock inner logger");
         JavaLogger::log_warn(&inner_logger, "Warning log!").expect("Failed to log to WARN");
@@ -190,8 +766,7 @@ mod test {
     fn error() {
         let jvm = JVM.lock().expect("Failed to lock JVM");
         let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
-        let logger = JavaLogger::new(&env, "com.example.Error").expect("Failed to create JavaLogger");
-        setup_log4j(&logger).expect("Failed to set up log4j");
+        let logger = TestJvm::logger(&env, "com.example.Error").expect("Failed to create JavaLogger");
 
         let inner_logger = logger.inner.lock().expect("Failed to lock inner logger");
         JavaLogger::log_error(&inner_logger, "Error log!").expect("Failed to log to ERROR");
@@ -201,8 +776,7 @@ mod test {
     fn trace_and_debug() {
         let jvm = JVM.lock().expect("Failed to lock JVM");
         let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
-        let logger = JavaLogger::new(&env, "com.example.Debug").expect("Failed to create JavaLogger");
-        setup_log4j(&logger).expect("Failed to set up log4j");
+        let logger = TestJvm::logger(&env, "com.example.Debug").expect("Failed to create JavaLogger");
 
         let inner_logger = logger.inner.lock().expect("Failed to lock inner logger");
         JavaLogger::log_debug(&inner_logger, "Trace and debug log!").expect("Failed to log to DEBUG");
@@ -212,12 +786,519 @@ mod test {
     fn log_general() {
         let jvm = JVM.lock().expect("Failed to lock JVM");
         let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
-        let logger = JavaLogger::new(&env, "com.example.General").expect("Failed to create JavaLogger");
-        setup_log4j(&logger).expect("Failed to set up log4j");
+        let logger = TestJvm::logger(&env, "com.example.General").expect("Failed to create JavaLogger");
 
         logger.log(LogLevel::Error, "Error!").expect("Failed to log to ERROR level");
         logger.log(LogLevel::Warn, "Warn!").expect("Failed to log to WARN level");
         logger.log(LogLevel::Info, "Info!").expect("Failed to log to INFO level");
         logger.log(LogLevel::Debug, "Debug!").expect("Failed to log to DEBUG level");
     }
+
+    #[allow(dead_code)] // Only read through the Debug impl
+    #[derive(Debug)]
+    struct Nested {
+        id: u32,
+        tags: Vec<&'static str>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    struct Outer {
+        name: &'static str,
+        nested: Nested,
+    }
+
+    #[test]
+    fn debug_value() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let (logger, output) = TestJvm::capture_with_pattern(&env, "com.example.DebugValue", "%m%n").expect("Failed to create capturing logger");
+
+        let value = Outer {
+            name: "outer",
+            nested: Nested {
+                id: 1,
+                tags: vec!["a", "b"],
+            },
+        };
+        logger.log_debug_value(LogLevel::Info, "value", &value).expect("Failed to log debug value");
+
+        let output = output.contents().expect("Failed to read captured output");
+        assert_eq!(output.trim_end(), format!("value = {:#?}", value));
+        assert!(output.contains("    nested: Nested {\n        id: 1,"));
+    }
+
+    #[test]
+    fn coded_error() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture_with_pattern("CodedError", "%X{errorCode}|%m%n").expect("Failed to create capturing logger");
+
+        logger.log_coded_error("E1234", "Database unavailable").expect("Failed to log coded error");
+        logger.log(LogLevel::Error, "Uncoded").expect("Failed to log to ERROR level");
+
+        let output = output.contents().expect("Failed to read captured output");
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines, vec!["E1234|[E1234] Database unavailable", "|Uncoded"]);
+    }
+
+    #[test]
+    fn trace_id() {
+        thread_local! {
+            static REQUEST_ID: std::cell::RefCell<String> = const { std::cell::RefCell::new(String::new()) };
+        }
+
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture_with_pattern("TraceId", "%X{traceId} %m%n").expect("Failed to create capturing logger");
+        logger.with_trace_id(|| REQUEST_ID.with(|id| id.borrow().clone()));
+
+        for request in ["req-1", "req-2"].iter() {
+            REQUEST_ID.with(|id| *id.borrow_mut() = request.to_string());
+            logger.log(LogLevel::Info, "Handling").expect("Failed to log to INFO level");
+            logger.log(LogLevel::Info, "Handled").expect("Failed to log to INFO level");
+        }
+
+        let output = output.contents().expect("Failed to read captured output");
+        assert_eq!(output.lines().collect::<Vec<_>>(), vec!["req-1 Handling", "req-1 Handled", "req-2 Handling", "req-2 Handled"]);
+        assert_eq!(mdc::get(&env, "traceId").unwrap(), None);
+    }
+
+    #[test]
+    fn clone() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Clone").expect("Failed to create capturing logger");
+
+        let clone = logger.clone();
+        assert_send_sync(&clone);
+        assert!(Arc::ptr_eq(&logger.inner, &clone.inner));
+
+        logger.log(LogLevel::Info, "From original").expect("Failed to log to INFO level");
+        clone.log(LogLevel::Info, "From clone").expect("Failed to log to INFO level");
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec!["INFO From original", "INFO From clone"]);
+    }
+
+    #[test]
+    fn from_jobject() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (_, output) = scope.capture("FromJObject").expect("Failed to create capturing logger");
+
+        let name = env.new_string(scope.logger_name("FromJObject")).expect("Failed to create Java string");
+        let java_logger = env
+            .call_static_method(LOG_MANAGER_CLASS, "getLogger", "(Ljava/lang/String;)Lorg/apache/log4j/Logger;", &[JValue::Object(name.into())])
+            .and_then(|logger| logger.l())
+            .expect("Failed to get logger from LogManager");
+
+        let logger = JavaLogger::from_jobject(&env, java_logger).expect("Failed to wrap logger");
+        logger.log(LogLevel::Warn, "Wrapped").expect("Failed to log to WARN level");
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec!["WARN Wrapped"]);
+
+        assert!(matches!(JavaLogger::from_jobject(&env, JObject::null()), Err(Error::NotALogger)));
+        assert!(matches!(JavaLogger::from_jobject(&env, name.into()), Err(Error::NotALogger)));
+    }
+
+    #[test]
+    fn counts() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, _output) = scope.capture("Counts").expect("Failed to create capturing logger");
+        assert_eq!(logger.counts(), LevelCounts::default());
+
+        for _ in 0..3 {
+            logger.log(LogLevel::Error, "Error").expect("Failed to log to ERROR level");
+        }
+        logger.log(LogLevel::Warn, "Warning").expect("Failed to log to WARN level");
+        logger.clone().log(LogLevel::Info, "Info").expect("Failed to log to INFO level");
+        logger.log_utf16(LogLevel::Info, &"UTF-16".encode_utf16().collect::<Vec<_>>()).expect("Failed to log to INFO level");
+        // Logged at WARN, as log4j has no level with this value
+        logger.log(LogLevel::Custom(35000), "Custom").expect("Failed to log to custom level");
+        logger.log(LogLevel::Custom(50000), "Fatal").expect("Failed to log to FATAL level");
+
+        let counts = logger.counts();
+        assert_eq!(
+            counts,
+            LevelCounts {
+                error: 3,
+                warn: 2,
+                info: 2,
+                debug: 0,
+                custom: 1,
+            }
+        );
+        assert_eq!(counts.total(), 8);
+    }
+
+    #[test]
+    fn optional_methods() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Optional").expect("Failed to create capturing logger");
+        scope.set_level(&logger, LogLevel::Warn).expect("Failed to set level");
+
+        let capabilities = logger.capabilities();
+        assert!(capabilities.contains(Capabilities::EFFECTIVE_LEVEL));
+        assert_eq!(logger.effective_level().expect("Failed to get effective level"), LogLevel::Warn);
+        if capabilities.contains(Capabilities::CHAINED_PRIORITY) {
+            assert_eq!(logger.chained_priority().expect("Failed to get chained priority"), LogLevel::Warn);
+        }
+
+        // Logger#trace(Object) was only added in log4j 1.2.12
+        match logger.log_trace("Trace log!") {
+            Ok(()) => assert!(capabilities.contains(Capabilities::TRACE)),
+            Err(Error::Unsupported(method)) => {
+                assert!(!capabilities.contains(Capabilities::TRACE));
+                assert_eq!(method, "Logger#trace(Object)");
+            }
+            Err(e) => panic!("Unexpected error: {}", e),
+        }
+        assert!(output.lines().expect("Failed to read captured output").is_empty());
+    }
+
+    #[cfg(feature = "redact")]
+    #[test]
+    fn redact() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture_with_pattern("Redact", "%c{1} %m%n").expect("Failed to create capturing logger");
+
+        logger.set_redactor(vec![Regex::new(r"password=\S+").unwrap(), Regex::new(r"Bearer [A-Za-z0-9._-]+").unwrap()]);
+        logger.log(LogLevel::Info, "Login with password=hunter2 and Authorization: Bearer eyJhbGciOi.abc-123").expect("Failed to log to INFO level");
+
+        let output = output.contents().expect("Failed to read captured output");
+        assert_eq!(output.lines().collect::<Vec<_>>(), vec!["Redact Login with *** and Authorization: ***"]);
+    }
+
+    #[test]
+    fn transforms() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Transforms").expect("Failed to create capturing logger");
+
+        logger.push_transform(Arc::new(crate::EscapeNewlines));
+        logger.push_transform(Arc::new(crate::Truncate::new(16)));
+        logger.clone().push_transform(Arc::new(crate::LocationPrefix::new("db")));
+
+        logger.log(LogLevel::Warn, "Query failed:\nSELECT * FROM users").expect("Failed to log to WARN level");
+        logger.log_utf16(LogLevel::Info, &"Short\r\nline".encode_utf16().collect::<Vec<_>>()).expect("Failed to log to INFO level");
+
+        // Escaped before truncating, so the prefix added last is not counted against the limit
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec!["WARN [db] Query failed:\\nS...", "INFO [db] Short\\r\\nline"]);
+    }
+
+    #[test]
+    fn capture() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("Capture").expect("Failed to create JavaLogger");
+
+        let (result, output) = logger
+            .capture(|| {
+                logger.log(LogLevel::Info, "First line").expect("Failed to log to INFO level");
+                logger.log(LogLevel::Warn, "Second line").expect("Failed to log to WARN level");
+                42
+            })
+            .expect("Failed to capture output");
+
+        assert_eq!(result, 42);
+        assert!(output.contains("First line"));
+        assert!(output.contains("Second line"));
+
+        let inner = logger.inner.lock().unwrap();
+        assert!(appender::get_additivity(inner.env, inner.logger).unwrap());
+    }
+
+    #[test]
+    fn capture_panic() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("CapturePanic").expect("Failed to create JavaLogger");
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            logger.capture(|| {
+                logger.log(LogLevel::Info, "Before panicking").expect("Failed to log to INFO level");
+                panic!("Capture closure panicked");
+            })
+        }));
+        assert!(panicked.is_err());
+
+        let inner = logger.inner.lock().unwrap();
+        assert!(appender::get_additivity(inner.env, inner.logger).unwrap());
+        assert!(appender::all_appenders(inner.env, inner.logger).unwrap().is_empty());
+    }
+
+    #[test]
+    fn primitives() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture_with_pattern("Primitives", "%m%n").expect("Failed to create capturing logger");
+
+        logger.log_i64(LogLevel::Info, -9_007_199_254_740_993).expect("Failed to log i64");
+        logger.log_f64(LogLevel::Info, 0.1).expect("Failed to log f64");
+        logger.log_f64(LogLevel::Info, f64::NAN).expect("Failed to log f64");
+        logger.log_bool(LogLevel::Info, true).expect("Failed to log bool");
+
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec!["-9007199254740993", "0.1", "NaN", "true"]);
+    }
+
+    #[test]
+    fn last_message() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("LastMessage").expect("Failed to create JavaLogger");
+
+        // Not tracked by default
+        logger.log(LogLevel::Info, "Untracked").expect("Failed to log to INFO level");
+        assert_eq!(logger.last_message(), None);
+
+        logger.set_track_last_message(true);
+        logger.log(LogLevel::Info, "First").expect("Failed to log to INFO level");
+        logger.clone().log(LogLevel::Warn, "Second ✓").expect("Failed to log to WARN level");
+        assert_eq!(logger.last_message(), Some((LogLevel::Warn, "Second ✓".to_string())));
+
+        logger.set_track_last_message(false);
+        assert_eq!(logger.last_message(), None);
+    }
+
+    #[test]
+    fn paused() {
+        /// Resumes logging when dropped, so a failing assertion doesn't silence other tests
+        struct Resume;
+        impl Drop for Resume {
+            fn drop(&mut self) {
+                set_logging_paused(false);
+            }
+        }
+
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Paused").expect("Failed to create capturing logger");
+
+        let _resume = Resume;
+        set_logging_paused(true);
+        assert!(logging_paused());
+        logger.log(LogLevel::Error, "During maintenance").expect("Failed to log while paused");
+        logger.log_i64(LogLevel::Info, 1).expect("Failed to log while paused");
+        assert!(output.lines().expect("Failed to read captured output").is_empty());
+        assert_eq!(logger.counts().total(), 0);
+
+        set_logging_paused(false);
+        logger.log(LogLevel::Info, "Resumed").expect("Failed to log");
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec!["INFO Resumed"]);
+    }
+
+    #[test]
+    fn base64() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture_with_pattern("Base64", "%m%n").expect("Failed to create capturing logger");
+
+        let large = vec![0xffu8; MAX_BASE64_BYTES + 1];
+        logger.log_base64(LogLevel::Info, "payload", &[0x00, 0xfb, 0xff, 0x4d, 0x61]).expect("Failed to log base64");
+        logger.log_base64(LogLevel::Info, "empty", &[]).expect("Failed to log base64");
+        logger.log_base64(LogLevel::Info, "large", &large).expect("Failed to log base64");
+
+        let lines = output.lines().expect("Failed to read captured output");
+        assert_eq!(lines[..2], ["payload: APv/TWE=", "empty: "]);
+        assert!(lines[2].starts_with("large: ////"));
+        assert!(lines[2].ends_with("/w==... (65536 of 65537 bytes)"), "{}", &lines[2][lines[2].len() - 40..]);
+    }
+
+    #[test]
+    fn utf16() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Utf16").expect("Failed to create capturing logger");
+
+        // Supplementary-plane characters are encoded as surrogate pairs
+        let message = "Emoji \u{1F600}, clef \u{1D11E} and \u{20000}";
+        let units: Vec<u16> = message.encode_utf16().collect();
+        logger.log_utf16(LogLevel::Info, &units).expect("Failed to log UTF-16 message");
+        logger.log_utf16(LogLevel::Custom(LogLevel::Warn.to_int()), &units).expect("Failed to log UTF-16 message");
+
+        let java_string = env.new_string(message).unwrap();
+        logger.log_jstring(LogLevel::Error, java_string).expect("Failed to log Java string");
+
+        assert_eq!(
+            output.lines().expect("Failed to read captured output"),
+            vec![format!("INFO {}", message), format!("WARN {}", message), format!("ERROR {}", message)]
+        );
+    }
+
+    #[test]
+    fn progress() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Progress").expect("Failed to create capturing logger");
+
+        for current in 0..=1000 {
+            logger.log_progress("import", current, 1000).expect("Failed to log progress");
+        }
+        let lines = output.lines().expect("Failed to read captured output");
+        assert_eq!(lines.len(), 101);
+        assert_eq!(lines[0], "INFO import: 0% (0/1000)");
+        assert_eq!(lines[1], "INFO import: 1% (10/1000)");
+        assert_eq!(lines[100], "INFO import: 100% (1000/1000)");
+
+        logger.log_progress("scan", 3, 0).expect("Failed to log progress");
+        logger.log_progress("scan", 4, 0).expect("Failed to log progress");
+        let lines = output.lines().expect("Failed to read captured output");
+        assert_eq!(&lines[101..], ["INFO scan: 3 (unknown total)"]);
+    }
+
+    #[test]
+    fn sampled() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Sampled").expect("Failed to create capturing logger");
+
+        let logged = (0..100).filter(|i| logger.log_sampled(LogLevel::Info, format!("Event {}", i), 10).expect("Failed to log sampled")).count();
+        assert_eq!(logged, 10);
+        let lines = output.lines().expect("Failed to read captured output");
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "INFO Event 0");
+        assert_eq!(lines[9], "INFO Event 90");
+
+        for rate in [0, 1] {
+            assert!((0..5).all(|_| logger.log_sampled(LogLevel::Info, "Every call", rate).expect("Failed to log sampled")));
+        }
+        assert_eq!(output.lines().expect("Failed to read captured output").len(), 20);
+    }
+
+    #[test]
+    fn diff() {
+        #[derive(Debug, PartialEq)]
+        struct Config {
+            workers: u32,
+            name: &'static str,
+        }
+
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Diff").expect("Failed to create capturing logger");
+
+        let before = Config { workers: 4, name: "main" };
+        logger.log_diff(LogLevel::Info, "config", &before, &Config { workers: 4, name: "main" }).expect("Failed to log diff");
+        assert!(output.lines().expect("Failed to read captured output").is_empty());
+
+        logger.log_diff(LogLevel::Info, "config", &before, &Config { workers: 8, name: "main" }).expect("Failed to log diff");
+        assert_eq!(
+            output.lines().expect("Failed to read captured output"),
+            vec![r#"INFO config changed: Config { workers: 4, name: "main" } -> Config { workers: 8, name: "main" }"#]
+        );
+    }
+
+    #[test]
+    fn since_checkpoint() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("SinceCheckpoint").expect("Failed to create capturing logger");
+        let clock = Arc::new(test_support::ManualClock::new());
+        logger.set_clock(clock.clone());
+
+        logger.checkpoint("parse");
+        clock.advance(std::time::Duration::from_millis(1500));
+        logger.log_since_checkpoint(LogLevel::Info, "parse", "Parsed").expect("Failed to log since checkpoint");
+        logger.checkpoint("parse");
+        logger.log_since_checkpoint(LogLevel::Info, "parse", "Parsed again").expect("Failed to log since checkpoint");
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec!["INFO [parse +1500ms] Parsed", "INFO [parse +0ms] Parsed again"]);
+
+        match logger.log_since_checkpoint(LogLevel::Info, "render", "Rendered") {
+            Err(Error::UnknownCheckpoint(name)) => assert_eq!(name, "render"),
+            _ => panic!("Expected UnknownCheckpoint"),
+        }
+    }
+
+    #[test]
+    fn since_epoch_manual_clock() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("SinceEpochManual").expect("Failed to create capturing logger");
+        let clock = Arc::new(test_support::ManualClock::new());
+        logger.set_clock(clock.clone());
+
+        clock.advance(std::time::Duration::from_millis(250));
+        logger.log_since_epoch(LogLevel::Info, "Stage done").expect("Failed to log since epoch");
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec!["INFO [+250ms] Stage done"]);
+    }
+
+    #[cfg(feature = "timestamp")]
+    #[test]
+    fn timestamped() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture_with_pattern("Timestamped", "%m%n").expect("Failed to create capturing logger");
+
+        let before = chrono::Utc::now();
+        logger.log_timestamped(LogLevel::Info, "Started").expect("Failed to log timestamped message");
+        let after = chrono::Utc::now();
+
+        let lines = output.lines().expect("Failed to read captured output");
+        let (timestamp, message) = lines[0].split_once(' ').expect("Missing timestamp");
+        assert_eq!(message, "Started");
+        assert!(timestamp.ends_with('Z'), "Timestamp {} is not in UTC", timestamp);
+
+        let timestamp = chrono::DateTime::parse_from_rfc3339(timestamp).expect("Failed to parse timestamp");
+        assert!(timestamp >= before - chrono::Duration::milliseconds(1) && timestamp <= after);
+    }
+
+    #[test]
+    fn since_epoch() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture_with_pattern("SinceEpoch", "%m%n").expect("Failed to create capturing logger");
+
+        logger.set_epoch(Instant::now());
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        logger.log_since_epoch(LogLevel::Info, "Stage done").expect("Failed to log since epoch");
+
+        let output = output.contents().expect("Failed to read captured output");
+        let elapsed: u128 = output
+            .strip_prefix("[+")
+            .and_then(|rest| rest.split("ms] ").next())
+            .and_then(|ms| ms.parse().ok())
+            .expect("Missing elapsed prefix");
+        assert!((50..10_000).contains(&elapsed));
+        assert!(output.trim_end().ends_with("ms] Stage done"));
+    }
+
+    #[test]
+    fn custom_level() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("CustomLevel").expect("Failed to create capturing logger");
+
+        let audit = crate::register_custom_level(&env, "AUDIT", 35000, 4).expect("Failed to register custom level");
+        assert!(audit.as_log_level().is_greater_or_equal(&LogLevel::Warn));
+        logger.log_at_custom(&audit, "User deleted").expect("Failed to log at custom level");
+
+        let output = output.contents().expect("Failed to read captured output");
+        assert_eq!(output.lines().collect::<Vec<_>>(), vec!["AUDIT User deleted"]);
+    }
 }
@@ -0,0 +1,284 @@
+//! The MDC (Mapped Diagnostic Context) of log4j, which is local to each Java thread
+
+use crate::error::Result;
+use crate::sys;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use std::collections::HashMap;
+
+const MDC_CLASS: &str = "org/apache/log4j/MDC";
+
+/// Put a value in the MDC of the current thread using `MDC#put(String, Object)`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn put(env: &JNIEnv, key: &str, value: &str) -> Result<()> {
+    sys::local_frame(env, || {
+        let key = env.new_string(key)?;
+        let value = env.new_string(value)?;
+        env.call_static_method(MDC_CLASS, "put", "(Ljava/lang/String;Ljava/lang/Object;)V", &[JValue::Object(key.into()), JValue::Object(value.into())])?;
+        Ok(())
+    })
+}
+
+/// Get a value from the MDC of the current thread using `MDC#get(String)`.
+/// Values which are not Strings are converted using `toString()`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn get(env: &JNIEnv, key: &str) -> Result<Option<String>> {
+    sys::local_frame(env, || {
+        let key = env.new_string(key)?;
+        let value = env.call_static_method(MDC_CLASS, "get", "(Ljava/lang/String;)Ljava/lang/Object;", &[JValue::Object(key.into())])?.l()?;
+        if value.is_null() {
+            return Ok(None);
+        }
+
+        Ok(Some(to_string(env, value)?))
+    })
+}
+
+/// Remove a value from the MDC of the current thread using `MDC#remove(String)`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn remove(env: &JNIEnv, key: &str) -> Result<()> {
+    sys::local_frame(env, || {
+        let key = env.new_string(key)?;
+        env.call_static_method(MDC_CLASS, "remove", "(Ljava/lang/String;)V", &[JValue::Object(key.into())])?;
+        Ok(())
+    })
+}
+
+/// Remove every value from the MDC of the current thread, one key at a time.
+/// `MDC#clear()` is not used, as it is missing before log4j 1.2.16
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn clear(env: &JNIEnv) -> Result<()> {
+    for key in snapshot(env)?.keys() {
+        remove(env, key)?;
+    }
+
+    Ok(())
+}
+
+/// Get a copy of the entire MDC of the current thread using `MDC#getContext()`.
+/// Values which are not Strings are converted using `toString()`. Returns an empty map if the thread has no MDC.
+///
+/// The copy is taken entry by entry and is not synchronized with other changes, e.g. an MDC modified from Java code
+/// running on the same thread while the copy is taken
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub fn snapshot(env: &JNIEnv) -> Result<HashMap<String, String>> {
+    sys::local_frame(env, || {
+        let context = env.call_static_method(MDC_CLASS, "getContext", "()Ljava/util/Hashtable;", &[])?.l()?;
+        if context.is_null() {
+            return Ok(HashMap::new());
+        }
+
+        let mut snapshot = HashMap::new();
+        for (key, value) in env.get_map(context)?.iter()? {
+            // Freed per entry, so large maps don't exhaust the local reference frame
+            snapshot.insert(to_string(env, key)?, to_string(env, value)?);
+            env.delete_local_ref(key)?;
+            env.delete_local_ref(value)?;
+        }
+
+        Ok(snapshot)
+    })
+}
+
+/// Replace the entire MDC of the current thread: every value is removed, then every entry of `map` is put in it.
+/// Together with `snapshot` this propagates the MDC to another thread
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub fn replace(env: &JNIEnv, map: &HashMap<String, String>) -> Result<()> {
+    clear(env)?;
+    for (key, value) in map {
+        put(env, key, value)?;
+    }
+
+    Ok(())
+}
+
+/// Get a copy of the entire MDC of the current thread, see `mdc::snapshot`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub fn mdc_snapshot(env: &JNIEnv) -> Result<HashMap<String, String>> {
+    snapshot(env)
+}
+
+/// A copy of the MDC of a thread, which can be moved to another thread and installed there.
+/// The MDC is thread-local in Java, so threads spawned from Rust start without the MDC of the thread that spawned them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MdcContext {
+    entries: HashMap<String, String>,
+}
+
+impl MdcContext {
+    /// Put every captured value in the MDC of the current thread, overwriting values with the same key.
+    /// Other values already in the MDC are kept
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn install(&self, env: &JNIEnv) -> Result<()> {
+        for (key, value) in &self.entries {
+            put(env, key, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// The captured values
+    pub fn entries(&self) -> &HashMap<String, String> {
+        &self.entries
+    }
+}
+
+/// Capture the MDC of the current thread, e.g. to propagate correlation ids to a spawned thread with `MdcContext::install`.
+/// Values which are not Strings are converted using `toString()`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub fn capture_mdc(env: &JNIEnv) -> Result<MdcContext> {
+    Ok(MdcContext {
+        entries: mdc_snapshot(env)?,
+    })
+}
+
+/// Convert any object to a String using `toString()`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+fn to_string(env: &JNIEnv, object: JObject) -> Result<String> {
+    let string = env.call_method(object, "toString", "()Ljava/lang/String;", &[])?.l()?;
+    let converted = env.get_string(string.into())?.into();
+    env.delete_local_ref(string)?;
+    Ok(converted)
+}
+
+/// Put a value in the MDC while running `f`, restoring the previous value (or absence of one) afterwards
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+/// - If `f` fails
+pub(crate) fn with_value<T, F: FnOnce() -> Result<T>>(env: &JNIEnv, key: &str, value: &str, f: F) -> Result<T> {
+    let previous = get(env, key)?;
+    put(env, key, value)?;
+
+    let result = f();
+    match previous {
+        Some(previous) => put(env, key, &previous)?,
+        None => remove(env, key)?,
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support;
+    use crate::{JavaLogger, LogLevel};
+
+    #[test]
+    fn snapshot() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        put(&env, "requestId", "abc-123").expect("Failed to put requestId");
+        put(&env, "user", "jänis").expect("Failed to put user");
+
+        let snapshot = mdc_snapshot(&env).expect("Failed to take MDC snapshot");
+        assert_eq!(snapshot.get("requestId").map(String::as_str), Some("abc-123"));
+        assert_eq!(snapshot.get("user").map(String::as_str), Some("jänis"));
+
+        remove(&env, "requestId").expect("Failed to remove requestId");
+        remove(&env, "user").expect("Failed to remove user");
+        let snapshot = mdc_snapshot(&env).expect("Failed to take MDC snapshot");
+        assert!(!snapshot.contains_key("requestId"));
+        assert!(!snapshot.contains_key("user"));
+    }
+
+    #[test]
+    fn clear_without_mdc_clear() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        // The harness pins log4j 1.2.9, which has no MDC#clear()
+        put(&env, "requestId", "abc-123").expect("Failed to put requestId");
+        put(&env, "user", "jänis").expect("Failed to put user");
+        clear(&env).expect("Failed to clear MDC");
+        assert!(super::snapshot(&env).expect("Failed to take MDC snapshot").is_empty());
+
+        replace(&env, &std::iter::once(("requestId".to_string(), "def-456".to_string())).collect()).expect("Failed to replace MDC");
+        assert_eq!(get(&env, "requestId").expect("Failed to get requestId").as_deref(), Some("def-456"));
+        clear(&env).expect("Failed to clear MDC");
+    }
+
+    #[test]
+    fn snapshot_replace_across_threads() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        put(&env, "requestId", "abc-123").expect("Failed to put requestId");
+        put(&env, "ユーザー", "jänis 🦀").expect("Failed to put user");
+        let snap = super::snapshot(&env).expect("Failed to take MDC snapshot");
+        clear(&env).expect("Failed to clear MDC");
+
+        let vm: &jni::JavaVM = &jvm;
+        let replaced = std::thread::scope(|threads| {
+            threads
+                .spawn(|| {
+                    let env = vm.attach_current_thread().expect("Failed to attach worker thread to the JVM");
+                    put(&env, "stale", "value").expect("Failed to put stale value");
+                    replace(&env, &snap).expect("Failed to replace MDC");
+                    let replaced = super::snapshot(&env).expect("Failed to take MDC snapshot");
+                    clear(&env).expect("Failed to clear MDC");
+                    replaced
+                })
+                .join()
+                .expect("Worker thread panicked")
+        });
+
+        assert_eq!(replaced, snap);
+        assert_eq!(replaced.get("ユーザー").map(String::as_str), Some("jänis 🦀"));
+        assert!(!replaced.contains_key("stale"));
+        assert!(super::snapshot(&env).expect("Failed to take MDC snapshot").is_empty());
+    }
+
+    #[test]
+    fn propagate_to_thread() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (_logger, output) = scope.capture_with_pattern("Propagated", "%X{requestId} %m%n").expect("Failed to create capturing logger");
+        let logger_name = scope.logger_name("Propagated");
+
+        put(&env, "requestId", "req-42").expect("Failed to put requestId");
+        let context = capture_mdc(&env).expect("Failed to capture MDC");
+        remove(&env, "requestId").expect("Failed to remove requestId");
+
+        let vm: &jni::JavaVM = &jvm;
+        std::thread::scope(|threads| {
+            threads
+                .spawn(|| {
+                    let env = vm.attach_current_thread().expect("Failed to attach worker thread to the JVM");
+                    let logger = JavaLogger::new(&env, &logger_name).expect("Failed to create JavaLogger");
+                    logger.log(LogLevel::Info, "Before install").expect("Failed to log to INFO level");
+                    context.install(&env).expect("Failed to install MDC context");
+                    logger.log(LogLevel::Info, "After install").expect("Failed to log to INFO level");
+                    remove(&env, "requestId").expect("Failed to remove requestId");
+                })
+                .join()
+                .expect("Worker thread panicked");
+        });
+
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec![" Before install", "req-42 After install"]);
+    }
+}
@@ -0,0 +1,154 @@
+#[cfg(windows)]
+use crate::appender::add_appender;
+use crate::appender::build_layout;
+use crate::error::{Error, Result};
+#[cfg(windows)]
+use crate::logger::JavaLogger;
+use crate::sys;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+
+const NT_EVENT_LOG_APPENDER_CLASS: &str = "org/apache/log4j/nt/NTEventLogAppender";
+const LINKAGE_ERROR_CLASS: &str = "java/lang/LinkageError";
+/// The conversion pattern used unless configured otherwise, event log entries have no trailing line separator
+const DEFAULT_PATTERN: &str = "%m";
+
+/// Builder for an `org.apache.log4j.nt.NTEventLogAppender`, writing to the Windows event log.
+/// The appender relies on `NTEventLogAppender.dll`, so it is only available on Windows with the DLL on the `java.library.path`
+#[derive(Debug, Clone)]
+pub struct NTEventLogAppenderBuilder {
+    source: String,
+    pattern: String,
+}
+
+impl NTEventLogAppenderBuilder {
+    /// Create a builder for an appender logging under the event source `source`
+    pub fn new<S: Into<String>>(source: S) -> Self {
+        Self {
+            source: source.into(),
+            pattern: DEFAULT_PATTERN.to_string(),
+        }
+    }
+
+    /// Set the conversion pattern for the `PatternLayout`, defaults to `%m`
+    pub fn pattern<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.pattern = pattern.into();
+        self
+    }
+
+    /// Create the appender, it still has to be attached to a logger
+    ///
+    /// # Error
+    /// - `Error::AppenderUnavailable` if the appender class or its native library cannot be loaded, e.g. outside of Windows
+    /// - If the pattern is invalid
+    /// - If one of the underlying JNI calls fail
+    pub fn build<'a>(&self, env: &JNIEnv<'a>) -> Result<JObject<'a>> {
+        sys::local_frame_object(env, || {
+            // Loading the class also loads the native library, which fails with a LinkageError when it is missing
+            let class = match env.find_class(NT_EVENT_LOG_APPENDER_CLASS) {
+                Ok(class) => class,
+                Err(e) => return Err(unavailable_on_linkage_error(env, e.into())),
+            };
+
+            let layout = build_layout(env, &self.pattern, false)?;
+            let source = env.new_string(&self.source)?;
+            env.new_object(class, "(Ljava/lang/String;Lorg/apache/log4j/Layout;)V", &[JValue::Object(source.into()), JValue::Object(layout)])
+                .map_err(|e| unavailable_on_linkage_error(env, e.into()))
+        })
+    }
+}
+
+#[cfg(windows)]
+impl<'a> JavaLogger<'a> {
+    /// Attach an `NTEventLogAppender`, so events show up in the Windows Event Viewer.
+    /// log4j loads `NTEventLogAppender.dll` when the appender class is first used, the DLL must be in a directory on the `java.library.path`.
+    /// It is shipped in the log4j distribution, not in the jar. Writing under a new event source may require administrator rights
+    ///
+    /// # Params
+    /// - `source` The event source the entries are logged under, e.g. the application name
+    /// - `pattern` The conversion pattern for the `PatternLayout`, event log entries need no trailing `%n`
+    ///
+    /// # Error
+    /// - `Error::AppenderUnavailable` if the appender class or its native library cannot be loaded
+    /// - If the pattern is invalid
+    /// - If one of the underlying JNI calls fail
+    pub fn add_nt_event_log_appender(&self, source: &str, pattern: &str) -> Result<()> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        sys::local_frame(inner.env, || {
+            let appender = NTEventLogAppenderBuilder::new(source).pattern(pattern).build(inner.env)?;
+            add_appender(inner.env, inner.logger, appender)
+        })
+    }
+}
+
+/// Turn `error` into `Error::AppenderUnavailable` if it was caused by a pending `LinkageError`, which is cleared
+fn unavailable_on_linkage_error(env: &JNIEnv, error: Error) -> Error {
+    let linkage_error = env.exception_occurred().ok().filter(|throwable| !throwable.is_null()).map(|throwable| {
+        let is_linkage_error = env.is_instance_of(throwable, LINKAGE_ERROR_CLASS);
+        let _ = env.delete_local_ref(throwable.into());
+        is_linkage_error
+    });
+
+    match linkage_error {
+        Some(Ok(true)) => {
+            let _ = env.exception_clear();
+            Error::AppenderUnavailable("NTEventLogAppender")
+        }
+        _ => error,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn unavailable() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        // Fails for the first load of the class as well as later ones
+        for _ in 0..2 {
+            match NTEventLogAppenderBuilder::new("log4j-rs").build(&env) {
+                Err(Error::AppenderUnavailable(appender)) => assert_eq!(appender, "NTEventLogAppender"),
+                _ => panic!("Expected AppenderUnavailable"),
+            }
+            assert!(!env.exception_check().unwrap());
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn event_log() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = crate::test_support::isolated(&env, "com.example");
+        let logger = scope.logger("EventLog").expect("Failed to create JavaLogger");
+
+        let appender = NTEventLogAppenderBuilder::new("log4j-rs").pattern("%p %m").build(&env).expect("Failed to build NTEventLogAppender");
+        scope.add_appender(&logger, appender).expect("Failed to add NTEventLogAppender");
+        logger.log(crate::LogLevel::Info, "Event log message").expect("Failed to log to INFO level");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn add_nt_event_log_appender() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = crate::test_support::isolated(&env, "com.example");
+        let logger = scope.logger("AddEventLog").expect("Failed to create JavaLogger");
+
+        logger.add_nt_event_log_appender("log4j-rs", "%p %m").expect("Failed to add NTEventLogAppender");
+        logger.reconfigure(|ctx| {
+            assert_eq!(ctx.appenders()?.len(), 1);
+            Ok(())
+        })
+        .expect("Failed to inspect appenders");
+        logger.log(crate::LogLevel::Info, "Event log message").expect("Failed to log to INFO level");
+
+        logger.close_appenders().expect("Failed to close appenders");
+        logger.reconfigure(|ctx| ctx.remove_all_appenders()).expect("Failed to remove appenders");
+    }
+}
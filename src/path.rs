@@ -0,0 +1,96 @@
+use crate::error::{Error, Result};
+use std::path::Path;
+
+/// Prefix of a verbatim Windows path, e.g. `\\?\C:\logs`
+const VERBATIM_PREFIX: &str = r"\\?\";
+/// Prefix of a verbatim Windows UNC path, e.g. `\\?\UNC\server\share`
+const VERBATIM_UNC_PREFIX: &str = r"\\?\UNC\";
+
+/// How `path_to_java_string` handles paths which are not valid Unicode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathConversion {
+    /// Replace invalid sequences with `U+FFFD`. The resulting path may refer to a different file
+    Lossy,
+    /// Fail with `Error::NonUnicodePath`
+    Strict,
+}
+
+/// Convert a path to the string handed to Java, e.g. as the file name of a `FileAppender`.
+/// Every path accepting API of this crate converts its paths with `PathConversion::Lossy`.
+///
+/// On Windows, verbatim prefixes are removed as `java.io.File` does not understand them:
+/// `\\?\C:\logs` becomes `C:\logs` and `\\?\UNC\server\share` becomes `\\server\share`.
+/// Forward slashes are replaced with backslashes. Paths on other platforms are passed on unchanged
+///
+/// # Error
+/// - If the path is not valid Unicode and `conversion` is `PathConversion::Strict`
+pub fn path_to_java_string(path: &Path, conversion: PathConversion) -> Result<String> {
+    let path = match (path.to_str(), conversion) {
+        (Some(path), _) => path.into(),
+        (None, PathConversion::Lossy) => path.to_string_lossy(),
+        (None, PathConversion::Strict) => return Err(Error::NonUnicodePath(path.to_path_buf())),
+    };
+
+    Ok(normalize(&path, cfg!(windows)))
+}
+
+/// Normalize a path for `java.io.File`, see `path_to_java_string`
+fn normalize(path: &str, windows: bool) -> String {
+    if !windows {
+        return path.to_string();
+    }
+
+    let path = if let Some(unc) = path.strip_prefix(VERBATIM_UNC_PREFIX) {
+        format!(r"\\{}", unc)
+    } else {
+        path.strip_prefix(VERBATIM_PREFIX).unwrap_or(path).to_string()
+    };
+
+    path.replace('/', r"\")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalize_windows() {
+        assert_eq!(normalize(r"C:\logs\app.log", true), r"C:\logs\app.log");
+        assert_eq!(normalize("C:/logs/app.log", true), r"C:\logs\app.log");
+        assert_eq!(normalize(r"\\?\C:\logs\app.log", true), r"C:\logs\app.log");
+        assert_eq!(normalize(r"\\?\UNC\server\share\app.log", true), r"\\server\share\app.log");
+        assert_eq!(normalize(r"\\server\share/app.log", true), r"\\server\share\app.log");
+        assert_eq!(normalize("logs/app.log", true), r"logs\app.log");
+    }
+
+    #[test]
+    fn normalize_other() {
+        assert_eq!(normalize("/var/log/app.log", false), "/var/log/app.log");
+        // Backslashes are valid in file names outside of Windows
+        assert_eq!(normalize(r"logs\app.log", false), r"logs\app.log");
+        assert_eq!(normalize(r"\\?\C:\logs", false), r"\\?\C:\logs");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_paths() {
+        assert_eq!(path_to_java_string(Path::new(r"\\?\C:\logs\app.log"), PathConversion::Strict).unwrap(), r"C:\logs\app.log");
+        assert_eq!(path_to_java_string(Path::new("C:/logs/app.log"), PathConversion::Strict).unwrap(), r"C:\logs\app.log");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_unicode() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"/var/log/\xFFapp.log"));
+        assert_eq!(path_to_java_string(path, PathConversion::Lossy).unwrap(), "/var/log/\u{FFFD}app.log");
+        match path_to_java_string(path, PathConversion::Strict) {
+            Err(Error::NonUnicodePath(invalid)) => assert_eq!(invalid, path),
+            _ => panic!("Expected NonUnicodePath"),
+        }
+
+        assert_eq!(path_to_java_string(Path::new("/var/log/app.log"), PathConversion::Strict).unwrap(), "/var/log/app.log");
+    }
+}
@@ -0,0 +1,280 @@
+use crate::error::{Error, Result};
+use crate::sys;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+
+/// The logcat-like conversion pattern, e.g. `10-16 13:37:00.123 INFO/com.example.Example: Message`
+const LOGCAT_PATTERN: &str = "%d{MM-dd HH:mm:ss.SSS} %p/%c: %m%n";
+/// The separator placed between conversions unless configured otherwise
+const DEFAULT_SEPARATOR: &str = " ";
+
+const PATTERN_LAYOUT_CLASS: &str = "org/apache/log4j/PatternLayout";
+const LOGGING_EVENT_CLASS: &str = "org/apache/log4j/spi/LoggingEvent";
+const LOGGER_CLASS: &str = "org/apache/log4j/Logger";
+const LEVEL_CLASS: &str = "org/apache/log4j/Level";
+/// Fully qualified name of the class log4j considers the caller boundary for location information
+const CATEGORY_FQN: &str = "org.apache.log4j.Category";
+/// The message of the event rendered by `validate_pattern`
+const VALIDATION_MESSAGE: &str = "log4j-rs pattern validation";
+
+/// Builder for conversion patterns used by log4j's `PatternLayout`.
+/// Conversions are separated by a single space by default, and the pattern is terminated with `%n`
+///
+/// ```
+/// # use log4j::Pattern;
+/// let pattern = Pattern::new().date("ISO8601").level().thread().message().build();
+/// assert_eq!(pattern, "%d{ISO8601} %p %t %m%n");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    conversions: Vec<String>,
+    separator: String,
+}
+
+impl Default for Pattern {
+    fn default() -> Self {
+        Self {
+            conversions: Vec::new(),
+            separator: DEFAULT_SEPARATOR.to_string(),
+        }
+    }
+}
+
+impl Pattern {
+    /// Create an empty pattern
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A conversion pattern resembling Android's logcat output: `%d{MM-dd HH:mm:ss.SSS} %p/%c: %m%n`
+    pub fn logcat() -> String {
+        LOGCAT_PATTERN.to_string()
+    }
+
+    /// Set the separator placed between conversions, defaults to a single space
+    pub fn separator<S: AsRef<str>>(mut self, separator: S) -> Self {
+        self.separator = escape(separator.as_ref());
+        self
+    }
+
+    /// The date of the event, `%d{format}`.
+    /// `format` is either one of log4j's named formats (`ISO8601`, `ABSOLUTE`, `DATE`) or a `SimpleDateFormat` pattern
+    pub fn date<S: AsRef<str>>(self, format: S) -> Self {
+        self.push(format!("%d{{{}}}", format.as_ref()))
+    }
+
+    /// The level of the event, `%p`
+    pub fn level(self) -> Self {
+        self.push("%p")
+    }
+
+    /// The level of the event, padded to 5 characters so messages line up, `%-5p`
+    pub fn level_padded(self) -> Self {
+        self.push("%-5p")
+    }
+
+    /// The name of the thread that generated the event, `%t`
+    pub fn thread(self) -> Self {
+        self.push("%t")
+    }
+
+    /// The name of the logger, `%c`
+    pub fn category(self) -> Self {
+        self.push("%c")
+    }
+
+    /// Milliseconds elapsed since the layout was created, `%r`
+    pub fn relative(self) -> Self {
+        self.push("%r")
+    }
+
+    /// The NDC of the thread that generated the event, `%x`
+    pub fn ndc(self) -> Self {
+        self.push("%x")
+    }
+
+    /// The MDC value for `key`, `%X{key}`
+    pub fn mdc<S: AsRef<str>>(self, key: S) -> Self {
+        self.push(format!("%X{{{}}}", key.as_ref()))
+    }
+
+    /// The message of the event, `%m`
+    pub fn message(self) -> Self {
+        self.push("%m")
+    }
+
+    /// Literal text, any `%` is escaped
+    pub fn literal<S: AsRef<str>>(self, text: S) -> Self {
+        let text = escape(text.as_ref());
+        self.push(text)
+    }
+
+    /// Assemble the conversion pattern, terminated with `%n`
+    pub fn build(self) -> String {
+        let mut pattern = self.conversions.join(&self.separator);
+        pattern.push_str("%n");
+        pattern
+    }
+
+    fn push<S: Into<String>>(mut self, conversion: S) -> Self {
+        self.conversions.push(conversion.into());
+        self
+    }
+}
+
+/// Conversion characters understood by log4j 1.2's `PatternParser`
+const CONVERSION_CHARS: &str = "cCdFlLmMnprtxX";
+/// Conversion characters which accept an option in braces, e.g. `%d{ISO8601}`
+const OPTION_CHARS: &str = "cCdX";
+
+/// Check a conversion pattern for syntax errors: a `%` which is not followed by a known conversion,
+/// or an option brace which is never closed. Returns a description of the first problem found
+pub(crate) fn check_syntax(pattern: &str) -> std::result::Result<(), String> {
+    let mut chars = pattern.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+
+        // Format modifiers, e.g. %-5p or %.30c
+        while let Some((_, '-' | '.' | '0'..='9')) = chars.peek() {
+            chars.next();
+        }
+
+        match chars.next() {
+            Some((_, '%')) => {}
+            Some((_, conversion)) if CONVERSION_CHARS.contains(conversion) => {
+                if OPTION_CHARS.contains(conversion) && matches!(chars.peek(), Some((_, '{'))) {
+                    let (open, _) = chars.next().unwrap();
+                    if !chars.any(|(_, c)| c == '}') {
+                        return Err(format!("unclosed '{{' at offset {}", open));
+                    }
+                }
+            }
+            Some((_, conversion)) => return Err(format!("unknown conversion '%{}' at offset {}", conversion, offset)),
+            None => return Err(format!("dangling '%' at offset {}", offset)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate a conversion pattern before using it, e.g. when loading a configuration.
+/// The pattern's syntax is checked, then a `PatternLayout` is created with it and a dummy `LoggingEvent` is rendered through it
+///
+/// # Error
+/// - `Error::InvalidPattern` if the pattern is malformed, or log4j fails to render an event with it
+/// - If one of the underlying JNI calls fail
+pub fn validate_pattern(env: &JNIEnv, pattern: &str) -> Result<()> {
+    check_syntax(pattern).map_err(|reason| Error::InvalidPattern {
+        pattern: pattern.to_string(),
+        reason,
+    })?;
+
+    let rendered = sys::local_frame(env, || {
+        let layout = env.new_object(PATTERN_LAYOUT_CLASS, "()V", &[])?;
+        env.call_method(layout, "setConversionPattern", "(Ljava/lang/String;)V", &[JValue::Object(env.new_string(pattern)?.into())])?;
+
+        let logger = env.call_static_method(LOGGER_CLASS, "getRootLogger", "()Lorg/apache/log4j/Logger;", &[])?.l()?;
+        let priority = env.get_static_field(LEVEL_CLASS, "INFO", "Lorg/apache/log4j/Level;")?.l()?;
+        let event = env.new_object(
+            LOGGING_EVENT_CLASS,
+            "(Ljava/lang/String;Lorg/apache/log4j/Category;Lorg/apache/log4j/Priority;Ljava/lang/Object;Ljava/lang/Throwable;)V",
+            &[
+                JValue::Object(env.new_string(CATEGORY_FQN)?.into()),
+                JValue::Object(logger),
+                JValue::Object(priority),
+                JValue::Object(env.new_string(VALIDATION_MESSAGE)?.into()),
+                JValue::Object(JObject::null()),
+            ],
+        )?;
+
+        env.call_method(layout, "format", "(Lorg/apache/log4j/spi/LoggingEvent;)Ljava/lang/String;", &[JValue::Object(event)])?;
+        Ok(())
+    });
+
+    match rendered {
+        Err(Error::Java(jni::errors::Error::JavaException)) => {
+            let reason = describe_exception(env)?;
+            Err(Error::InvalidPattern {
+                pattern: pattern.to_string(),
+                reason,
+            })
+        }
+        rendered => rendered,
+    }
+}
+
+/// Clear the pending exception, returning its `toString()`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+fn describe_exception(env: &JNIEnv) -> Result<String> {
+    let throwable = env.exception_occurred()?;
+    env.exception_clear()?;
+    sys::local_frame(env, || {
+        let description = env.call_method(throwable, "toString", "()Ljava/lang/String;", &[])?.l()?;
+        Ok(env.get_string(description.into())?.into())
+    })
+}
+
+/// Escape literal text for use in a conversion pattern
+fn escape(text: &str) -> String {
+    text.replace('%', "%%")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+
+    #[test]
+    fn builder() {
+        let pattern = Pattern::new().date("ISO8601").level().thread().message().build();
+        assert_eq!(pattern, "%d{ISO8601} %p %t %m%n");
+
+        let pattern = Pattern::new()
+            .separator(" | ")
+            .date("HH:mm:ss")
+            .level_padded()
+            .category()
+            .mdc("requestId")
+            .literal("100%")
+            .message()
+            .build();
+        assert_eq!(pattern, "%d{HH:mm:ss} | %-5p | %c | %X{requestId} | 100%% | %m%n");
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(Pattern::new().build(), "%n");
+    }
+
+    #[test]
+    fn syntax() {
+        assert!(check_syntax("%d{ISO8601} %-5p [%t] %c{2} %X{user} - %m%n").is_ok());
+        assert!(check_syntax("100%% {literal} braces").is_ok());
+        assert!(check_syntax(&Pattern::logcat()).is_ok());
+        assert_eq!(check_syntax("%d{HH:mm %m%n"), Err("unclosed '{' at offset 2".to_string()));
+        assert_eq!(check_syntax("%m%"), Err("dangling '%' at offset 2".to_string()));
+        assert_eq!(check_syntax("%-5"), Err("dangling '%' at offset 0".to_string()));
+        assert_eq!(check_syntax("%q %m"), Err("unknown conversion '%q' at offset 0".to_string()));
+    }
+
+    #[test]
+    fn validate() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        validate_pattern(&env, "%d{ISO8601} %-5p [%t] %c{2} %X{user} - %m%n").expect("Pattern should be valid");
+        validate_pattern(&env, &Pattern::logcat()).expect("Pattern should be valid");
+        match validate_pattern(&env, "%d{HH:mm %m%n") {
+            Err(Error::InvalidPattern { pattern, reason }) => {
+                assert_eq!(pattern, "%d{HH:mm %m%n");
+                assert_eq!(reason, "unclosed '{' at offset 2");
+            }
+            _ => panic!("Expected InvalidPattern"),
+        }
+        assert!(!env.exception_check().unwrap());
+    }
+}
@@ -0,0 +1,248 @@
+use crate::circuit::CircuitBreaker;
+use crate::clock::{Clock, SystemClock};
+use crate::error::Result;
+use crate::history::History;
+use crate::level::{LevelCounters, LogLevel};
+use crate::logger::{logging_paused, JavaLogger};
+use crate::mdc;
+use crate::stamp::{self, RustTimestamp, RUST_TIME_MDC_KEY};
+use crate::sys;
+use crate::transform::{self, MessageTransform};
+use jni::objects::{GlobalRef, JObject, JValue};
+use jni::{JNIEnv, JavaVM};
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+
+/// The MDC key under which the trace id configured with `with_trace_id` is stored
+const TRACE_ID_MDC_KEY: &str = "traceId";
+
+/// Generates the trace id put in the MDC on every log call
+pub(crate) type TraceIdGenerator = Arc<dyn Fn() -> String + Send + Sync>;
+
+/// The level and message of the last successful log call, see `JavaLogger::last_message`
+type LastMessage = Mutex<Option<(LogLevel, String)>>;
+
+/// Everything about a log call which doesn't involve the JVM: the message pipeline, the circuit breaker and the bookkeeping.
+/// A pipeline is never changed once in use, setters replace it as a whole with `InnerLogger::update_pipeline`,
+/// so it can be shared with threads logging on behalf of the logger, see `RemoteLogger`
+#[derive(Clone)]
+pub(crate) struct Pipeline {
+    /// Source of the current time, configured with `set_clock`
+    pub(crate) clock: Arc<dyn Clock>,
+    /// Number of successful log calls per level
+    pub(crate) counts: Arc<LevelCounters>,
+    /// The last successfully logged message, only tracked once enabled with `set_track_last_message`
+    pub(crate) last_message: Option<Arc<LastMessage>>,
+    /// Installed by `HistoryLogger::wrap`
+    pub(crate) history: Option<Arc<Mutex<History>>>,
+    /// Configured with `set_circuit_breaker`
+    pub(crate) circuit: Option<Arc<Mutex<CircuitBreaker>>>,
+    /// Configured with `with_trace_id`
+    pub(crate) trace_id: Option<TraceIdGenerator>,
+    /// Added with `push_transform`, run after the redactor
+    pub(crate) transforms: Vec<Arc<dyn MessageTransform>>,
+    /// Configured with `stamp_rust_time`
+    pub(crate) rust_time: Option<Arc<Mutex<RustTimestamp>>>,
+    /// Configured with `set_redactor`, the first stage of the pipeline
+    #[cfg(feature = "redact")]
+    pub(crate) redactor: Option<Arc<dyn MessageTransform>>,
+}
+
+impl Pipeline {
+    pub(crate) fn new() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            counts: Arc::new(LevelCounters::default()),
+            last_message: None,
+            history: None,
+            circuit: None,
+            trace_id: None,
+            transforms: Vec::new(),
+            rust_time: None,
+            #[cfg(feature = "redact")]
+            redactor: None,
+        }
+    }
+
+    /// Run `message` through the message pipeline: the redactor, then the transforms in the order they were added.
+    /// Finally the timestamp of `stamp_rust_time` is prefixed, if configured
+    pub(crate) fn transform<'m>(&self, level: LogLevel, message: &'m str) -> Cow<'m, str> {
+        let message = self.run_stages(level, message);
+        match &self.rust_time {
+            Some(rust_time) => stamp::prefixed(rust_time, self.clock.system_now(), message),
+            None => message,
+        }
+    }
+
+    /// Run `message` through the redactor and the transforms
+    fn run_stages<'m>(&self, level: LogLevel, message: &'m str) -> Cow<'m, str> {
+        #[cfg(feature = "redact")]
+        {
+            if let Some(redactor) = &self.redactor {
+                let message = redactor.apply(level, Cow::Borrowed(message));
+                return self.transforms.iter().fold(message, |message, stage| stage.apply(level, message));
+            }
+        }
+
+        transform::run_pipeline(&self.transforms, level, message)
+    }
+
+    /// Whether the message pipeline has any stages, or a timestamp is prefixed
+    pub(crate) fn has_transforms(&self) -> bool {
+        if let Some(rust_time) = &self.rust_time {
+            if !rust_time.lock().expect("Failed to lock timestamp formatter").in_mdc() {
+                return true;
+            }
+        }
+
+        #[cfg(feature = "redact")]
+        {
+            if self.redactor.is_some() {
+                return true;
+            }
+        }
+
+        !self.transforms.is_empty()
+    }
+
+    /// Run `f` with the generated trace id in the MDC, if a generator is configured,
+    /// and with the current time in the MDC if `stamp_rust_time` is configured to put it there
+    ///
+    /// # Params
+    /// - `env` The JNIEnv of the current thread, whose MDC is changed
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub(crate) fn traced<F: FnOnce() -> Result<()>>(&self, env: &JNIEnv, f: F) -> Result<()> {
+        let with_trace_id = || match &self.trace_id {
+            Some(generator) => mdc::with_value(env, TRACE_ID_MDC_KEY, &generator(), f),
+            None => f(),
+        };
+
+        match &self.rust_time {
+            Some(rust_time) => {
+                let mut rust_time = rust_time.lock().expect("Failed to lock timestamp formatter");
+                if rust_time.in_mdc() {
+                    let timestamp = rust_time.format(self.clock.system_now());
+                    mdc::with_value(env, RUST_TIME_MDC_KEY, timestamp, with_trace_id)
+                } else {
+                    drop(rust_time);
+                    with_trace_id()
+                }
+            }
+            None => with_trace_id(),
+        }
+    }
+
+    /// Deliver a message at `level` with `deliver`, and count and record it if delivery succeeds, see `settle`.
+    /// If the circuit breaker is open, the message created by `message` is sent to the breaker's fallback instead,
+    /// see `set_circuit_breaker`. Nothing happens while logging is paused, see `set_logging_paused`
+    ///
+    /// # Error
+    /// - If `deliver` fails
+    pub(crate) fn guarded<M: FnOnce() -> String, F: FnOnce() -> Result<()>>(&self, level: LogLevel, message: M, deliver: F) -> Result<()> {
+        if logging_paused() {
+            return Ok(());
+        }
+
+        if let Some(circuit) = &self.circuit {
+            let mut breaker = circuit.lock().expect("Failed to lock circuit breaker");
+            if !breaker.allow(self.clock.now()) {
+                breaker.divert(level, &message(), self.clock.system_now());
+                return Ok(());
+            }
+        }
+
+        let result = deliver();
+        self.settle(level, message, result.is_ok());
+        result
+    }
+
+    /// Record the outcome of a delivery with the circuit breaker. A delivered message is counted,
+    /// and the message created by `message` is recorded if `last_message` or `HistoryLogger` need it.
+    /// Otherwise `message` is not called, so a delivered message costs no allocation
+    pub(crate) fn settle<M: FnOnce() -> String>(&self, level: LogLevel, message: M, delivered: bool) {
+        if let Some(circuit) = &self.circuit {
+            circuit.lock().expect("Failed to lock circuit breaker").record(delivered, self.clock.now());
+        }
+
+        if !delivered {
+            return;
+        }
+
+        self.counts.record(level);
+        if self.history.is_none() && self.last_message.is_none() {
+            return;
+        }
+
+        let message = message();
+        if let Some(history) = &self.history {
+            history.lock().expect("Failed to lock history").record(self.clock.system_now(), level, message.clone());
+        }
+        if let Some(last_message) = &self.last_message {
+            *last_message.lock().expect("Failed to lock last message") = Some((level, message));
+        }
+    }
+}
+
+/// Logs through a JavaLogger from another thread attached to the JVM, e.g. a background thread started by `start_heartbeat`.
+/// Every message goes through the logger's current pipeline, like a message logged with `JavaLogger::log`
+pub(crate) struct RemoteLogger {
+    vm: JavaVM,
+    logger: GlobalRef,
+    /// The pipeline of the logger, replaced by `InnerLogger::update_pipeline`
+    pipeline: Arc<Mutex<Arc<Pipeline>>>,
+}
+
+impl RemoteLogger {
+    /// The JavaVM of the logger
+    pub(crate) fn vm(&self) -> &JavaVM {
+        &self.vm
+    }
+
+    /// The current pipeline of the logger
+    pub(crate) fn pipeline(&self) -> Arc<Pipeline> {
+        self.pipeline.lock().expect("Failed to lock published pipeline").clone()
+    }
+
+    /// Log `message` at `level` using `Category#log(Priority, Object)`
+    ///
+    /// # Params
+    /// - `env` The JNIEnv of the current thread
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub(crate) fn log(&self, env: &JNIEnv, level: LogLevel, message: &str) -> Result<()> {
+        let pipeline = self.pipeline();
+        let message = pipeline.transform(level, message);
+        pipeline.guarded(level.resolved(), || message.to_string(), || {
+            sys::local_frame(env, || pipeline.traced(env, || log_priority(env, self.logger.as_obj(), level, &message)))
+        })
+    }
+}
+
+impl<'a> JavaLogger<'a> {
+    /// A handle for logging through this logger from other threads
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub(crate) fn remote(&self) -> Result<RemoteLogger> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        Ok(RemoteLogger {
+            vm: inner.env.get_java_vm()?,
+            logger: inner.env.new_global_ref(inner.logger)?,
+            pipeline: inner.published.clone(),
+        })
+    }
+}
+
+/// Log `message` to `logger` using `Category#log(Priority, Object)`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn log_priority(env: &JNIEnv, logger: JObject, level: LogLevel, message: &str) -> Result<()> {
+    let priority = level.to_java_level(env)?;
+    let message = JavaLogger::jstring(env, message)?;
+    env.call_method(logger, "log", "(Lorg/apache/log4j/Priority;Ljava/lang/Object;)V", &[JValue::Object(priority), message])?;
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use crate::level::LogLevel;
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Width level names are padded to, the length of the longest standard name
+const LEVEL_WIDTH: usize = 5;
+/// Length of the cached `YYYY-MM-DD HH:MM:SS` part of a timestamp
+const TIMESTAMP_SECONDS_LEN: usize = 19;
+
+/// A log event formatted without log4j, by `plain_format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlainRecord<'r> {
+    pub level: LogLevel,
+    /// The name of the logger
+    pub logger: &'r str,
+    pub message: &'r str,
+    /// The time of the event, omitted from the output if `None`
+    pub timestamp: Option<SystemTime>,
+}
+
+/// Format a record the way this crate formats lines it writes without log4j:
+/// `{timestamp} {level} {logger} - {message}`, with the timestamp as `YYYY-MM-DD HH:MM:SS.mmm` in UTC
+/// and the level name padded to 5 characters. Custom levels are shown by their integer value.
+/// The line is not terminated
+pub fn plain_format(record: &PlainRecord) -> String {
+    let mut line = String::with_capacity(TIMESTAMP_SECONDS_LEN + record.logger.len() + record.message.len() + 16);
+    LineFormatter::default().format(record, &mut line);
+    line
+}
+
+/// Formats records as described by `plain_format` into a caller-provided buffer, without intermediate allocations.
+/// The formatted seconds of the last timestamp are cached, so consecutive records within the same second only format milliseconds
+#[derive(Debug, Default)]
+pub(crate) struct LineFormatter {
+    /// The second since the Unix epoch `cached_seconds` was formatted for
+    cached_second: Option<u64>,
+    cached_seconds: String,
+}
+
+impl LineFormatter {
+    /// Append the formatted record to `out`
+    pub(crate) fn format(&mut self, record: &PlainRecord, out: &mut String) {
+        if let Some(timestamp) = record.timestamp {
+            // Times before the Unix epoch are clamped to it
+            let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+            let second = since_epoch.as_secs();
+            if self.cached_second != Some(second) {
+                self.cached_seconds.clear();
+                write_seconds(second, ' ', &mut self.cached_seconds);
+                self.cached_second = Some(second);
+            }
+
+            out.push_str(&self.cached_seconds);
+            let _ = write!(out, ".{:03} ", since_epoch.subsec_millis());
+        }
+
+        let start = out.len();
+        match record.level {
+            LogLevel::Error => out.push_str("ERROR"),
+            LogLevel::Warn => out.push_str("WARN"),
+            LogLevel::Info => out.push_str("INFO"),
+            LogLevel::Debug => out.push_str("DEBUG"),
+            LogLevel::Custom(value) => out.push_str(itoa::Buffer::new().format(value)),
+        }
+        for _ in out.len() - start..LEVEL_WIDTH {
+            out.push(' ');
+        }
+
+        out.push(' ');
+        out.push_str(record.logger);
+        out.push_str(" - ");
+        out.push_str(record.message);
+    }
+}
+
+/// Write `YYYY-MM-DD{separator}HH:MM:SS` in UTC for a number of seconds since the Unix epoch
+pub(crate) fn write_seconds(seconds: u64, separator: char, out: &mut String) {
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let time_of_day = seconds % 86_400;
+    let _ = write!(out, "{:04}-{:02}-{:02}{}{:02}:{:02}:{:02}", year, month, day, separator, time_of_day / 3600, time_of_day / 60 % 60, time_of_day % 60);
+}
+
+/// Convert days since the Unix epoch to a proleptic Gregorian `(year, month, day)`, using Howard Hinnant's `civil_from_days`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn record(level: LogLevel, timestamp: Option<SystemTime>) -> PlainRecord<'static> {
+        PlainRecord {
+            level,
+            logger: "com.example.Plain",
+            message: "Message",
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn format() {
+        let timestamp = UNIX_EPOCH + Duration::from_millis(1_792_157_820_123);
+        assert_eq!(plain_format(&record(LogLevel::Info, Some(timestamp))), "2026-10-16 13:37:00.123 INFO  com.example.Plain - Message");
+        assert_eq!(plain_format(&record(LogLevel::Error, None)), "ERROR com.example.Plain - Message");
+        assert_eq!(plain_format(&record(LogLevel::Warn, None)), "WARN  com.example.Plain - Message");
+        assert_eq!(plain_format(&record(LogLevel::Debug, None)), "DEBUG com.example.Plain - Message");
+        assert_eq!(plain_format(&record(LogLevel::Custom(50000), None)), "50000 com.example.Plain - Message");
+        assert_eq!(plain_format(&record(LogLevel::Custom(7), None)), "7     com.example.Plain - Message");
+    }
+
+    #[test]
+    fn cached_seconds() {
+        let mut formatter = LineFormatter::default();
+        let mut out = String::new();
+        let second = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        for (offset, expected) in [(5, "2000-02-29 00:00:00.005"), (999, "2000-02-29 00:00:00.999"), (1000, "2000-02-29 00:00:01.000")] {
+            out.clear();
+            formatter.format(&record(LogLevel::Info, Some(second + Duration::from_millis(offset))), &mut out);
+            assert!(out.starts_with(expected), "{}", out);
+        }
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(951_782_400 / 86_400), (2000, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(2_932_896), (9999, 12, 31));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::error::{Error, Result};
+use crate::logger::JavaLogger;
+use crate::sys;
+use jni::objects::JObject;
+use jni::JNIEnv;
+
+/// Classes which must be resolvable for this crate to work
+const REQUIRED_CLASSES: [&str; 5] = [
+    "org/apache/log4j/LogManager",
+    "org/apache/log4j/Category",
+    "org/apache/log4j/Logger",
+    "org/apache/log4j/Level",
+    "org/apache/log4j/PatternLayout",
+];
+const LOG_MANAGER_CLASS: &str = "org/apache/log4j/LogManager";
+/// Integer value of `Level.OFF_INT`
+const OFF_INT: i32 = i32::MAX;
+
+/// The severity of a preflight finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Informational, logging works
+    Info,
+    /// Logging works, but likely not as intended
+    Warning,
+    /// Nothing will be logged
+    Error,
+}
+
+/// A single finding of a preflight check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// How severe the finding is
+    pub severity: Severity,
+    /// What kind of problem was found
+    pub kind: FindingKind,
+    /// Human readable description
+    pub message: String,
+}
+
+/// The kind of problem found by a preflight check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    /// A required log4j class could not be resolved
+    MissingClass,
+    /// No appender is reachable from the checked logger, so log4j prints its "please initialize" warning and drops events
+    NoAppenders,
+    /// The repository threshold filters out some or all events
+    Threshold,
+}
+
+/// The result of `preflight`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreflightReport {
+    /// All findings, in the order they were found
+    pub findings: Vec<Finding>,
+}
+
+impl PreflightReport {
+    /// Whether there are no findings of severity `Error`
+    pub fn is_ok(&self) -> bool {
+        self.findings.iter().all(|finding| finding.severity < Severity::Error)
+    }
+
+    /// Whether at least one appender is reachable
+    pub fn has_appenders(&self) -> bool {
+        !self.findings.iter().any(|finding| matches!(finding.kind, FindingKind::NoAppenders | FindingKind::MissingClass))
+    }
+
+    /// Turn a missing appender into a hard error, for applications that must not run without logging
+    ///
+    /// # Error
+    /// - If there are findings of severity `Error`, or no appender is reachable
+    pub fn ensure_configured(&self) -> Result<()> {
+        match self.findings.iter().find(|finding| finding.severity == Severity::Error || finding.kind == FindingKind::NoAppenders) {
+            Some(finding) => Err(Error::Unconfigured(finding.message.clone())),
+            None => Ok(()),
+        }
+    }
+
+    fn push<S: Into<String>>(&mut self, severity: Severity, kind: FindingKind, message: S) {
+        self.findings.push(Finding {
+            severity,
+            kind,
+            message: message.into(),
+        });
+    }
+}
+
+/// Check whether log4j is usable and configured, using the root logger.
+/// This detects the classic case of log4j having no appenders, where its warning goes to stderr and every event is dropped
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub fn preflight(env: &JNIEnv) -> Result<PreflightReport> {
+    sys::local_frame(env, || preflight_logger(env, None))
+}
+
+impl<'a> JavaLogger<'a> {
+    /// Check whether log4j is usable and configured for this logger, taking the logger's hierarchy and additivity into account
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn preflight(&self) -> Result<PreflightReport> {
+        let inner = self.inner.lock().expect("Failed to lock inner logger");
+        sys::local_frame(inner.env, || preflight_logger(inner.env, Some(inner.logger)))
+    }
+}
+
+/// Run the preflight checks for `logger`, or the root logger if `None`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+fn preflight_logger(env: &JNIEnv, logger: Option<JObject>) -> Result<PreflightReport> {
+    let mut report = PreflightReport::default();
+
+    for class in REQUIRED_CLASSES.iter() {
+        if env.find_class(*class).is_err() {
+            env.exception_clear()?;
+            report.push(Severity::Error, FindingKind::MissingClass, format!("Class {} could not be found, is log4j on the classpath?", class.replace('/', ".")));
+        }
+    }
+
+    if !report.findings.is_empty() {
+        return Ok(report);
+    }
+
+    let repository = env.call_static_method(LOG_MANAGER_CLASS, "getLoggerRepository", "()Lorg/apache/log4j/spi/LoggerRepository;", &[])?.l()?;
+    let threshold = env.call_method(repository, "getThreshold", "()Lorg/apache/log4j/Level;", &[])?.l()?;
+    let threshold_int = env.call_method(threshold, "toInt", "()I", &[])?.i()?;
+    if threshold_int == OFF_INT {
+        report.push(Severity::Error, FindingKind::Threshold, "The repository threshold is OFF, nothing will be logged");
+    } else if threshold_int > i32::MIN {
+        let name = env.call_method(threshold, "toString", "()Ljava/lang/String;", &[])?.l()?;
+        let name: String = env.get_string(name.into())?.into();
+        report.push(Severity::Info, FindingKind::Threshold, format!("The repository threshold is {}, events below it are dropped", name));
+    }
+
+    let mut category = match logger {
+        Some(logger) => logger,
+        None => env.call_static_method(LOG_MANAGER_CLASS, "getRootLogger", "()Lorg/apache/log4j/Logger;", &[])?.l()?,
+    };
+
+    let mut has_appenders = false;
+    while !category.is_null() {
+        let appenders = env.call_method(category, "getAllAppenders", "()Ljava/util/Enumeration;", &[])?.l()?;
+        if env.call_method(appenders, "hasMoreElements", "()Z", &[])?.z()? {
+            has_appenders = true;
+            break;
+        }
+
+        if !env.call_method(category, "getAdditivity", "()Z", &[])?.z()? {
+            break;
+        }
+
+        category = env.call_method(category, "getParent", "()Lorg/apache/log4j/Category;", &[])?.l()?;
+    }
+
+    if !has_appenders {
+        report.push(Severity::Warning, FindingKind::NoAppenders, "No appenders could be found, log4j is not initialized properly and will drop all events");
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support::ConfigSnapshot;
+
+    const BASIC_CONFIGURATOR_CLASS: &str = "org/apache/log4j/BasicConfigurator";
+
+    #[test]
+    fn before_and_after_configure() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let snapshot = ConfigSnapshot::capture(&env).expect("Failed to capture configuration");
+        env.call_static_method(BASIC_CONFIGURATOR_CLASS, "resetConfiguration", "()V", &[]).expect("Failed to reset configuration");
+
+        let logger = JavaLogger::new(&env, "com.example.Preflight").expect("Failed to create JavaLogger");
+        let report = logger.preflight().expect("Failed to run preflight");
+        assert!(report.is_ok());
+        assert!(!report.has_appenders());
+        assert!(matches!(report.ensure_configured(), Err(Error::Unconfigured(_))));
+
+        env.call_static_method(BASIC_CONFIGURATOR_CLASS, "configure", "()V", &[]).expect("Failed to configure log4j");
+
+        let report = preflight(&env).expect("Failed to run preflight");
+        assert_eq!(report, PreflightReport::default());
+        logger.preflight().expect("Failed to run preflight").ensure_configured().expect("Logger should be configured");
+
+        snapshot.restore().expect("Failed to restore configuration");
+        assert!(!logger.preflight().expect("Failed to run preflight").has_appenders());
+    }
+}
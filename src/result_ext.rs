@@ -0,0 +1,127 @@
+use crate::level::LogLevel;
+use crate::logger::JavaLogger;
+use std::fmt::Display;
+
+/// Log the outcome of a `Result` and pass it on unchanged, e.g. `fetch().log_err(&logger, "Fetching failed")?`.
+/// Messages are only formatted if the logger is enabled for their level.
+/// Failures to log are ignored, the value is returned either way
+pub trait LogResultExt<T, E>: Sized {
+    /// Log an error at ERROR, formatted as `{message}: {error}`
+    fn log_err(self, logger: &JavaLogger, message: &str) -> Self;
+
+    /// Log success at `ok_level`, formatted as `{label} succeeded`, and an error at `err_level`, formatted as `{label} failed: {error}`
+    fn log_both(self, logger: &JavaLogger, ok_level: LogLevel, err_level: LogLevel, label: &str) -> Self;
+
+    /// Log an error at ERROR, formatted as `{message}: {error}`, and replace it with the default value
+    fn log_err_and_default(self, logger: &JavaLogger, message: &str) -> T
+    where
+        T: Default;
+}
+
+/// Log the absence of a value and pass the `Option` on unchanged
+pub trait LogOptionExt: Sized {
+    /// Log `message` at WARN if the value is `None`. Failures to log are ignored
+    fn log_none(self, logger: &JavaLogger, message: &str) -> Self;
+}
+
+/// Log the message created by `message` at `level`, if the logger is enabled for it
+fn log_lazy<F: FnOnce() -> String>(logger: &JavaLogger, level: LogLevel, message: F) {
+    if let Ok(true) = logger.is_enabled_for(level) {
+        let _ = logger.log(level, message());
+    }
+}
+
+impl<T, E: Display> LogResultExt<T, E> for Result<T, E> {
+    fn log_err(self, logger: &JavaLogger, message: &str) -> Self {
+        if let Err(e) = &self {
+            log_lazy(logger, LogLevel::Error, || format!("{}: {}", message, e));
+        }
+
+        self
+    }
+
+    fn log_both(self, logger: &JavaLogger, ok_level: LogLevel, err_level: LogLevel, label: &str) -> Self {
+        match &self {
+            Ok(_) => log_lazy(logger, ok_level, || format!("{} succeeded", label)),
+            Err(e) => log_lazy(logger, err_level, || format!("{} failed: {}", label, e)),
+        }
+
+        self
+    }
+
+    fn log_err_and_default(self, logger: &JavaLogger, message: &str) -> T
+    where
+        T: Default,
+    {
+        self.log_err(logger, message).unwrap_or_default()
+    }
+}
+
+impl<T> LogOptionExt for Option<T> {
+    fn log_none(self, logger: &JavaLogger, message: &str) -> Self {
+        if self.is_none() {
+            log_lazy(logger, LogLevel::Warn, || message.to_string());
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support;
+    use std::cell::Cell;
+    use std::fmt;
+
+    /// An error counting how often it is formatted
+    struct CountingError<'c>(&'c Cell<u32>);
+
+    impl fmt::Display for CountingError<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.set(self.0.get() + 1);
+            f.write_str("connection refused")
+        }
+    }
+
+    #[test]
+    fn result_and_option() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("ResultExt").expect("Failed to create capturing logger");
+        scope.set_level(&logger, LogLevel::Info).expect("Failed to set level");
+
+        let formatted = Cell::new(0);
+        let ok: Result<u32, CountingError> = Ok(7);
+        assert_eq!(ok.log_err(&logger, "Fetching failed").ok(), Some(7));
+        let err: Result<u32, CountingError> = Err(CountingError(&formatted));
+        assert!(err.log_err(&logger, "Fetching failed").is_err());
+
+        let ok: Result<u32, CountingError> = Ok(7);
+        assert_eq!(ok.log_both(&logger, LogLevel::Debug, LogLevel::Error, "Fetch").ok(), Some(7));
+        let ok: Result<u32, CountingError> = Ok(7);
+        assert_eq!(ok.log_both(&logger, LogLevel::Info, LogLevel::Error, "Fetch").ok(), Some(7));
+        // WARN is enabled, DEBUG isn't, so the error is only formatted once more
+        let err: Result<u32, CountingError> = Err(CountingError(&formatted));
+        assert!(err.log_both(&logger, LogLevel::Info, LogLevel::Warn, "Fetch").is_err());
+        let err: Result<u32, CountingError> = Err(CountingError(&formatted));
+        assert!(err.log_both(&logger, LogLevel::Info, LogLevel::Debug, "Fetch").is_err());
+        assert_eq!(formatted.get(), 2);
+
+        let err: Result<u32, CountingError> = Err(CountingError(&formatted));
+        assert_eq!(err.log_err_and_default(&logger, "Reading the count failed"), 0);
+
+        assert_eq!(Some(1).log_none(&logger, "No user"), Some(1));
+        assert_eq!(None::<u32>.log_none(&logger, "No user"), None);
+
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec![
+            "ERROR Fetching failed: connection refused",
+            "INFO Fetch succeeded",
+            "WARN Fetch failed: connection refused",
+            "ERROR Reading the count failed: connection refused",
+            "WARN No user",
+        ]);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::error::{AlreadySet, Error, Result};
+use crate::sys;
+use jni::objects::JValue;
+use jni::sys::JNI_VERSION_1_6;
+use jni::{JNIEnv, JavaVM};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+const SYSTEM_CLASS: &str = "java/lang/System";
+const LOG_MANAGER_CLASS: &str = "org/apache/log4j/LogManager";
+
+/// The oldest JNI version supported by this crate
+const MIN_JNI_VERSION: i32 = JNI_VERSION_1_6;
+/// The oldest Java major version supported by this crate
+const MIN_JAVA_VERSION: u32 = 8;
+
+/// The process-wide JavaVM, set with `set_global_vm`
+static GLOBAL_VM: OnceLock<Arc<JavaVM>> = OnceLock::new();
+
+/// Set once `ensure_supported` succeeded, there is only one JVM per process
+static VERSIONS_SUPPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Information about the Java runtime the crate is running in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeInfo {
+    /// The value of the `java.vendor` system property
+    pub vendor: String,
+    /// The value of the `java.version` system property
+    pub version: String,
+    /// The value of the `java.vm.name` system property
+    pub vm_name: String,
+}
+
+/// Get information about the Java runtime, useful for diagnostics dumps
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub fn java_runtime_info(env: &JNIEnv) -> Result<RuntimeInfo> {
+    Ok(RuntimeInfo {
+        vendor: system_property(env, "java.vendor")?.unwrap_or_default(),
+        version: system_property(env, "java.version")?.unwrap_or_default(),
+        vm_name: system_property(env, "java.vm.name")?.unwrap_or_default(),
+    })
+}
+
+/// Set the process-wide JavaVM, for code which has no JNIEnv at hand to attach a thread with.
+/// The VM can only be set once. JNI libraries should set it when they are loaded:
+///
+/// ```no_run
+/// # use jni::JavaVM;
+/// # use jni::sys::{jint, JNI_VERSION_1_6};
+/// # use std::os::raw::c_void;
+/// # use std::sync::Arc;
+/// #[no_mangle]
+/// pub extern "system" fn JNI_OnLoad(vm: JavaVM, _reserved: *mut c_void) -> jint {
+///     let _ = log4j::set_global_vm(Arc::new(vm));
+///     JNI_VERSION_1_6
+/// }
+/// ```
+///
+/// # Error
+/// - If a global JavaVM has already been set
+pub fn set_global_vm(vm: Arc<JavaVM>) -> std::result::Result<(), AlreadySet> {
+    GLOBAL_VM.set(vm).map_err(|_| AlreadySet)
+}
+
+/// Get the process-wide JavaVM, if it has been set with `set_global_vm`
+pub fn global_vm() -> Option<Arc<JavaVM>> {
+    GLOBAL_VM.get().cloned()
+}
+
+/// Flush and close all appenders using `LogManager#shutdown()`, then destroy the JVM.
+///
+/// This must be the last JNI operation of the process: the JVM cannot be used, nor created again, afterwards.
+/// The calling thread must not be permanently attached to the JVM, and every other thread must have detached,
+/// as `DestroyJavaVM` waits until the calling thread is the last non-daemon thread
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub fn shutdown_jvm(vm: JavaVM) -> Result<()> {
+    {
+        let env = vm.attach_current_thread()?;
+        sys::local_frame(&env, || {
+            env.call_static_method(LOG_MANAGER_CLASS, "shutdown", "()V", &[])?;
+            Ok(())
+        })?;
+    }
+
+    let vm_ptr = vm.get_java_vm_pointer();
+    // SAFETY: vm_ptr is a valid JavaVM pointer, and the JavaVM is consumed so it can't be used afterwards
+    let code = unsafe {
+        let destroy = (**vm_ptr).DestroyJavaVM.ok_or(jni::errors::Error::JavaVMMethodNotFound("DestroyJavaVM"))?;
+        destroy(vm_ptr)
+    };
+
+    jni::errors::jni_error_code_to_result(code)?;
+    Ok(())
+}
+
+/// Verify that the JNI and Java versions are new enough to be used by this crate.
+/// The versions are only queried until the check succeeds once
+///
+/// # Error
+/// - If the JNI version or the Java version is older than supported
+/// - If one of the underlying JNI calls fail
+pub(crate) fn ensure_supported(env: &JNIEnv) -> Result<()> {
+    if VERSIONS_SUPPORTED.load(Ordering::Acquire) {
+        return Ok(());
+    }
+
+    let jni_version: i32 = env.get_version()?.into();
+    let java_version = system_property(env, "java.version")?.unwrap_or_default();
+    check_versions(jni_version, &java_version)?;
+    VERSIONS_SUPPORTED.store(true, Ordering::Release);
+    Ok(())
+}
+
+/// Compare the JNI version and the `java.version` string against the supported floor
+///
+/// # Error
+/// - If either version is below the supported floor, or the Java version cannot be parsed
+fn check_versions(jni_version: i32, java_version: &str) -> Result<()> {
+    if jni_version < MIN_JNI_VERSION {
+        return Err(Error::UnsupportedJavaVersion {
+            found: format!("JNI {}", format_jni_version(jni_version)),
+            required: format!("JNI {}", format_jni_version(MIN_JNI_VERSION)),
+        });
+    }
+
+    match java_major_version(java_version) {
+        Some(major) if major >= MIN_JAVA_VERSION => Ok(()),
+        _ => Err(Error::UnsupportedJavaVersion {
+            found: format!("Java {}", java_version),
+            required: format!("Java {}", MIN_JAVA_VERSION),
+        }),
+    }
+}
+
+/// Format a JNI version constant, e.g. `0x00010006` becomes `1.6`
+fn format_jni_version(version: i32) -> String {
+    format!("{}.{}", version >> 16, version & 0xFFFF)
+}
+
+/// Get the major version from a `java.version` string.
+/// Both the legacy (`1.8.0_292`) and the modern (`11.0.2`, `17`, `9-ea`) formats are accepted
+fn java_major_version(version: &str) -> Option<u32> {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    let first: u32 = parts.next()?.parse().ok()?;
+    if first == 1 {
+        parts.next()?.parse().ok()
+    } else {
+        Some(first)
+    }
+}
+
+/// Read a system property using `System#getProperty(String)`
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn system_property(env: &JNIEnv, key: &str) -> Result<Option<String>> {
+    sys::local_frame(env, || {
+        let key = env.new_string(key)?;
+        let value = env.call_static_method(SYSTEM_CLASS, "getProperty", "(Ljava/lang/String;)Ljava/lang/String;", &[JValue::Object(key.into())])?.l()?;
+        if value.is_null() {
+            return Ok(None);
+        }
+
+        Ok(Some(env.get_string(value.into())?.into()))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+
+    #[test]
+    fn runtime_info() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        let info = java_runtime_info(&env).expect("Failed to get runtime info");
+        assert!(!info.vendor.is_empty());
+        assert!(!info.vm_name.is_empty());
+        assert!(java_major_version(&info.version).expect("Failed to parse java.version") >= MIN_JAVA_VERSION);
+        ensure_supported(&env).expect("Harness JVM should be supported");
+    }
+
+    #[test]
+    fn global_vm() {
+        // The global VM is process-wide, this is the only test setting it
+        let vm = JVM.lock().expect("Failed to lock JVM").clone();
+        assert!(super::global_vm().is_none());
+
+        set_global_vm(vm.clone()).expect("Failed to set global VM");
+        assert!(Arc::ptr_eq(&super::global_vm().expect("Global VM is not set"), &vm));
+        assert_eq!(set_global_vm(vm), Err(AlreadySet));
+    }
+
+    /// Set in the child process running `shutdown_flushes`, contains the path of the log file
+    const SHUTDOWN_CHILD_ENV: &str = "LOG4J_RS_SHUTDOWN_CHILD";
+
+    #[test]
+    fn shutdown_flushes() {
+        let path = match std::env::var(SHUTDOWN_CHILD_ENV) {
+            Ok(path) => path,
+            Err(_) => {
+                // A destroyed JVM cannot be created again, so shut down a JVM of our own in a separate process
+                let tmpdir = tempfile::tempdir().expect("Failed to create temporary directory");
+                let path = tmpdir.path().join("shutdown.log");
+                let status = std::process::Command::new(std::env::current_exe().expect("Failed to get test executable"))
+                    .args(["--exact", "runtime::test::shutdown_flushes"])
+                    .env(SHUTDOWN_CHILD_ENV, &path)
+                    .status()
+                    .expect("Failed to run child process");
+                assert!(status.success());
+
+                let content = std::fs::read_to_string(&path).expect("Failed to read log file");
+                assert_eq!(content.lines().count(), 1000);
+                assert_eq!(content.lines().last(), Some("Line 999"));
+                return;
+            }
+        };
+
+        let vm = crate::test_support::TestJvm::create();
+        {
+            let env = vm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+            let logger = crate::JavaLogger::new(&env, "com.example.Shutdown").expect("Failed to create JavaLogger");
+
+            // Buffered, so the file is only complete once the appender is closed
+            let layout = env.new_object("org/apache/log4j/PatternLayout", "(Ljava/lang/String;)V", &[JValue::Object(env.new_string("%m%n").unwrap().into())]).unwrap();
+            let appender = env
+                .new_object(
+                    "org/apache/log4j/FileAppender",
+                    "(Lorg/apache/log4j/Layout;Ljava/lang/String;ZZI)V",
+                    &[JValue::Object(layout), JValue::Object(env.new_string(&path).unwrap().into()), JValue::Bool(0), JValue::Bool(1), JValue::Int(64 * 1024)],
+                )
+                .expect("Failed to create FileAppender");
+            logger.reconfigure(|ctx| ctx.add_appender(appender)).expect("Failed to add FileAppender");
+
+            for i in 0..1000 {
+                logger.log(crate::LogLevel::Info, format!("Line {}", i)).expect("Failed to log to INFO level");
+            }
+        }
+
+        shutdown_jvm(vm).expect("Failed to shut down JVM");
+    }
+
+    #[test]
+    fn major_version() {
+        assert_eq!(java_major_version("1.6.0_45"), Some(6));
+        assert_eq!(java_major_version("1.8.0_292"), Some(8));
+        assert_eq!(java_major_version("11.0.2"), Some(11));
+        assert_eq!(java_major_version("17"), Some(17));
+        assert_eq!(java_major_version("9-ea"), Some(9));
+        assert_eq!(java_major_version(""), None);
+    }
+
+    #[test]
+    fn version_floor() {
+        assert!(check_versions(JNI_VERSION_1_6, "1.8.0_292").is_ok());
+        assert!(check_versions(0x000a0000, "17.0.1").is_ok());
+
+        match check_versions(0x00010004, "1.8.0_292") {
+            Err(Error::UnsupportedJavaVersion { found, required }) => {
+                assert_eq!(found, "JNI 1.4");
+                assert_eq!(required, "JNI 1.6");
+            }
+            _ => panic!("Expected UnsupportedJavaVersion"),
+        }
+
+        match check_versions(JNI_VERSION_1_6, "1.7.0_80") {
+            Err(Error::UnsupportedJavaVersion { found, required }) => {
+                assert_eq!(found, "Java 1.7.0_80");
+                assert_eq!(required, "Java 8");
+            }
+            _ => panic!("Expected UnsupportedJavaVersion"),
+        }
+    }
+}
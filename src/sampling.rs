@@ -0,0 +1,314 @@
+use crate::error::Result;
+use crate::level::LogLevel;
+use crate::logger::JavaLogger;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How a `SamplingLogger` decides which messages to log
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Log one in every N messages, starting with the first. 0 and 1 log every message
+    OneInN(u32),
+    /// Log every message with the given probability, between 0.0 and 1.0
+    Probability(f64),
+    /// Log at most `rate` messages per second on average, allowing bursts of up to `burst` messages
+    TokenBucket { rate: f64, burst: u32 },
+}
+
+/// Counts of a `SamplingLogger`, see `SamplingLogger::stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SamplingStats {
+    /// Messages handed to the wrapped logger
+    pub logged: u64,
+    /// Messages dropped by sampling
+    pub sampled_out: u64,
+}
+
+impl SamplingStats {
+    /// The fraction of messages which was logged, 1.0 if there were none
+    pub fn ratio(&self) -> f64 {
+        match self.logged + self.sampled_out {
+            0 => 1.0,
+            total => self.logged as f64 / total as f64,
+        }
+    }
+}
+
+/// Wrapper around a JavaLogger which only logs a sample of the messages, for high-volume paths.
+/// Every level is sampled separately, with the default strategy unless configured otherwise with `level_strategy`.
+/// ERROR messages are never sampled by default.
+/// Time is taken from the wrapped logger's clock, see `JavaLogger::set_clock`
+pub struct SamplingLogger<'a> {
+    logger: JavaLogger<'a>,
+    strategy: Strategy,
+    /// Per level strategies, `None` logs every message
+    level_strategies: HashMap<LogLevel, Option<Strategy>>,
+    summary_interval: Option<Duration>,
+    state: Mutex<State>,
+}
+
+/// The mutable state of a `SamplingLogger`
+struct State {
+    samplers: HashMap<LogLevel, Sampler>,
+    rng: SplitMix64,
+    stats: SamplingStats,
+    /// Counts since the last summary, and when it was logged
+    interval_stats: SamplingStats,
+    last_summary: Option<Instant>,
+}
+
+impl<'a> SamplingLogger<'a> {
+    /// Wrap a logger, sampling all levels but ERROR with `strategy`
+    pub fn new(logger: &JavaLogger<'a>, strategy: Strategy) -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+        let mut level_strategies = HashMap::new();
+        level_strategies.insert(LogLevel::Error, None);
+
+        Self {
+            logger: logger.clone(),
+            strategy,
+            level_strategies,
+            summary_interval: None,
+            state: Mutex::new(State {
+                samplers: HashMap::new(),
+                rng: SplitMix64(seed),
+                stats: SamplingStats::default(),
+                interval_stats: SamplingStats::default(),
+                last_summary: None,
+            }),
+        }
+    }
+
+    /// Use a different strategy for `level`, or log every message of `level` if `None`.
+    /// Use `Some` for `LogLevel::Error` to sample errors too
+    pub fn level_strategy(mut self, level: LogLevel, strategy: Option<Strategy>) -> Self {
+        self.level_strategies.insert(level, strategy);
+        self
+    }
+
+    /// Seed the random number generator used by `Strategy::Probability`, making the sampled messages reproducible.
+    /// Defaults to a seed derived from the current time
+    pub fn seed(self, seed: u64) -> Self {
+        self.state.lock().expect("Failed to lock sampling state").rng = SplitMix64(seed);
+        self
+    }
+
+    /// Log a summary at INFO at most once every `interval`, formatted as
+    /// `Sampling logged {logged} of {total} messages ({percentage}%)`, covering the messages since the previous summary.
+    /// The summary is logged by the first call to `log` after the interval has passed
+    pub fn summary_interval(mut self, interval: Duration) -> Self {
+        self.summary_interval = Some(interval);
+        self
+    }
+
+    /// The number of logged and sampled out messages so far
+    pub fn stats(&self) -> SamplingStats {
+        self.state.lock().expect("Failed to lock sampling state").stats
+    }
+
+    /// Log a message if it is sampled. Returns whether the message was logged
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log<S: AsRef<str>>(&self, level: LogLevel, message: S) -> Result<bool> {
+        let now = self.logger.now();
+        let (sampled, summary) = {
+            let mut state = self.state.lock().expect("Failed to lock sampling state");
+            let strategy = self.level_strategies.get(&level).copied().unwrap_or(Some(self.strategy));
+            let State {
+                samplers,
+                rng,
+                stats,
+                interval_stats,
+                ..
+            } = &mut *state;
+            let sampled = match strategy {
+                Some(strategy) => samplers.entry(level).or_insert_with(|| Sampler::new(strategy, now)).sample(now, rng),
+                None => true,
+            };
+
+            for stats in [stats, interval_stats] {
+                if sampled {
+                    stats.logged += 1;
+                } else {
+                    stats.sampled_out += 1;
+                }
+            }
+
+            (sampled, self.take_summary(&mut state, now))
+        };
+
+        if sampled {
+            self.logger.log(level, message)?;
+        }
+
+        if let Some(summary) = summary {
+            self.logger.log(LogLevel::Info, format!("Sampling logged {} of {} messages ({:.1}%)", summary.logged, summary.logged + summary.sampled_out, summary.ratio() * 100.0))?;
+        }
+
+        Ok(sampled)
+    }
+
+    /// The counts for a summary if one is due, resetting them
+    fn take_summary(&self, state: &mut State, now: Instant) -> Option<SamplingStats> {
+        let interval = self.summary_interval?;
+        let last_summary = *state.last_summary.get_or_insert(now);
+        if now.saturating_duration_since(last_summary) < interval {
+            return None;
+        }
+
+        state.last_summary = Some(now);
+        Some(std::mem::take(&mut state.interval_stats))
+    }
+}
+
+/// The sampling state of a single level
+enum Sampler {
+    OneInN { n: u32, calls: u64 },
+    Probability(f64),
+    TokenBucket { rate: f64, burst: f64, tokens: f64, refilled: Instant },
+}
+
+impl Sampler {
+    fn new(strategy: Strategy, now: Instant) -> Self {
+        match strategy {
+            Strategy::OneInN(n) => Self::OneInN {
+                n,
+                calls: 0,
+            },
+            Strategy::Probability(probability) => Self::Probability(probability),
+            Strategy::TokenBucket {
+                rate,
+                burst,
+            } => Self::TokenBucket {
+                rate,
+                burst: f64::from(burst),
+                tokens: f64::from(burst),
+                refilled: now,
+            },
+        }
+    }
+
+    /// Whether the next message should be logged
+    fn sample(&mut self, now: Instant, rng: &mut SplitMix64) -> bool {
+        match self {
+            Self::OneInN {
+                n,
+                calls,
+            } => {
+                let call = *calls;
+                *calls += 1;
+                *n <= 1 || call % u64::from(*n) == 0
+            }
+            Self::Probability(probability) => rng.next_f64() < *probability,
+            Self::TokenBucket {
+                rate,
+                burst,
+                tokens,
+                refilled,
+            } => {
+                *tokens = (*tokens + now.saturating_duration_since(*refilled).as_secs_f64() * *rate).min(*burst);
+                *refilled = now;
+                if *tokens >= 1.0 {
+                    *tokens -= 1.0;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// A small, seedable pseudo-random number generator, see <https://prng.di.unimi.it/splitmix64.c>
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A uniformly distributed number in `[0, 1)`
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support::{self, ManualClock};
+    use std::sync::Arc;
+
+    #[test]
+    fn strategies() {
+        let now = Instant::now();
+        let mut rng = SplitMix64(7);
+
+        let mut one_in_three = Sampler::new(Strategy::OneInN(3), now);
+        let sampled: Vec<bool> = (0..7).map(|_| one_in_three.sample(now, &mut rng)).collect();
+        assert_eq!(sampled, vec![true, false, false, true, false, false, true]);
+
+        let mut never = Sampler::new(Strategy::Probability(0.0), now);
+        let mut always = Sampler::new(Strategy::Probability(1.0), now);
+        assert!((0..100).all(|_| !never.sample(now, &mut rng) && always.sample(now, &mut rng)));
+
+        let mut half = Sampler::new(Strategy::Probability(0.5), now);
+        let logged = (0..10_000).filter(|_| half.sample(now, &mut rng)).count();
+        assert!((4_500..5_500).contains(&logged), "{} of 10000 sampled", logged);
+
+        let mut bucket = Sampler::new(Strategy::TokenBucket {
+            rate: 2.0,
+            burst: 3,
+        }, now);
+        assert_eq!((0..5).filter(|_| bucket.sample(now, &mut rng)).count(), 3);
+        let later = now + Duration::from_millis(1000);
+        assert_eq!((0..5).filter(|_| bucket.sample(later, &mut rng)).count(), 2);
+        // The bucket doesn't fill beyond the burst size
+        let much_later = later + Duration::from_secs(60);
+        assert_eq!((0..5).filter(|_| bucket.sample(much_later, &mut rng)).count(), 3);
+    }
+
+    #[test]
+    fn sampling_logger() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Sampling").expect("Failed to create capturing logger");
+        let clock = Arc::new(ManualClock::new());
+        logger.set_clock(clock.clone());
+
+        let sampling = SamplingLogger::new(&logger, Strategy::OneInN(4)).level_strategy(LogLevel::Debug, Some(Strategy::Probability(0.5))).seed(42).summary_interval(Duration::from_secs(10));
+        for i in 0..8 {
+            sampling.log(LogLevel::Info, format!("Info {}", i)).expect("Failed to log");
+            sampling.log(LogLevel::Error, format!("Error {}", i)).expect("Failed to log");
+        }
+        assert_eq!(sampling.stats(), SamplingStats {
+            logged: 10,
+            sampled_out: 6,
+        });
+
+        // The same seed samples the same messages
+        let debug_sampled = |seed| {
+            let sampling = SamplingLogger::new(&logger, Strategy::OneInN(1)).level_strategy(LogLevel::Debug, Some(Strategy::Probability(0.5))).seed(seed);
+            (0..32).map(|_| sampling.log(LogLevel::Debug, "Debug").expect("Failed to log")).collect::<Vec<bool>>()
+        };
+        assert_eq!(debug_sampled(42), debug_sampled(42));
+
+        clock.advance(Duration::from_secs(10));
+        sampling.log(LogLevel::Warn, "Warn").expect("Failed to log");
+
+        let lines = output.lines().expect("Failed to read captured output");
+        let sampled: Vec<&String> = lines.iter().filter(|line| !line.starts_with("DEBUG")).collect();
+        assert_eq!(sampled[..4], ["INFO Info 0", "ERROR Error 0", "ERROR Error 1", "ERROR Error 2"]);
+        assert_eq!(sampled.iter().filter(|line| line.starts_with("ERROR")).count(), 8);
+        assert_eq!(sampled.iter().filter(|line| line.starts_with("INFO Info")).count(), 2);
+        assert_eq!(sampled.last().unwrap().as_str(), "INFO Sampling logged 11 of 17 messages (64.7%)");
+    }
+}
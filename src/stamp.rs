@@ -0,0 +1,185 @@
+use crate::logger::JavaLogger;
+use crate::plain::write_seconds;
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The MDC key the Rust-side timestamp is stored under with `TimestampStyle::Mdc`, use `%X{rust.ts}` in a pattern to show it
+pub const RUST_TIME_MDC_KEY: &str = "rust.ts";
+
+/// How a Rust-side timestamp is formatted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// RFC 3339 in UTC with millisecond precision, e.g. `2021-09-14T12:30:05.123Z`
+    Rfc3339,
+    /// Milliseconds since the Unix epoch, e.g. `1631622605123`
+    EpochMillis,
+}
+
+/// Where `stamp_rust_time` puts the Rust-side timestamp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampStyle {
+    /// In the MDC under `RUST_TIME_MDC_KEY`
+    Mdc(TimestampFormat),
+    /// In front of the message, separated by a space
+    Prefix(TimestampFormat),
+}
+
+/// The timestamp configured with `stamp_rust_time`, with the formatting state reused across log calls
+#[derive(Debug)]
+pub(crate) struct RustTimestamp {
+    style: TimestampStyle,
+    /// The second since the Unix epoch `cached_seconds` was formatted for
+    cached_second: Option<u64>,
+    cached_seconds: String,
+    /// The last formatted timestamp
+    buffer: String,
+}
+
+impl RustTimestamp {
+    pub(crate) fn new(style: TimestampStyle) -> Self {
+        Self {
+            style,
+            cached_second: None,
+            cached_seconds: String::new(),
+            buffer: String::new(),
+        }
+    }
+
+    /// Format `time`, reusing the buffer of the previous call.
+    /// With `TimestampFormat::Rfc3339` the date and time up to the second are only formatted once per second
+    pub(crate) fn format(&mut self, time: SystemTime) -> &str {
+        // Times before the Unix epoch are clamped to it
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.buffer.clear();
+        match self.format_kind() {
+            TimestampFormat::Rfc3339 => {
+                let second = since_epoch.as_secs();
+                if self.cached_second != Some(second) {
+                    self.cached_seconds.clear();
+                    write_seconds(second, 'T', &mut self.cached_seconds);
+                    self.cached_second = Some(second);
+                }
+
+                self.buffer.push_str(&self.cached_seconds);
+                let _ = write!(self.buffer, ".{:03}Z", since_epoch.subsec_millis());
+            }
+            TimestampFormat::EpochMillis => self.buffer.push_str(itoa::Buffer::new().format(since_epoch.as_millis())),
+        }
+
+        &self.buffer
+    }
+
+    /// Whether the timestamp goes into the MDC rather than in front of the message
+    pub(crate) fn in_mdc(&self) -> bool {
+        matches!(self.style, TimestampStyle::Mdc(_))
+    }
+
+    fn format_kind(&self) -> TimestampFormat {
+        match self.style {
+            TimestampStyle::Mdc(format) | TimestampStyle::Prefix(format) => format,
+        }
+    }
+}
+
+/// Prefix `message` with the timestamp of `time`, if `stamp` is configured to do so
+pub(crate) fn prefixed<'m>(stamp: &Mutex<RustTimestamp>, time: SystemTime, message: Cow<'m, str>) -> Cow<'m, str> {
+    let mut stamp = stamp.lock().expect("Failed to lock timestamp formatter");
+    if stamp.in_mdc() {
+        return message;
+    }
+
+    let timestamp = stamp.format(time);
+    let mut prefixed = String::with_capacity(timestamp.len() + 1 + message.len());
+    prefixed.push_str(timestamp);
+    prefixed.push(' ');
+    prefixed.push_str(&message);
+    Cow::Owned(prefixed)
+}
+
+impl<'a> JavaLogger<'a> {
+    /// Record the time of every log call as seen by Rust, taken from the configured clock when the call is made.
+    /// Comparing it with log4j's own `%d` shows clock skew between Rust and the JVM,
+    /// and for messages delivered later, e.g. by `log_with_deadline`, how long they were queued.
+    /// A prefix is added after the message pipeline has run, so transforms don't see it
+    ///
+    /// # Params
+    /// - `style` Where to put the timestamp and how to format it
+    pub fn stamp_rust_time(&self, style: TimestampStyle) {
+        self.inner.lock().expect("Failed to lock inner logger").update_pipeline(|pipeline| pipeline.rust_time = Some(Arc::new(Mutex::new(RustTimestamp::new(style)))));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::level::LogLevel;
+    use crate::test::JVM;
+    use crate::test_support;
+    use jni::objects::{JObject, JValue};
+    use jni::JNIEnv;
+    use std::time::Duration;
+
+    /// Pattern printing log4j's timestamp in the same format as `TimestampFormat::Rfc3339`, provided the default time zone is UTC
+    const LOG4J_RFC3339: &str = "%d{yyyy-MM-dd'T'HH:mm:ss.SSS'Z'}";
+
+    /// Set the JVM's default time zone, returning the previous one
+    fn set_default_time_zone<'a>(env: &JNIEnv<'a>, zone: JObject<'a>) -> JObject<'a> {
+        let previous = env.call_static_method("java/util/TimeZone", "getDefault", "()Ljava/util/TimeZone;", &[]).unwrap().l().unwrap();
+        env.call_static_method("java/util/TimeZone", "setDefault", "(Ljava/util/TimeZone;)V", &[JValue::Object(zone)]).unwrap();
+        previous
+    }
+
+    #[test]
+    fn formats() {
+        let time = UNIX_EPOCH + Duration::from_millis(951_782_400_005);
+        let mut stamp = RustTimestamp::new(TimestampStyle::Mdc(TimestampFormat::Rfc3339));
+        assert_eq!(stamp.format(time), "2000-02-29T00:00:00.005Z");
+        assert_eq!(stamp.format(time + Duration::from_millis(995)), "2000-02-29T00:00:01.000Z");
+        assert_eq!(stamp.format(UNIX_EPOCH - Duration::from_secs(1)), "1970-01-01T00:00:00.000Z");
+
+        let mut stamp = RustTimestamp::new(TimestampStyle::Prefix(TimestampFormat::EpochMillis));
+        assert_eq!(stamp.format(time), "951782400005");
+        assert_eq!(prefixed(&Mutex::new(stamp), time, Cow::Borrowed("Started")), "951782400005 Started");
+    }
+
+    #[test]
+    fn rust_time() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+
+        // The date format of %d is created with the layout, in the default time zone
+        let utc = env.new_string("UTC").unwrap();
+        let utc = env.call_static_method("java/util/TimeZone", "getTimeZone", "(Ljava/lang/String;)Ljava/util/TimeZone;", &[JValue::Object(utc.into())]).unwrap().l().unwrap();
+        let previous = set_default_time_zone(&env, utc);
+        let mdc_capture = scope.capture_with_pattern("RustTimeMdc", &format!("%X{{rust.ts}} {} %m%n", LOG4J_RFC3339));
+        let prefix_capture = scope.capture_with_pattern("RustTimePrefix", &format!("{} %m%n", LOG4J_RFC3339));
+        set_default_time_zone(&env, previous);
+        let (mdc_logger, mdc_output) = mdc_capture.expect("Failed to create capturing logger");
+        let (prefix_logger, prefix_output) = prefix_capture.expect("Failed to create capturing logger");
+
+        mdc_logger.stamp_rust_time(TimestampStyle::Mdc(TimestampFormat::Rfc3339));
+        prefix_logger.stamp_rust_time(TimestampStyle::Prefix(TimestampFormat::Rfc3339));
+        mdc_logger.log(LogLevel::Info, "In the MDC").expect("Failed to log");
+        prefix_logger.log(LogLevel::Info, "In the message").expect("Failed to log");
+
+        for (output, message) in [(mdc_output, "In the MDC"), (prefix_output, "In the message")] {
+            let lines = output.lines().expect("Failed to read captured output");
+            let fields: Vec<&str> = lines[0].splitn(3, ' ').collect();
+            let (log4j_time, rust_time) = if message == "In the MDC" {
+                (fields[1], fields[0])
+            } else {
+                (fields[0], fields[1])
+            };
+            assert_eq!(fields[2], message);
+            assert_eq!(rust_time.len(), "2000-02-29T00:00:00.005Z".len(), "Unexpected Rust timestamp {}", rust_time);
+            // Both are formatted identically, so they compare chronologically
+            assert!(log4j_time >= rust_time, "log4j's timestamp {} is before the Rust timestamp {}", log4j_time, rust_time);
+        }
+
+        // The MDC entry only exists during the log call
+        assert_eq!(crate::mdc::get(&env, RUST_TIME_MDC_KEY).expect("Failed to read the MDC"), None);
+    }
+}
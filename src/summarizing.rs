@@ -0,0 +1,186 @@
+use crate::clock::Clock;
+use crate::error::Result;
+use crate::level::LogLevel;
+use crate::logger::JavaLogger;
+use crate::pipeline::RemoteLogger;
+use jni::JNIEnv;
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Occurrences per distinct (level, message) within the current interval
+type Counts = HashMap<(LogLevel, String), u64>;
+
+/// Wrapper around a JavaLogger which suppresses duplicate messages.
+/// The first occurrence of a message is logged immediately, repeats within the same interval are only counted.
+/// A background thread, attached to the JVM, periodically logs `"{message}" occurred {count} times` for every message
+/// which was repeated, after which the interval starts over. Remaining summaries are logged when the wrapper is dropped.
+/// Summaries go through the wrapped logger's message pipeline and circuit breaker, like every other message.
+/// Time is taken from the wrapped logger's clock, see `JavaLogger::set_clock`
+pub struct SummarizingLogger<'a> {
+    logger: JavaLogger<'a>,
+    clock: Arc<dyn Clock>,
+    shared: Arc<(Mutex<State>, Condvar)>,
+    flusher: Option<JoinHandle<()>>,
+}
+
+/// The state shared with the flusher thread
+struct State {
+    counts: Counts,
+    /// When the current interval ends
+    next_flush: Instant,
+    stopped: bool,
+}
+
+impl<'a> SummarizingLogger<'a> {
+    /// Wrap a logger, emitting summaries every `interval`
+    ///
+    /// # Error
+    /// - If the flusher thread could not be attached to the JVM
+    /// - If one of the underlying JNI calls fail
+    pub fn new(logger: &JavaLogger<'a>, interval: Duration) -> Result<Self> {
+        let remote = logger.remote()?;
+        let clock = remote.pipeline().clock.clone();
+        let shared = Arc::new((
+            Mutex::new(State {
+                counts: Counts::new(),
+                next_flush: clock.now() + interval,
+                stopped: false,
+            }),
+            Condvar::new(),
+        ));
+        let (started_tx, started_rx) = mpsc::channel::<Result<()>>();
+
+        let flusher = {
+            let shared = shared.clone();
+            let clock = clock.clone();
+            std::thread::spawn(move || {
+                let env = match remote.vm().attach_current_thread() {
+                    Ok(env) => env,
+                    Err(e) => return started_tx.send(Err(e.into())).unwrap_or_default(),
+                };
+                started_tx.send(Ok(())).unwrap_or_default();
+                flush_periodically(&remote, &env, &*clock, &shared, interval);
+            })
+        };
+
+        started_rx.recv().expect("Flusher thread exited before starting")?;
+        Ok(Self {
+            logger: logger.clone(),
+            clock,
+            shared,
+            flusher: Some(flusher),
+        })
+    }
+
+    /// Log a message, unless it was already logged at the same level in the current interval
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn log<S: AsRef<str>>(&self, level: LogLevel, message: S) -> Result<()> {
+        let now = self.clock.now();
+        let (first, due) = {
+            let mut state = self.shared.0.lock().expect("Failed to lock summarizing state");
+            let count = state.counts.entry((level, message.as_ref().to_string())).or_insert(0);
+            *count += 1;
+            (*count == 1, now >= state.next_flush)
+        };
+
+        if first {
+            self.logger.log(level, message)?;
+        }
+
+        // The flusher waits for the interval in real time, which a clock controlled by a test doesn't follow
+        if due {
+            self.shared.1.notify_all();
+        }
+
+        Ok(())
+    }
+}
+
+/// Log the summaries of every interval until stopped, and the remaining ones once stopped
+fn flush_periodically(remote: &RemoteLogger, env: &JNIEnv, clock: &dyn Clock, shared: &(Mutex<State>, Condvar), interval: Duration) {
+    let (state, condvar) = shared;
+    loop {
+        let (summaries, stopped) = {
+            let mut state = state.lock().expect("Failed to lock summarizing state");
+            loop {
+                let remaining = state.next_flush.saturating_duration_since(clock.now());
+                if state.stopped || remaining.is_zero() {
+                    break;
+                }
+                state = condvar.wait_timeout(state, remaining).expect("Failed to wait for the next interval").0;
+            }
+
+            state.next_flush = clock.now() + interval;
+            (std::mem::take(&mut state.counts), state.stopped)
+        };
+
+        for ((level, message), count) in summaries {
+            if count > 1 {
+                // There is nobody to report a failure to, the next interval will try again
+                let _ = remote.log(env, level, &format!("\"{}\" occurred {} times", message, count));
+            }
+        }
+
+        if stopped {
+            break;
+        }
+    }
+}
+
+impl<'a> Drop for SummarizingLogger<'a> {
+    fn drop(&mut self) {
+        let (state, condvar) = &*self.shared;
+        state.lock().expect("Failed to lock summarizing state").stopped = true;
+        condvar.notify_all();
+
+        if let Some(flusher) = self.flusher.take() {
+            let _ = flusher.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support::{self, ManualClock};
+    use crate::LocationPrefix;
+
+    #[test]
+    fn summary() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Summarizing").expect("Failed to create capturing logger");
+        let clock = Arc::new(ManualClock::new());
+        logger.set_clock(clock.clone());
+        logger.push_transform(Arc::new(LocationPrefix::new("disk")));
+
+        let summarizing = SummarizingLogger::new(&logger, Duration::from_secs(60)).expect("Failed to create SummarizingLogger");
+        for _ in 0..5 {
+            summarizing.log(LogLevel::Warn, "Disk almost full").expect("Failed to log");
+        }
+        clock.advance(Duration::from_secs(60));
+        summarizing.log(LogLevel::Info, "Once").expect("Failed to log");
+
+        // The summary is logged by the flusher thread, woken up by the last call
+        let start = Instant::now();
+        while output.lines().expect("Failed to read captured output").len() < 3 {
+            assert!(start.elapsed() < Duration::from_secs(5), "No summary was logged");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // The summary went through the logger's pipeline
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec![
+            "WARN [disk] Disk almost full",
+            "INFO [disk] Once",
+            "WARN [disk] \"Disk almost full\" occurred 5 times",
+        ]);
+        assert_eq!(logger.counts().warn, 2);
+    }
+}
@@ -0,0 +1,200 @@
+//! Raw JNI calls whose signatures differ between versions of the `jni` crate.
+//! Code outside this module should not use `JMethodID`, `JavaType` or the `*_unchecked` calls directly,
+//! so upgrading the `jni` crate only requires changes here
+
+use crate::error::{Error, Result};
+use jni::objects::{JClass, JMethodID, JObject, JString, JValue};
+use jni::signature::{JavaType, Primitive};
+use jni::JNIEnv;
+use std::convert::TryFrom;
+
+/// A resolved instance method, valid for as long as its class is loaded
+pub(crate) type MethodId<'a> = JMethodID<'a>;
+
+/// Resolve an instance method, so it can be called without looking it up again
+///
+/// # Error
+/// - If the method does not exist
+/// - If one of the underlying JNI calls fail
+pub(crate) fn method_id<'a>(env: &JNIEnv<'a>, class: JClass<'a>, name: &str, signature: &str) -> Result<MethodId<'a>> {
+    Ok(env.get_method_id(class, name, signature)?)
+}
+
+/// Resolve an instance method which may not exist, e.g. because it was added in a later version of log4j
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+pub(crate) fn optional_method_id<'a>(env: &JNIEnv<'a>, class: JClass<'a>, name: &str, signature: &str) -> Result<Option<MethodId<'a>>> {
+    match env.get_method_id(class, name, signature) {
+        Ok(method) => Ok(Some(method)),
+        Err(jni::errors::Error::MethodNotFound { .. }) => {
+            // Clear the pending NoSuchMethodError
+            env.exception_clear()?;
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Create a Java string directly from UTF-16 code units using `NewString`, without converting to modified UTF-8 first
+///
+/// # Error
+/// - If there are more code units than a Java string can hold
+/// - If one of the underlying JNI calls fail
+pub(crate) fn new_string_utf16<'a>(env: &JNIEnv<'a>, units: &[u16]) -> Result<JString<'a>> {
+    let len = i32::try_from(units.len()).map_err(|_| Error::utf16_message_encoding(units))?;
+    let raw = env.get_native_interface();
+    // SAFETY: raw is the valid JNIEnv pointer of the current thread, and the buffer is valid for len code units
+    let string = unsafe {
+        let new_string = (**raw).NewString.ok_or(jni::errors::Error::JNIEnvMethodNotFound("NewString"))?;
+        new_string(raw, units.as_ptr(), len)
+    };
+
+    if string.is_null() {
+        // NewString only fails when the JVM is out of memory, an OutOfMemoryError is pending
+        return Err(jni::errors::Error::JavaException.into());
+    }
+
+    Ok(JString::from(string))
+}
+
+/// Call a resolved instance method returning an object.
+/// The arguments are not checked against the method's signature
+///
+/// # Error
+/// - If the method throws
+/// - If one of the underlying JNI calls fail
+pub(crate) fn call_object_method<'a>(env: &JNIEnv<'a>, object: JObject<'a>, method: MethodId<'a>, args: &[JValue<'a>]) -> Result<JObject<'a>> {
+    Ok(env.call_method_unchecked(object, method, JavaType::Object(String::new()), args)?.l()?)
+}
+
+/// Call a resolved instance method returning `void`.
+/// The arguments are not checked against the method's signature
+///
+/// # Error
+/// - If the method throws
+/// - If one of the underlying JNI calls fail
+pub(crate) fn call_void_method<'a>(env: &JNIEnv<'a>, object: JObject<'a>, method: MethodId<'a>, args: &[JValue<'a>]) -> Result<()> {
+    env.call_method_unchecked(object, method, JavaType::Primitive(Primitive::Void), args)?;
+    Ok(())
+}
+
+/// Capacity requested for local frames, the JVM grows a frame beyond it when needed
+const FRAME_CAPACITY: i32 = 16;
+
+#[cfg(debug_assertions)]
+thread_local! {
+    /// Local frames pushed by this crate on the current thread which have not been popped yet
+    static OPEN_FRAMES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Run `f` in a new local reference frame, so every local reference it creates is freed when it returns.
+/// The result must not contain local references created by `f`, use `local_frame_object` to return an object
+///
+/// # Error
+/// - If `f` fails
+/// - If the frame could not be pushed
+pub(crate) fn local_frame<'a, T, F: FnOnce() -> Result<T>>(env: &JNIEnv<'a>, f: F) -> Result<T> {
+    push_frame(env)?;
+    let result = f();
+    pop_frame(env, JObject::null())?;
+    result
+}
+
+/// Run `f` in a new local reference frame, freeing every local reference it creates except the returned object,
+/// which is moved to the enclosing frame
+///
+/// # Error
+/// - If `f` fails
+/// - If the frame could not be pushed
+pub(crate) fn local_frame_object<'a, F: FnOnce() -> Result<JObject<'a>>>(env: &JNIEnv<'a>, f: F) -> Result<JObject<'a>> {
+    push_frame(env)?;
+    match f() {
+        Ok(object) => pop_frame(env, object),
+        Err(e) => {
+            pop_frame(env, JObject::null())?;
+            Err(e)
+        }
+    }
+}
+
+/// The number of local frames pushed by this crate on the current thread which have not been popped yet.
+/// Zero whenever no call into this crate is in progress, only available with debug assertions
+#[cfg(all(debug_assertions, any(test, feature = "test-util")))]
+pub fn open_local_frames() -> usize {
+    OPEN_FRAMES.with(|frames| frames.get())
+}
+
+fn push_frame(env: &JNIEnv) -> Result<()> {
+    env.push_local_frame(FRAME_CAPACITY)?;
+    #[cfg(debug_assertions)]
+    OPEN_FRAMES.with(|frames| frames.set(frames.get() + 1));
+    Ok(())
+}
+
+fn pop_frame<'a>(env: &JNIEnv<'a>, result: JObject<'a>) -> Result<JObject<'a>> {
+    #[cfg(debug_assertions)]
+    OPEN_FRAMES.with(|frames| frames.set(frames.get() - 1));
+    Ok(env.pop_local_frame(result)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support;
+    use crate::LogLevel;
+
+    const NULL_APPENDER_CLASS: &str = "org/apache/log4j/varia/NullAppender";
+    const SOAK_ITERATIONS: usize = 2000;
+    /// Heap growth allowed during the soak test. A leaked local reference pins its message, which would add up to well over 100 MiB
+    const MAX_HEAP_GROWTH: i64 = 16 * 1024 * 1024;
+
+    /// Heap in use after a garbage collection
+    fn used_heap(env: &JNIEnv) -> i64 {
+        let runtime = env.call_static_method("java/lang/Runtime", "getRuntime", "()Ljava/lang/Runtime;", &[]).unwrap().l().unwrap();
+        env.call_method(runtime, "gc", "()V", &[]).unwrap();
+        let total = env.call_method(runtime, "totalMemory", "()J", &[]).unwrap().j().unwrap();
+        let free = env.call_method(runtime, "freeMemory", "()J", &[]).unwrap().j().unwrap();
+        total - free
+    }
+
+    #[test]
+    fn soak() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let logger = scope.logger("Soak").expect("Failed to create JavaLogger");
+
+        let null_appender = env.new_object(NULL_APPENDER_CLASS, "()V", &[]).unwrap();
+        env.call_method(null_appender, "setName", "(Ljava/lang/String;)V", &[JValue::Object(env.new_string("null").unwrap().into())]).unwrap();
+        scope.add_appender(&logger, null_appender).expect("Failed to add NullAppender");
+        logger.reconfigure(|ctx| crate::appender::set_additivity(ctx.env(), ctx.logger(), false)).unwrap();
+        logger.with_trace_id(|| "trace".to_string());
+        let custom = crate::register_custom_level(&env, "SOAK", 35000, 4).expect("Failed to register custom level");
+        let message = "x".repeat(4096);
+
+        let baseline = used_heap(&env);
+        for _ in 0..SOAK_ITERATIONS {
+            logger.log(LogLevel::Info, &message).unwrap();
+            logger.log(LogLevel::Custom(35000), &message).unwrap();
+            logger.log_at_custom(&custom, &message).unwrap();
+            logger.log_coded_error("E1", &message).unwrap();
+            logger.log_i64(LogLevel::Info, 42).unwrap();
+            assert!(logger.would_log(LogLevel::Info).unwrap());
+
+            let appender = logger.get_appender("null").unwrap().expect("NullAppender is not attached");
+            logger.log_to(&appender, LogLevel::Info, &message).unwrap();
+            env.delete_local_ref(appender.as_jobject()).unwrap();
+
+            assert!(logger.preflight().unwrap().has_appenders());
+            crate::mdc_snapshot(&env).unwrap();
+            crate::java_runtime_info(&env).unwrap();
+            #[cfg(debug_assertions)]
+            assert_eq!(open_local_frames(), 0);
+        }
+
+        let growth = used_heap(&env) - baseline;
+        assert!(growth < MAX_HEAP_GROWTH, "Heap grew by {} bytes", growth);
+    }
+}
@@ -0,0 +1,554 @@
+//! Utilities for testing code which logs through this crate, enabled with the `test-util` feature.
+//!
+//! A single JavaVM is shared by the whole process, with log4j and slf4j on its classpath.
+//! The jars are taken from the directory in `LOG4J_RS_JAR_DIR` if set, otherwise they are downloaded
+//! from Maven Central once and cached in `LOG4J_RS_JAR_CACHE`, defaulting to a directory in the system's temporary directory
+
+use crate::appender;
+use crate::clock::Clock;
+use crate::error::Result;
+use crate::level::LogLevel;
+use crate::logger::JavaLogger;
+use crate::mdc;
+use jni::objects::{JObject, JValue};
+use jni::{InitArgsBuilder, JNIEnv, JNIVersion, JavaVM};
+use lazy_static::lazy_static;
+use proptest::prelude::*;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(debug_assertions)]
+pub use crate::sys::open_local_frames;
+
+/// The jars put on the classpath, as `(file name, download URL)`
+const JARS: [(&str, &str); 3] = [
+    ("slf4j-log4j12-1.7.9.jar", "https://repo1.maven.org/maven2/org/slf4j/slf4j-log4j12/1.7.9/slf4j-log4j12-1.7.9.jar"),
+    ("slf4j-api-1.7.9.jar", "https://repo1.maven.org/maven2/org/slf4j/slf4j-api/1.7.9/slf4j-api-1.7.9.jar"),
+    ("log4j-1.2.9.jar", "https://repo1.maven.org/maven2/log4j/log4j/1.2.9/log4j-1.2.9.jar"),
+];
+/// Directory containing the jars, skips downloading entirely
+const JAR_DIR_ENV: &str = "LOG4J_RS_JAR_DIR";
+/// Directory downloaded jars are cached in
+const JAR_CACHE_ENV: &str = "LOG4J_RS_JAR_CACHE";
+/// The pattern used by `TestJvm::logger`
+const CONSOLE_PATTERN: &str = "%r [%t] %p %c %x - %m%n";
+/// The pattern used by `TestJvm::capture`
+const CAPTURE_PATTERN: &str = "%p %m%n";
+const LOG_MANAGER_CLASS: &str = "org/apache/log4j/LogManager";
+const NDC_CLASS: &str = "org/apache/log4j/NDC";
+
+/// Numbers isolated scopes, so logger names are unique within the process
+static SCOPE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    static ref SHARED: Arc<JavaVM> = Arc::new(TestJvm::create());
+}
+
+/// A clock which only moves when advanced explicitly, for deterministic tests of time-dependent behaviour.
+/// Starts at the real time it was created at
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant,
+    system_start: SystemTime,
+    elapsed: Mutex<Duration>,
+}
+
+impl ManualClock {
+    /// Create a clock standing still at the current time
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            system_start: SystemTime::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Move the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().expect("Failed to lock elapsed time") += duration;
+    }
+
+    fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().expect("Failed to lock elapsed time")
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        self.system_start + self.elapsed()
+    }
+}
+
+/// Access to the JavaVM shared by all tests in the process
+pub struct TestJvm;
+
+impl TestJvm {
+    /// Get the JavaVM shared by the process, creating it on first use
+    ///
+    /// # Panics
+    /// - If the jars cannot be provisioned, or the JavaVM cannot be created
+    pub fn get() -> Arc<JavaVM> {
+        SHARED.clone()
+    }
+
+    /// Create a new JavaVM with log4j and slf4j on the classpath, rather than using the shared one.
+    /// Only one JavaVM can ever exist in a process, this is meant for tests running in a process of their own
+    ///
+    /// # Panics
+    /// - If the jars cannot be provisioned, or the JavaVM cannot be created
+    pub fn create() -> JavaVM {
+        let classpath = std::env::join_paths(jar_paths()).expect("Failed to join classpath");
+        let classpath = classpath.to_str().expect("Failed to convert classpath to &str");
+
+        let jvm_args = InitArgsBuilder::new()
+            .version(JNIVersion::V8)
+            .option("-Xcheck:jni")
+            .option(&format!("-Djava.class.path={}", classpath))
+            .build()
+            .expect("Failed to build JavaVM arguments");
+
+        JavaVM::new(jvm_args).expect("Failed to create JavaVM")
+    }
+
+    /// Create a logger with a ConsoleAppender attached
+    ///
+    /// # Params
+    /// - `env` The JNIEnv of the current thread
+    /// - `name` The name of the logger
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn logger<'a>(env: &'a JNIEnv<'a>, name: &str) -> Result<JavaLogger<'a>> {
+        let logger = JavaLogger::new(env, name)?;
+        let layout = appender::build_layout(env, CONSOLE_PATTERN, false)?;
+        let console_appender = appender::console_appender(env, layout)?;
+        appender::add_appender(env, java_logger(&logger), console_appender)?;
+        Ok(logger)
+    }
+
+    /// Create a logger with an appender attached which captures its output as `%p %m%n`
+    ///
+    /// # Params
+    /// - `env` The JNIEnv of the current thread
+    /// - `name` The name of the logger
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn capture<'a>(env: &'a JNIEnv<'a>, name: &str) -> Result<(JavaLogger<'a>, CapturedOutput<'a>)> {
+        Self::capture_with_pattern(env, name, CAPTURE_PATTERN)
+    }
+
+    /// Create a logger with an appender attached which captures its output using a custom conversion pattern
+    ///
+    /// # Params
+    /// - `env` The JNIEnv of the current thread
+    /// - `name` The name of the logger
+    /// - `pattern` The conversion pattern of the capturing appender
+    ///
+    /// # Error
+    /// - If the pattern is invalid
+    /// - If one of the underlying JNI calls fail
+    pub fn capture_with_pattern<'a>(env: &'a JNIEnv<'a>, name: &str, pattern: &str) -> Result<(JavaLogger<'a>, CapturedOutput<'a>)> {
+        let logger = JavaLogger::new(env, name)?;
+        let (_, output) = attach_capture(env, &logger, pattern)?;
+        Ok((logger, output))
+    }
+}
+
+/// Create a scope which isolates a test from others sharing the JVM.
+/// Loggers created through the scope get unique names, and everything the scope changed is undone when it is dropped:
+/// appenders it added are removed, levels it set are restored, and the MDC and NDC of the current thread are cleared
+///
+/// # Params
+/// - `env` The JNIEnv of the current thread
+/// - `name_prefix` The prefix of all logger names, e.g. `com.example.tests`
+pub fn isolated<'a>(env: &'a JNIEnv<'a>, name_prefix: &str) -> IsolatedScope<'a> {
+    IsolatedScope {
+        env,
+        prefix: format!("{}.scope{}", name_prefix, SCOPE_COUNTER.fetch_add(1, Ordering::Relaxed)),
+        appenders: RefCell::new(Vec::new()),
+        levels: RefCell::new(Vec::new()),
+    }
+}
+
+/// A scope created by `isolated`, which undoes its changes to the log4j configuration on Drop
+pub struct IsolatedScope<'a> {
+    env: &'a JNIEnv<'a>,
+    prefix: String,
+    /// Appenders added by the scope, as `(logger, appender)`
+    appenders: RefCell<Vec<(JObject<'a>, JObject<'a>)>>,
+    /// Levels changed by the scope, as `(logger, previous level)`. The previous level may be null
+    levels: RefCell<Vec<(JObject<'a>, JObject<'a>)>>,
+}
+
+impl<'a> IsolatedScope<'a> {
+    /// The unique logger name for `name` within this scope
+    pub fn logger_name(&self, name: &str) -> String {
+        format!("{}.{}", self.prefix, name)
+    }
+
+    /// Create a logger with a unique name
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn logger(&self, name: &str) -> Result<JavaLogger<'a>> {
+        JavaLogger::new(self.env, self.logger_name(name))
+    }
+
+    /// Create a logger with a unique name and an appender attached which captures its output as `%p %m%n`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn capture(&self, name: &str) -> Result<(JavaLogger<'a>, CapturedOutput<'a>)> {
+        self.capture_with_pattern(name, CAPTURE_PATTERN)
+    }
+
+    /// Create a logger with a unique name and an appender attached which captures its output using a custom conversion pattern
+    ///
+    /// # Error
+    /// - If the pattern is invalid
+    /// - If one of the underlying JNI calls fail
+    pub fn capture_with_pattern(&self, name: &str, pattern: &str) -> Result<(JavaLogger<'a>, CapturedOutput<'a>)> {
+        let logger = self.logger(name)?;
+        let (string_appender, output) = attach_capture(self.env, &logger, pattern)?;
+        self.appenders.borrow_mut().push((java_logger(&logger), string_appender));
+        Ok((logger, output))
+    }
+
+    /// Attach an appender to a logger, it is removed again when the scope is dropped
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn add_appender(&self, logger: &JavaLogger<'a>, appender: JObject<'a>) -> Result<()> {
+        let logger = java_logger(logger);
+        appender::add_appender(self.env, logger, appender)?;
+        self.appenders.borrow_mut().push((logger, appender));
+        Ok(())
+    }
+
+    /// Set the level of a logger, the previous level is restored when the scope is dropped
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn set_level(&self, logger: &JavaLogger<'a>, level: LogLevel) -> Result<()> {
+        let logger = java_logger(logger);
+        let previous = self.env.call_method(logger, "getLevel", "()Lorg/apache/log4j/Level;", &[])?.l()?;
+        set_level(self.env, logger, level.to_java_level(self.env)?)?;
+        self.levels.borrow_mut().push((logger, previous));
+        Ok(())
+    }
+
+    /// Undo all changes, in reverse order
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    fn restore(&self) -> Result<()> {
+        for (logger, appender) in self.appenders.borrow_mut().drain(..).rev() {
+            appender::remove_appender(self.env, logger, appender)?;
+        }
+
+        for (logger, previous) in self.levels.borrow_mut().drain(..).rev() {
+            set_level(self.env, logger, previous)?;
+        }
+
+        for key in mdc::mdc_snapshot(self.env)?.keys() {
+            mdc::remove(self.env, key)?;
+        }
+        self.env.call_static_method(NDC_CLASS, "remove", "()V", &[])?;
+        Ok(())
+    }
+}
+
+impl<'a> Drop for IsolatedScope<'a> {
+    fn drop(&mut self) {
+        // Errors can't be propagated from Drop, restore as much as possible
+        if self.restore().is_err() {
+            let _ = self.env.exception_clear();
+        }
+    }
+}
+
+/// A snapshot of the root logger's configuration, for tests which must change it.
+/// Restoring removes appenders added since the snapshot and re-attaches removed ones.
+/// Appenders closed in the meantime, e.g. by `BasicConfigurator.resetConfiguration()`, are re-attached but stay closed
+pub struct ConfigSnapshot<'a> {
+    env: &'a JNIEnv<'a>,
+    root: JObject<'a>,
+    level: JObject<'a>,
+    threshold: JObject<'a>,
+    appenders: Vec<JObject<'a>>,
+}
+
+impl<'a> ConfigSnapshot<'a> {
+    /// Record the appenders and level of the root logger, and the threshold of the logger repository
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn capture(env: &'a JNIEnv<'a>) -> Result<Self> {
+        let root = env.call_static_method(LOG_MANAGER_CLASS, "getRootLogger", "()Lorg/apache/log4j/Logger;", &[])?.l()?;
+        let repository = env.call_static_method(LOG_MANAGER_CLASS, "getLoggerRepository", "()Lorg/apache/log4j/spi/LoggerRepository;", &[])?.l()?;
+        Ok(Self {
+            env,
+            root,
+            level: env.call_method(root, "getLevel", "()Lorg/apache/log4j/Level;", &[])?.l()?,
+            threshold: env.call_method(repository, "getThreshold", "()Lorg/apache/log4j/Level;", &[])?.l()?,
+            appenders: appender::all_appenders(env, root)?,
+        })
+    }
+
+    /// Restore the root logger and the repository threshold to the recorded state
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn restore(&self) -> Result<()> {
+        let current = appender::all_appenders(self.env, self.root)?;
+        for appender in &current {
+            if !self.contains(&self.appenders, *appender)? {
+                appender::remove_appender(self.env, self.root, *appender)?;
+            }
+        }
+
+        for appender in &self.appenders {
+            if !self.contains(&current, *appender)? {
+                appender::add_appender(self.env, self.root, *appender)?;
+            }
+        }
+
+        set_level(self.env, self.root, self.level)?;
+        let repository = self.env.call_static_method(LOG_MANAGER_CLASS, "getLoggerRepository", "()Lorg/apache/log4j/spi/LoggerRepository;", &[])?.l()?;
+        self.env.call_method(repository, "setThreshold", "(Lorg/apache/log4j/Level;)V", &[JValue::Object(self.threshold)])?;
+        Ok(())
+    }
+
+    /// Whether `appenders` contains `appender`, by identity
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    fn contains(&self, appenders: &[JObject<'a>], appender: JObject<'a>) -> Result<bool> {
+        for candidate in appenders {
+            if self.env.is_same_object(*candidate, appender)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+/// Strategy generating messages which are hard to pass through JNI intact:
+/// arbitrary Unicode, embedded NULs, lone surrogates (replaced by U+FFFD, as in `String::from_utf16_lossy`),
+/// bidirectional overrides, emoji sequences and messages of up to 64 KiB
+pub fn hostile_message() -> BoxedStrategy<String> {
+    prop_oneof![
+        any::<String>(),
+        "[a-z ]{0,16}\\x00[a-z\\x00 ]{0,16}",
+        proptest::collection::vec(any::<u16>(), 0..64).prop_map(|units| String::from_utf16_lossy(&units)),
+        "[a-z \\u{202A}-\\u{202E}\\u{2066}-\\u{2069}]{0,32}",
+        "[\\u{1F468}\\u{1F469}\\u{1F600}-\\u{1F64F}\\u{1F3FB}-\\u{1F3FF}\\u{200D}\\u{FE0F}]{0,32}",
+        (1024usize..65536, any::<char>()).prop_map(|(length, c)| c.to_string().repeat(length / c.len_utf8())),
+    ]
+    .boxed()
+}
+
+/// The output a capture appender with the pattern `%m%n` produces for `message`.
+/// Messages are passed to log4j verbatim, so this is the message followed by the platform's line separator
+pub fn expected_capture(message: &str) -> String {
+    let line_separator = if cfg!(windows) {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    format!("{}{}", message, line_separator)
+}
+
+/// Get the underlying `org.apache.log4j.Logger` of a JavaLogger
+fn java_logger<'a>(logger: &JavaLogger<'a>) -> JObject<'a> {
+    logger.inner.lock().expect("Failed to lock inner logger").logger
+}
+
+/// Set the level of a logger using `Category#setLevel(Level)`, `level` may be null to inherit the parent's level
+///
+/// # Error
+/// - If one of the underlying JNI calls fail
+fn set_level(env: &JNIEnv, logger: JObject, level: JObject) -> Result<()> {
+    env.call_method(logger, "setLevel", "(Lorg/apache/log4j/Level;)V", &[JValue::Object(level)])?;
+    Ok(())
+}
+
+/// Attach an appender to `logger` which writes to a StringWriter, returns the appender and its output
+///
+/// # Error
+/// - If the pattern is invalid
+/// - If one of the underlying JNI calls fail
+fn attach_capture<'a>(env: &'a JNIEnv<'a>, logger: &JavaLogger<'a>, pattern: &str) -> Result<(JObject<'a>, CapturedOutput<'a>)> {
+    let (string_appender, writer) = appender::string_appender(env, pattern)?;
+    appender::add_appender(env, java_logger(logger), string_appender)?;
+    Ok((string_appender, CapturedOutput {
+        env,
+        writer,
+    }))
+}
+
+/// Output captured by the appender attached by `TestJvm::capture`
+pub struct CapturedOutput<'a> {
+    env: &'a JNIEnv<'a>,
+    writer: JObject<'a>,
+}
+
+impl<'a> CapturedOutput<'a> {
+    /// Everything captured so far
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn contents(&self) -> Result<String> {
+        appender::read_string_writer(self.env, self.writer)
+    }
+
+    /// Everything captured so far, split into lines
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn lines(&self) -> Result<Vec<String>> {
+        Ok(self.contents()?.lines().map(String::from).collect())
+    }
+}
+
+/// Get the paths of all jars, either from `LOG4J_RS_JAR_DIR` or the download cache
+///
+/// # Panics
+/// - If a jar is missing from `LOG4J_RS_JAR_DIR`, or cannot be downloaded
+fn jar_paths() -> Vec<PathBuf> {
+    if let Some(dir) = std::env::var_os(JAR_DIR_ENV) {
+        let dir = PathBuf::from(dir);
+        return JARS
+            .iter()
+            .map(|(name, _)| {
+                let path = dir.join(name);
+                assert!(path.is_file(), "{} does not exist, but {} is set", path.display(), JAR_DIR_ENV);
+                path
+            })
+            .collect();
+    }
+
+    let cache = std::env::var_os(JAR_CACHE_ENV).map(PathBuf::from).unwrap_or_else(|| std::env::temp_dir().join("log4j-rs-jars"));
+    std::fs::create_dir_all(&cache).expect("Failed to create jar cache directory");
+    JARS.iter().map(|(name, url)| cached_jar(&cache, name, url)).collect()
+}
+
+/// Get a jar from the cache, downloading it first if it isn't cached yet
+///
+/// # Panics
+/// - If the jar cannot be downloaded or written to the cache
+fn cached_jar(cache: &Path, name: &str, url: &str) -> PathBuf {
+    let path = cache.join(name);
+    if path.is_file() {
+        return path;
+    }
+
+    let response = reqwest::blocking::get(url).and_then(|response| response.error_for_status()).unwrap_or_else(|e| panic!("Failed to download {}: {}", url, e));
+    let content = response.bytes().expect("Failed to read response bytes");
+
+    // Written to a temporary file first, so concurrent test processes never see a partial jar
+    let partial = cache.join(format!("{}.{}.part", name, std::process::id()));
+    std::fs::write(&partial, &content).expect("Failed to write jar to cache");
+    std::fs::rename(&partial, &path).expect("Failed to move jar into cache");
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::Error;
+    use proptest::test_runner::{Config, TestRunner};
+
+    #[test]
+    fn capture() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        let (logger, output) = TestJvm::capture(&env, "com.example.TestSupport").expect("Failed to create capturing logger");
+        logger.log(LogLevel::Warn, "Captured").expect("Failed to log to WARN level");
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec!["WARN Captured"]);
+    }
+
+    #[test]
+    fn manual_clock() {
+        let clock = ManualClock::new();
+        let (start, system_start) = (clock.now(), clock.system_now());
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.now() - start, Duration::from_secs(90));
+        assert_eq!(clock.system_now().duration_since(system_start).unwrap(), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn hostile_message_generator() {
+        let mut runner = TestRunner::deterministic();
+        let messages: Vec<String> = (0..512).map(|_| hostile_message().new_tree(&mut runner).expect("Failed to generate message").current()).collect();
+
+        assert!(messages.iter().any(|message| message.contains('\0')));
+        assert!(messages.iter().any(|message| message.contains('\u{FFFD}')));
+        assert!(messages.iter().any(|message| message.contains('\u{202E}')));
+        assert!(messages.iter().any(|message| message.len() >= 1024));
+        assert_eq!(expected_capture("a\0b"), format!("a\0b{}", if cfg!(windows) { "\r\n" } else { "\n" }));
+    }
+
+    #[test]
+    fn hostile_messages_round_trip() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        let mut runner = TestRunner::new(Config::with_cases(256));
+        runner
+            .run(&hostile_message(), |message| {
+                let scope = isolated(&env, "com.example.Hostile");
+                let (logger, output) = scope.capture_with_pattern("message", "%m%n").expect("Failed to create capturing logger");
+
+                match logger.log(LogLevel::Info, &message) {
+                    Ok(()) => prop_assert_eq!(output.contents().expect("Failed to read captured output"), expected_capture(&message)),
+                    Err(e) => prop_assert!(matches!(e, Error::MessageEncoding { .. }), "Untyped error: {:?}", e),
+                }
+                Ok(())
+            })
+            .expect("Message did not round trip");
+    }
+
+    #[test]
+    fn isolated_scope() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        let (logger, output) = {
+            let scope = isolated(&env, "com.example.Isolated");
+            assert_ne!(scope.logger_name("a"), isolated(&env, "com.example.Isolated").logger_name("a"));
+
+            let (logger, output) = scope.capture("scoped").expect("Failed to create capturing logger");
+            scope.set_level(&logger, LogLevel::Warn).expect("Failed to set level");
+            mdc::put(&env, "user", "alice").expect("Failed to put MDC value");
+
+            logger.log(LogLevel::Info, "Filtered").expect("Failed to log to INFO level");
+            logger.log(LogLevel::Warn, "Kept").expect("Failed to log to WARN level");
+            (logger, output)
+        };
+
+        logger.log(LogLevel::Info, "After scope").expect("Failed to log to INFO level");
+        assert_eq!(output.lines().unwrap(), vec!["WARN Kept"]);
+        assert!(mdc::mdc_snapshot(&env).unwrap().is_empty());
+        assert!(env.call_method(java_logger(&logger), "getLevel", "()Lorg/apache/log4j/Level;", &[]).unwrap().l().unwrap().is_null());
+    }
+}
@@ -0,0 +1,197 @@
+use crate::error::{Error, Result};
+use crate::sys;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use std::convert::TryFrom;
+
+/// The class of throwables created from Rust errors
+pub const DEFAULT_THROWABLE_CLASS: &str = "java.lang.RuntimeException";
+
+const THROWABLE_CLASS: &str = "java/lang/Throwable";
+const STACK_TRACE_ELEMENT_CLASS: &str = "java/lang/StackTraceElement";
+/// The line number `StackTraceElement` uses when it is unknown
+const UNKNOWN_LINE: i32 = -1;
+
+/// A frame of a synthetic stack trace, as shown by `%throwable` or a layout's stack trace output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    /// The path of the function, e.g. `my_crate::module::function`
+    pub function: String,
+    /// The source file, if known
+    pub file: Option<String>,
+    /// The line in the source file, if known
+    pub line: Option<u32>,
+}
+
+/// A `java.lang.Throwable` created from Rust data
+pub struct JavaThrowable<'a> {
+    throwable: JObject<'a>,
+}
+
+impl<'a> JavaThrowable<'a> {
+    /// Create a throwable of class `class` with message `message`, using its `(String)` constructor
+    ///
+    /// # Params
+    /// - `class` The fully qualified name of the class, e.g. `java.lang.IllegalStateException`
+    ///
+    /// # Error
+    /// - `Error::InvalidThrowableClass` if the class does not exist or is not a `Throwable`
+    /// - If one of the underlying JNI calls fail
+    pub fn from_message(env: &JNIEnv<'a>, class: &str, message: &str) -> Result<Self> {
+        let throwable = sys::local_frame_object(env, || {
+            let internal_name = class.replace('.', "/");
+            let java_class = match env.find_class(internal_name.as_str()) {
+                Ok(java_class) => java_class,
+                Err(_) => {
+                    env.exception_clear()?;
+                    return Err(Error::InvalidThrowableClass(class.to_string()));
+                }
+            };
+
+            if !env.is_assignable_from(java_class, THROWABLE_CLASS)? {
+                return Err(Error::InvalidThrowableClass(class.to_string()));
+            }
+
+            let message = env.new_string(message)?;
+            Ok(env.new_object(java_class, "(Ljava/lang/String;)V", &[JValue::Object(message.into())])?)
+        })?;
+
+        Ok(Self { throwable })
+    }
+
+    /// Create a `java.lang.RuntimeException` from a Rust error, with a cause for every error in its `source()` chain
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn from_error(env: &JNIEnv<'a>, error: &dyn std::error::Error) -> Result<Self> {
+        let throwable = Self::from_message(env, DEFAULT_THROWABLE_CLASS, &error.to_string())?;
+        if let Some(source) = error.source() {
+            let cause = Self::from_error(env, source)?;
+            env.call_method(throwable.throwable, "initCause", "(Ljava/lang/Throwable;)Ljava/lang/Throwable;", &[JValue::Object(cause.throwable)])?;
+            env.delete_local_ref(cause.throwable)?;
+        }
+
+        Ok(throwable)
+    }
+
+    /// Replace the stack trace with `frames`, outermost call last, using `Throwable#setStackTrace(StackTraceElement[])`
+    ///
+    /// # Error
+    /// - If one of the underlying JNI calls fail
+    pub fn with_stack(self, env: &JNIEnv<'a>, frames: &[StackFrame]) -> Result<Self> {
+        sys::local_frame(env, || {
+            let elements = env.new_object_array(frames.len() as i32, STACK_TRACE_ELEMENT_CLASS, JObject::null())?;
+            for (index, frame) in frames.iter().enumerate() {
+                let (declaring_class, method, file, line) = java_frame(frame);
+                let file = match file {
+                    Some(file) => env.new_string(file)?.into(),
+                    None => JObject::null(),
+                };
+
+                let element = env.new_object(
+                    STACK_TRACE_ELEMENT_CLASS,
+                    "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;I)V",
+                    &[JValue::Object(env.new_string(declaring_class)?.into()), JValue::Object(env.new_string(method)?.into()), JValue::Object(file), JValue::Int(line)],
+                )?;
+                env.set_object_array_element(elements, index as i32, element)?;
+            }
+
+            env.call_method(self.throwable, "setStackTrace", "([Ljava/lang/StackTraceElement;)V", &[JValue::Object(elements.into())])?;
+            Ok(())
+        })?;
+
+        Ok(self)
+    }
+
+    /// The underlying `java.lang.Throwable`
+    pub fn as_jobject(&self) -> JObject<'a> {
+        self.throwable
+    }
+}
+
+/// Convert a frame to the `StackTraceElement` constructor arguments: declaring class, method name, file name and line number.
+/// The function path is split at its last `::`, a function without a path gets an empty declaring class
+fn java_frame(frame: &StackFrame) -> (&str, &str, Option<&str>, i32) {
+    let (declaring_class, method) = frame.function.rsplit_once("::").unwrap_or(("", &frame.function));
+    let line = frame.line.and_then(|line| i32::try_from(line).ok()).unwrap_or(UNKNOWN_LINE);
+    (declaring_class, method, frame.file.as_deref(), line)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support;
+    use std::fmt;
+
+    fn frame(function: &str, file: Option<&str>, line: Option<u32>) -> StackFrame {
+        StackFrame {
+            function: function.to_string(),
+            file: file.map(str::to_string),
+            line,
+        }
+    }
+
+    #[test]
+    fn frame_conversion() {
+        assert_eq!(java_frame(&frame("my_crate::io::read", Some("src/io.rs"), Some(42))), ("my_crate::io", "read", Some("src/io.rs"), 42));
+        assert_eq!(java_frame(&frame("main", None, None)), ("", "main", None, UNKNOWN_LINE));
+        assert_eq!(java_frame(&frame("<T as Trait>::call", Some("lib.rs"), Some(u32::MAX))), ("<T as Trait>", "call", Some("lib.rs"), UNKNOWN_LINE));
+    }
+
+    #[derive(Debug)]
+    struct ConfigError(std::io::Error);
+
+    impl fmt::Display for ConfigError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Failed to load configuration")
+        }
+    }
+
+    impl std::error::Error for ConfigError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn rendered_stack_trace() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture_with_pattern("Throwable", "%m%n").expect("Failed to create capturing logger");
+
+        let error = ConfigError(std::io::Error::new(std::io::ErrorKind::NotFound, "config.toml not found"));
+        let throwable = JavaThrowable::from_error(&env, &error)
+            .and_then(|throwable| throwable.with_stack(&env, &[frame("app::config::load", Some("src/config.rs"), Some(12)), frame("app::main", Some("src/main.rs"), None)]))
+            .expect("Failed to create throwable");
+
+        let java_logger = logger.inner.lock().unwrap().logger;
+        let message = env.new_string("Startup failed").unwrap();
+        env.call_method(java_logger, "error", "(Ljava/lang/Object;Ljava/lang/Throwable;)V", &[JValue::Object(message.into()), JValue::Object(throwable.as_jobject())]).unwrap();
+
+        let lines = output.lines().expect("Failed to read captured output");
+        assert_eq!(lines[0], "Startup failed");
+        assert_eq!(lines[1], "java.lang.RuntimeException: Failed to load configuration");
+        assert_eq!(lines[2].trim(), "at app::config.load(src/config.rs:12)");
+        assert_eq!(lines[3].trim(), "at app.main(src/main.rs)");
+        assert!(lines.iter().any(|line| line == "Caused by: java.lang.RuntimeException: config.toml not found"));
+    }
+
+    #[test]
+    fn invalid_class() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+
+        for class in ["com.example.DoesNotExist", "java.lang.String"] {
+            match JavaThrowable::from_message(&env, class, "Message") {
+                Err(Error::InvalidThrowableClass(invalid)) => assert_eq!(invalid, class),
+                _ => panic!("Expected InvalidThrowableClass"),
+            }
+            assert!(!env.exception_check().unwrap());
+        }
+
+        let throwable = JavaThrowable::from_message(&env, "java.lang.IllegalStateException", "Message").expect("Failed to create throwable");
+        assert!(env.is_instance_of(throwable.as_jobject(), "java/lang/IllegalStateException").unwrap());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::level::LogLevel;
+#[cfg(feature = "redact")]
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// Replacement for every match of a redaction pattern
+#[cfg(feature = "redact")]
+const REDACTED: &str = "***";
+/// Appended to messages shortened by `Truncate`
+const TRUNCATION_MARKER: &str = "...";
+
+/// A stage of a logger's message pipeline, configured with `JavaLogger::push_transform`.
+///
+/// Messages pass through the pipeline before they are converted to Java strings, in this order:
+/// 1. The `Redactor` configured with `JavaLogger::set_redactor`, so secrets are masked before any other stage can split them
+/// 2. The transforms added with `push_transform`, in the order they were added
+///
+/// Stages should return the message as is when they leave it unchanged, so it is not copied
+pub trait MessageTransform: Send + Sync {
+    /// Transform a message logged at `level`
+    fn apply<'m>(&self, level: LogLevel, message: Cow<'m, str>) -> Cow<'m, str>;
+}
+
+/// Shortens messages longer than `max_bytes` to at most `max_bytes` bytes, cut at a character boundary, followed by `...`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Truncate {
+    max_bytes: usize,
+}
+
+impl Truncate {
+    /// Create a stage keeping at most `max_bytes` bytes of every message
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+        }
+    }
+}
+
+impl MessageTransform for Truncate {
+    fn apply<'m>(&self, _level: LogLevel, message: Cow<'m, str>) -> Cow<'m, str> {
+        if message.len() <= self.max_bytes {
+            return message;
+        }
+
+        let end = (0..=self.max_bytes).rev().find(|&index| message.is_char_boundary(index)).unwrap_or_default();
+        Cow::Owned(format!("{}{}", &message[..end], TRUNCATION_MARKER))
+    }
+}
+
+/// Escapes line breaks as `\n` and `\r`, so a message always renders as a single line
+/// and user input cannot forge additional log lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EscapeNewlines;
+
+impl MessageTransform for EscapeNewlines {
+    fn apply<'m>(&self, _level: LogLevel, message: Cow<'m, str>) -> Cow<'m, str> {
+        if !message.contains(['\n', '\r']) {
+            return message;
+        }
+
+        Cow::Owned(message.replace('\n', "\\n").replace('\r', "\\r"))
+    }
+}
+
+/// Prefixes messages with a location, formatted as `[{location}] {message}`, e.g. the module the logger belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocationPrefix {
+    location: String,
+}
+
+impl LocationPrefix {
+    /// Create a stage prefixing messages with `location`, e.g. `module_path!()`
+    pub fn new<S: Into<String>>(location: S) -> Self {
+        Self {
+            location: location.into(),
+        }
+    }
+}
+
+impl MessageTransform for LocationPrefix {
+    fn apply<'m>(&self, _level: LogLevel, message: Cow<'m, str>) -> Cow<'m, str> {
+        Cow::Owned(format!("[{}] {}", self.location, message))
+    }
+}
+
+/// Masks every match of its patterns with `***`, configured with `JavaLogger::set_redactor`
+#[cfg(feature = "redact")]
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+}
+
+#[cfg(feature = "redact")]
+impl Redactor {
+    /// Create a stage masking every match of `patterns`
+    pub fn new(patterns: Vec<Regex>) -> Self {
+        Self {
+            patterns,
+        }
+    }
+}
+
+#[cfg(feature = "redact")]
+impl MessageTransform for Redactor {
+    fn apply<'m>(&self, _level: LogLevel, mut message: Cow<'m, str>) -> Cow<'m, str> {
+        for pattern in &self.patterns {
+            if let Cow::Owned(redacted) = pattern.replace_all(&message, REDACTED) {
+                message = Cow::Owned(redacted);
+            }
+        }
+
+        message
+    }
+}
+
+/// Run `message` through `stages` in order, without allocating when there are no stages
+pub(crate) fn run_pipeline<'m>(stages: &[Arc<dyn MessageTransform>], level: LogLevel, message: &'m str) -> Cow<'m, str> {
+    stages.iter().fold(Cow::Borrowed(message), |message, stage| stage.apply(level, message))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stages() {
+        assert_eq!(Truncate::new(5).apply(LogLevel::Info, "Hello world".into()), "Hello...");
+        assert_eq!(Truncate::new(5).apply(LogLevel::Info, "Hello".into()), "Hello");
+        // 'é' is 2 bytes, the cut moves back to the boundary before it
+        assert_eq!(Truncate::new(2).apply(LogLevel::Info, "héllo".into()), "h...");
+        assert_eq!(EscapeNewlines.apply(LogLevel::Info, "a\r\nb".into()), "a\\r\\nb");
+        assert_eq!(LocationPrefix::new("app::db").apply(LogLevel::Info, "Connected".into()), "[app::db] Connected");
+    }
+
+    #[test]
+    fn pipeline_order() {
+        let stages: Vec<Arc<dyn MessageTransform>> = vec![Arc::new(EscapeNewlines), Arc::new(Truncate::new(9)), Arc::new(LocationPrefix::new("app"))];
+        assert_eq!(run_pipeline(&stages, LogLevel::Warn, "line one\nline two"), "[app] line one\\...");
+
+        // Truncating before escaping may cut the message differently
+        let stages: Vec<Arc<dyn MessageTransform>> = vec![Arc::new(Truncate::new(9)), Arc::new(EscapeNewlines), Arc::new(LocationPrefix::new("app"))];
+        assert_eq!(run_pipeline(&stages, LogLevel::Warn, "line one\nline two"), "[app] line one\\n...");
+
+        assert!(matches!(run_pipeline(&[], LogLevel::Warn, "unchanged"), Cow::Borrowed("unchanged")));
+    }
+}
@@ -0,0 +1,81 @@
+use crate::level::LogLevel;
+use crate::logger::JavaLogger;
+use std::io::{self, Write};
+
+/// An `io::Write` which logs every complete line written to it, created with `JavaLogger::as_writer`.
+/// Partial lines are buffered until a newline is written, a trailing partial line is logged when the writer is dropped.
+/// Line endings are not part of the logged message, invalid UTF-8 is replaced with `U+FFFD`
+pub struct LoggerWriter<'a> {
+    logger: JavaLogger<'a>,
+    level: LogLevel,
+    buffer: Vec<u8>,
+}
+
+impl<'a> JavaLogger<'a> {
+    /// Get an `io::Write` logging every line written to it at `level`, e.g. for use with `writeln!`
+    pub fn as_writer(&self, level: LogLevel) -> LoggerWriter<'a> {
+        LoggerWriter {
+            logger: self.clone(),
+            level,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl<'a> LoggerWriter<'a> {
+    /// Log a single line, without its line ending
+    fn log_line(&self, line: &[u8]) -> io::Result<()> {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        self.logger.log(self.level, String::from_utf8_lossy(line)).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+}
+
+impl<'a> Write for LoggerWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while let Some(newline) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=newline).collect();
+            self.log_line(&line[..newline])?;
+        }
+
+        Ok(buf.len())
+    }
+
+    /// Partial lines are not flushed, as that would split them over multiple messages
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> Drop for LoggerWriter<'a> {
+    fn drop(&mut self) {
+        if !self.buffer.is_empty() {
+            let _ = self.log_line(&self.buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::JVM;
+    use crate::test_support;
+
+    #[test]
+    fn writeln() {
+        let jvm = JVM.lock().expect("Failed to lock JVM");
+        let env = jvm.attach_current_thread().expect("Failed to attach current thread to the JVM");
+        let scope = test_support::isolated(&env, "com.example");
+        let (logger, output) = scope.capture("Writer").expect("Failed to create capturing logger");
+
+        {
+            let mut writer = logger.as_writer(LogLevel::Warn);
+            writeln!(writer, "First {}", 1).expect("Failed to write");
+            write!(writer, "Second ").expect("Failed to write");
+            assert_eq!(output.lines().expect("Failed to read captured output"), vec!["WARN First 1"]);
+            write!(writer, "line\r\nTrailing").expect("Failed to write");
+        }
+
+        assert_eq!(output.lines().expect("Failed to read captured output"), vec!["WARN First 1", "WARN Second line", "WARN Trailing"]);
+    }
+}